@@ -0,0 +1,371 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    core::{liquidity::modify_liquidity, LiquidityChange, PositionTickArrays},
+    errors::ClmmError,
+    states::{
+        LimitOrder, Pool, TickArrayState, POOL_STATUS_DISABLE_DECREASE_LIQUIDITY,
+        POOL_STATUS_DISABLE_OPEN_POSITION,
+    },
+    utils::{get_amount_0_delta, get_amount_1_delta, tick_to_sqrt_price_x96},
+};
+
+// limit orders are one-spacing ranges opened entirely on one side of the price. the orders a
+// tick fills are grouped in a batch at that tick (TickState::pending_orders): when a swap
+// crosses the tick the whole batch is filled and its liquidity leaves both ticks, so a price
+// coming back cannot turn the output back into the input. batch numbers come from the pool
+// and are never reused, so an order knows it was filled once its tick moved on to another
+// batch, even if the tick array was closed and created again since
+
+// the order's range, which has to fit in one tick array so a swap settling the batch holds
+// both of its ticks
+fn order_range(order: &LimitOrder, tick_spacing: i32) -> Result<(i32, i32)> {
+    let upper_tick = order
+        .tick
+        .checked_add(tick_spacing)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    require!(
+        TickArrayState::get_start_tick_idx(order.tick, tick_spacing)
+            == TickArrayState::get_start_tick_idx(upper_tick, tick_spacing),
+        ClmmError::InvalidLimitOrderTick
+    );
+    Ok((order.tick, upper_tick))
+}
+
+// whether a swap already crossed the order's tick, None meaning the tick array is gone
+fn filled_by_swap(
+    order: &LimitOrder,
+    tick_array: Option<&TickArrayState>,
+    tick_spacing: i32,
+) -> Result<bool> {
+    Ok(match tick_array {
+        Some(tick_array) => {
+            let tick_state =
+                tick_array.get_tick_state(order.settlement_tick(tick_spacing), tick_spacing)?;
+            tick_state.pending_orders(order.zero_for_one).1 != order.batch
+        }
+        // a pending order keeps its ticks initialized, so its array cannot have been closed
+        None => true,
+    })
+}
+
+// adds liquidity to an order the caller has just created with zero liquidity, the caller
+// deposits the token it sells
+pub fn open_limit_order(
+    pool: &mut Pool,
+    order: &mut LimitOrder,
+    tick_array: &mut TickArrayState,
+    liquidity_amount: u128,
+) -> Result<LiquidityChange> {
+    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    pool.require_enabled(POOL_STATUS_DISABLE_OPEN_POSITION)?;
+    let (lower_tick, upper_tick) = order_range(order, pool.tick_spacing)?;
+
+    let on_its_side = if order.zero_for_one {
+        pool.sqrt_price_x96 <= tick_to_sqrt_price_x96(lower_tick)?
+    } else {
+        pool.sqrt_price_x96 >= tick_to_sqrt_price_x96(upper_tick)?
+    };
+    require!(on_its_side, ClmmError::LimitOrderCrossesPrice);
+
+    // join the open batch of the settlement tick or open a new one
+    let settlement_tick = order.settlement_tick(pool.tick_spacing);
+    let tick_state = tick_array.get_tick_state_mut(settlement_tick, pool.tick_spacing)?;
+    let (pending, mut batch) = tick_state.pending_orders(order.zero_for_one);
+    if batch == 0 {
+        pool.limit_order_batch = pool
+            .limit_order_batch
+            .checked_add(1)
+            .ok_or(ClmmError::ArithmeticOverflow)?;
+        batch = pool.limit_order_batch;
+    }
+    let pending = pending
+        .checked_add(liquidity_amount)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    tick_state.set_pending_orders(order.zero_for_one, pending, batch);
+    order.batch = batch;
+
+    let tick_arrays = PositionTickArrays {
+        lower: tick_array,
+        upper: None,
+    };
+    let range = (lower_tick, upper_tick);
+    modify_liquidity(pool, range, &mut order.liquidity, tick_arrays, liquidity_amount, true)
+}
+
+// withdraws an order that no swap has filled yet, partially converted or not
+pub fn cancel_limit_order(
+    pool: &mut Pool,
+    order: &mut LimitOrder,
+    tick_array: &mut TickArrayState,
+) -> Result<LiquidityChange> {
+    pool.require_enabled(POOL_STATUS_DISABLE_DECREASE_LIQUIDITY)?;
+    require!(
+        !filled_by_swap(order, Some(tick_array), pool.tick_spacing)?,
+        ClmmError::LimitOrderFilled
+    );
+    withdraw_pending_order(pool, order, tick_array)
+}
+
+// pays out a filled order, which holds only the token it bought. a batch crossed by a swap
+// already left the ticks; an order whose range the price has reached the end of without
+// crossing it yet is withdrawn from the ticks here
+pub fn settle_limit_order(
+    pool: &mut Pool,
+    order: &mut LimitOrder,
+    tick_array: Option<&mut TickArrayState>,
+) -> Result<LiquidityChange> {
+    pool.require_enabled(POOL_STATUS_DISABLE_DECREASE_LIQUIDITY)?;
+    let (lower_tick, upper_tick) = order_range(order, pool.tick_spacing)?;
+    let lower_sqrt = tick_to_sqrt_price_x96(lower_tick)?;
+    let upper_sqrt = tick_to_sqrt_price_x96(upper_tick)?;
+
+    match tick_array {
+        Some(tick_array) if !filled_by_swap(order, Some(tick_array), pool.tick_spacing)? => {
+            let at_the_end = if order.zero_for_one {
+                pool.sqrt_price_x96 >= upper_sqrt
+            } else {
+                pool.sqrt_price_x96 <= lower_sqrt
+            };
+            require!(at_the_end, ClmmError::LimitOrderNotFilled);
+            withdraw_pending_order(pool, order, tick_array)
+        }
+        _ => {
+            let liquidity = std::mem::take(&mut order.liquidity);
+            let (amount_0, amount_1) = if order.zero_for_one {
+                (0, get_amount_1_delta(lower_sqrt, upper_sqrt, liquidity, false)?)
+            } else {
+                (get_amount_0_delta(lower_sqrt, upper_sqrt, liquidity, false)?, 0)
+            };
+            Ok(LiquidityChange {
+                amount_0: amount_0.try_into().map_err(|_| ClmmError::ArithmeticOverflow)?,
+                amount_1: amount_1.try_into().map_err(|_| ClmmError::ArithmeticOverflow)?,
+                lower_array_emptied: false,
+                upper_array_emptied: false,
+            })
+        }
+    }
+}
+
+// removes a pending order from its batch and its liquidity from the ticks
+fn withdraw_pending_order(
+    pool: &mut Pool,
+    order: &mut LimitOrder,
+    tick_array: &mut TickArrayState,
+) -> Result<LiquidityChange> {
+    let (lower_tick, upper_tick) = order_range(order, pool.tick_spacing)?;
+    let liquidity_amount = order.liquidity;
+
+    let settlement_tick = order.settlement_tick(pool.tick_spacing);
+    let tick_state = tick_array.get_tick_state_mut(settlement_tick, pool.tick_spacing)?;
+    let (pending, batch) = tick_state.pending_orders(order.zero_for_one);
+    let pending = pending
+        .checked_sub(liquidity_amount)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    tick_state.set_pending_orders(order.zero_for_one, pending, batch);
+
+    let tick_arrays = PositionTickArrays {
+        lower: tick_array,
+        upper: None,
+    };
+    let range = (lower_tick, upper_tick);
+    modify_liquidity(pool, range, &mut order.liquidity, tick_arrays, liquidity_amount, false)
+}
+
+// fills the batch of orders at a tick a swap has just crossed: orders selling token_0 when the
+// price rose through it, token_1 when it fell. the crossing already took their liquidity out
+// of the pool's, here it leaves the ticks so crossing back does not bring it back
+pub fn settle_crossed_orders(
+    tick_array: &mut TickArrayState,
+    tick: i32,
+    tick_spacing: i32,
+    price_up: bool,
+) -> Result<()> {
+    let zero_for_one = price_up;
+    let tick_state = tick_array.get_tick_state_mut(tick, tick_spacing)?;
+    let (liquidity, batch) = tick_state.pending_orders(zero_for_one);
+    if batch == 0 {
+        return Ok(());
+    }
+    tick_state.set_pending_orders(zero_for_one, 0, 0);
+
+    let (lower_tick, upper_tick) = if zero_for_one {
+        (tick - tick_spacing, tick)
+    } else {
+        (tick, tick + tick_spacing)
+    };
+    let liquidity_delta = i128::try_from(liquidity).map_err(|_| ClmmError::ArithmeticOverflow)?;
+    PositionTickArrays {
+        lower: tick_array,
+        upper: None,
+    }
+    .update_ticks(lower_tick, upper_tick, tick_spacing, -liquidity_delta)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{open_position, swap},
+        test_utils::{pool_at_tick, position, tick_array},
+    };
+
+    const SPACING: i32 = 10;
+    const LP_LIQUIDITY: u128 = 10_000_000_000;
+    const ORDER_LIQUIDITY: u128 = 100_000_000;
+
+    struct Setup {
+        pool: Pool,
+        below: TickArrayState,
+        above: TickArrayState,
+    }
+
+    // a pool at tick 0 with an LP position over [-100, 100], across the arrays at -600 and 0
+    fn setup() -> Setup {
+        let mut pool = pool_at_tick(0, SPACING);
+        let (mut below, mut above) = (tick_array(-600), tick_array(0));
+
+        let mut position = position(-100, 100);
+        let tick_arrays = PositionTickArrays {
+            lower: &mut below,
+            upper: Some(&mut above),
+        };
+        open_position(&mut pool, &mut position, tick_arrays, LP_LIQUIDITY).unwrap();
+        Setup { pool, below, above }
+    }
+
+    fn order(tick: i32, zero_for_one: bool) -> LimitOrder {
+        LimitOrder {
+            pool: Pubkey::default(),
+            owner: Pubkey::default(),
+            tick,
+            zero_for_one,
+            liquidity: 0,
+            batch: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_crossing_fills_the_batch_for_good() {
+        let Setup {
+            mut pool,
+            mut above,
+            ..
+        } = setup();
+        let mut first = order(20, true);
+        let mut second = order(20, true);
+        let opened = open_limit_order(&mut pool, &mut first, &mut above, ORDER_LIQUIDITY).unwrap();
+        open_limit_order(&mut pool, &mut second, &mut above, ORDER_LIQUIDITY).unwrap();
+        // an order above the price only takes token_0, both share the batch at tick 30
+        assert!(opened.amount_0 > 0 && opened.amount_1 == 0);
+        assert_eq!(first.batch, second.batch);
+        assert_eq!(
+            above.get_tick_state(30, SPACING).unwrap().pending_orders(true),
+            (2 * ORDER_LIQUIDITY, first.batch)
+        );
+
+        // up to tick 30, the last step runs through the orders and crosses their upper tick
+        for tick in [10, 20, 30] {
+            let result = swap(&mut pool, Some(&mut above), u32::MAX as u64, false).unwrap();
+            assert_eq!(result.tick, tick);
+        }
+        assert_eq!(pool.global_liquidity, LP_LIQUIDITY);
+        assert_eq!(above.get_tick_state(30, SPACING).unwrap().pending_orders(true), (0, 0));
+        assert!(!above.get_tick_state(20, SPACING).unwrap().is_initialized());
+
+        // coming back down does not turn the orders back into token_0
+        swap(&mut pool, Some(&mut above), u32::MAX as u64, true).unwrap();
+        swap(&mut pool, Some(&mut above), u32::MAX as u64, true).unwrap();
+        assert_eq!(pool.current_tick, 10);
+        assert_eq!(pool.global_liquidity, LP_LIQUIDITY);
+
+        let result = cancel_limit_order(&mut pool, &mut first, &mut above);
+        assert_eq!(result.unwrap_err(), ClmmError::LimitOrderFilled.into());
+        let settled = settle_limit_order(&mut pool, &mut first, Some(&mut above)).unwrap();
+        assert_eq!(settled.amount_0, 0);
+        assert!(settled.amount_1 > opened.amount_0, "bought token_1 above a price of 1");
+        assert_eq!(first.liquidity, 0);
+        // the array may be closed before the owner settles
+        assert_eq!(settle_limit_order(&mut pool, &mut second, None).unwrap(), settled);
+    }
+
+    #[test]
+    fn test_order_reached_but_not_crossed_settles_from_the_ticks() {
+        let Setup {
+            mut pool,
+            mut below,
+            mut above,
+        } = setup();
+        let mut order = order(-20, false);
+        let opened = open_limit_order(&mut pool, &mut order, &mut below, ORDER_LIQUIDITY).unwrap();
+        assert!(opened.amount_0 == 0 && opened.amount_1 > 0);
+
+        let result = settle_limit_order(&mut pool, &mut order, Some(&mut below));
+        assert_eq!(result.unwrap_err(), ClmmError::LimitOrderNotFilled.into());
+
+        // down to tick -20, the lower end of the order, without crossing it
+        swap(&mut pool, Some(&mut above), u32::MAX as u64, true).unwrap();
+        swap(&mut pool, Some(&mut below), u32::MAX as u64, true).unwrap();
+        assert_eq!(pool.current_tick, -20);
+        assert_eq!(pool.global_liquidity, LP_LIQUIDITY + ORDER_LIQUIDITY);
+
+        let settled = settle_limit_order(&mut pool, &mut order, Some(&mut below)).unwrap();
+        assert!(settled.amount_0 > 0 && settled.amount_1 == 0);
+        assert_eq!(pool.global_liquidity, LP_LIQUIDITY);
+        assert_eq!(below.get_tick_state(-20, SPACING).unwrap().pending_orders(false).0, 0);
+    }
+
+    #[test]
+    fn test_partially_filled_order_cancels_with_both_tokens() {
+        let Setup {
+            mut pool,
+            mut below,
+            mut above,
+        } = setup();
+        let mut order = order(-20, false);
+        open_limit_order(&mut pool, &mut order, &mut below, ORDER_LIQUIDITY).unwrap();
+
+        // down to the order's upper tick, then across it and partway into the order
+        swap(&mut pool, Some(&mut above), u32::MAX as u64, true).unwrap();
+        swap(&mut pool, Some(&mut below), 1_000, true).unwrap();
+        assert_eq!(pool.current_tick, -11);
+
+        let cancelled = cancel_limit_order(&mut pool, &mut order, &mut below).unwrap();
+        assert!(cancelled.amount_0 > 0 && cancelled.amount_1 > 0);
+        assert!(!cancelled.lower_array_emptied);
+        assert_eq!(pool.global_liquidity, LP_LIQUIDITY);
+    }
+
+    #[test]
+    fn test_order_placement_rules() {
+        let Setup {
+            mut pool,
+            mut below,
+            mut above,
+        } = setup();
+
+        // selling token_0 below the price, or token_1 above it, would fill right away
+        let result = open_limit_order(&mut pool, &mut order(-20, true), &mut below, 1);
+        assert_eq!(result.unwrap_err(), ClmmError::LimitOrderCrossesPrice.into());
+        let result = open_limit_order(&mut pool, &mut order(0, false), &mut above, 1);
+        assert_eq!(result.unwrap_err(), ClmmError::LimitOrderCrossesPrice.into());
+        // [590, 600] would span the arrays at 0 and 600, [-10, 0] the arrays at -600 and 0
+        let result = open_limit_order(&mut pool, &mut order(590, true), &mut above, 1);
+        assert_eq!(result.unwrap_err(), ClmmError::InvalidLimitOrderTick.into());
+        let result = open_limit_order(&mut pool, &mut order(-10, false), &mut below, 1);
+        assert_eq!(result.unwrap_err(), ClmmError::InvalidLimitOrderTick.into());
+        let result = open_limit_order(&mut pool, &mut order(20, true), &mut above, 0);
+        assert_eq!(result.unwrap_err(), ClmmError::ZeroAmount.into());
+
+        // a batch stays open while it has orders and a new one starts once it is filled
+        let mut first = order(0, true);
+        open_limit_order(&mut pool, &mut first, &mut above, 1).unwrap();
+        assert_eq!((first.batch, pool.limit_order_batch), (1, 1));
+        swap(&mut pool, Some(&mut above), u32::MAX as u64, false).unwrap();
+        let mut next = order(10, true);
+        open_limit_order(&mut pool, &mut next, &mut above, 1).unwrap();
+        assert_eq!(next.batch, 2);
+    }
+}
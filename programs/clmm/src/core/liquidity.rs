@@ -0,0 +1,335 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    states::{
+        Pool, Position, TickArrayState, POOL_STATUS_DISABLE_DECREASE_LIQUIDITY,
+        POOL_STATUS_DISABLE_INCREASE_LIQUIDITY, POOL_STATUS_DISABLE_OPEN_POSITION,
+    },
+    utils::{get_amounts_for_liquidity, tick_to_sqrt_price_x96, validate_position_range},
+};
+
+// the tick arrays holding a position's lower and upper tick
+// `upper` is None when the range fits in a single array and both ticks live in `lower`
+pub struct PositionTickArrays<'a> {
+    pub lower: &'a mut TickArrayState,
+    pub upper: Option<&'a mut TickArrayState>,
+}
+
+impl PositionTickArrays<'_> {
+    // applies a liquidity change to both ticks of a position and returns, for the lower and
+    // upper tick, whether its array was left without any initialized tick
+    pub(super) fn update_ticks(
+        &mut self,
+        lower_tick: i32,
+        upper_tick: i32,
+        tick_spacing: i32,
+        liquidity_delta: i128,
+    ) -> Result<(bool, bool)> {
+        let lower_flipped = self
+            .lower
+            .get_tick_state_mut(lower_tick, tick_spacing)?
+            .update_liquidity(liquidity_delta, true)?;
+        let upper_flipped = self
+            .upper
+            .as_deref_mut()
+            .unwrap_or(&mut *self.lower)
+            .get_tick_state_mut(upper_tick, tick_spacing)?
+            .update_liquidity(liquidity_delta, false)?;
+
+        Ok(match self.upper.as_deref() {
+            Some(upper) => (
+                lower_flipped && self.lower.is_empty(),
+                upper_flipped && upper.is_empty(),
+            ),
+            None => {
+                let emptied = (lower_flipped || upper_flipped) && self.lower.is_empty();
+                (emptied, emptied)
+            }
+        })
+    }
+}
+
+// what a liquidity change moves: the token amounts paid into the vaults by open_position and
+// increase_liquidity, or out of them by decrease_liquidity and close_position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidityChange {
+    pub amount_0: u64,
+    pub amount_1: u64,
+    // whether the array holding the lower / upper tick was left without any initialized tick
+    pub lower_array_emptied: bool,
+    pub upper_array_emptied: bool,
+}
+
+// adds liquidity to a position the caller has just created with zero liquidity
+pub fn open_position(
+    pool: &mut Pool,
+    position: &mut Position,
+    tick_arrays: PositionTickArrays,
+    liquidity_amount: u128,
+) -> Result<LiquidityChange> {
+    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    pool.require_enabled(POOL_STATUS_DISABLE_OPEN_POSITION)?;
+    let range = (position.lower_tick, position.upper_tick);
+    modify_liquidity(pool, range, &mut position.liquidity, tick_arrays, liquidity_amount, true)
+}
+
+pub fn increase_liquidity(
+    pool: &mut Pool,
+    position: &mut Position,
+    tick_arrays: PositionTickArrays,
+    liquidity_amount: u128,
+) -> Result<LiquidityChange> {
+    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    pool.require_enabled(POOL_STATUS_DISABLE_INCREASE_LIQUIDITY)?;
+    let range = (position.lower_tick, position.upper_tick);
+    modify_liquidity(pool, range, &mut position.liquidity, tick_arrays, liquidity_amount, true)
+}
+
+pub fn decrease_liquidity(
+    pool: &mut Pool,
+    position: &mut Position,
+    tick_arrays: PositionTickArrays,
+    liquidity_amount: u128,
+) -> Result<LiquidityChange> {
+    pool.require_enabled(POOL_STATUS_DISABLE_DECREASE_LIQUIDITY)?;
+    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    require!(
+        liquidity_amount <= position.liquidity,
+        ClmmError::InvalidAmount
+    );
+    let range = (position.lower_tick, position.upper_tick);
+    modify_liquidity(pool, range, &mut position.liquidity, tick_arrays, liquidity_amount, false)
+}
+
+// withdraws whatever liquidity is left before the position is closed
+pub fn close_position(
+    pool: &mut Pool,
+    position: &mut Position,
+    tick_arrays: PositionTickArrays,
+) -> Result<LiquidityChange> {
+    pool.require_enabled(POOL_STATUS_DISABLE_DECREASE_LIQUIDITY)?;
+    let liquidity_amount = position.liquidity;
+    let range = (position.lower_tick, position.upper_tick);
+    modify_liquidity(pool, range, &mut position.liquidity, tick_arrays, liquidity_amount, false)
+}
+
+// carves `liquidity_amount` off `from` into `to`, a position on the same range
+pub fn split_position(
+    from: &mut Position,
+    to: &mut Position,
+    liquidity_amount: u128,
+) -> Result<()> {
+    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    require!(liquidity_amount <= from.liquidity, ClmmError::InvalidAmount);
+    move_liquidity(from, to, liquidity_amount)
+}
+
+// moves all of `from`'s liquidity into `to`, a position on the same range, before `from` is
+// closed
+pub fn merge_positions(from: &mut Position, to: &mut Position) -> Result<()> {
+    let liquidity_amount = from.liquidity;
+    move_liquidity(from, to, liquidity_amount)
+}
+
+// the ticks only track the liquidity over a range, not who holds it, so moving it between two
+// positions on the same range leaves them and the pool's active liquidity untouched
+fn move_liquidity(from: &mut Position, to: &mut Position, liquidity_amount: u128) -> Result<()> {
+    require!(
+        from.pool == to.pool
+            && from.lower_tick == to.lower_tick
+            && from.upper_tick == to.upper_tick,
+        ClmmError::InvalidPositionRange
+    );
+    from.liquidity = from
+        .liquidity
+        .checked_sub(liquidity_amount)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    to.liquidity = to
+        .liquidity
+        .checked_add(liquidity_amount)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+// applies a liquidity change to the range's ticks, the liquidity held over it and, while the
+// range holds the price, the pool's active liquidity
+pub(super) fn modify_liquidity(
+    pool: &mut Pool,
+    (lower_tick, upper_tick): (i32, i32),
+    liquidity: &mut u128,
+    mut tick_arrays: PositionTickArrays,
+    liquidity_amount: u128,
+    add: bool,
+) -> Result<LiquidityChange> {
+    validate_position_range(lower_tick, upper_tick, pool.tick_spacing)?;
+
+    let liquidity_delta =
+        i128::try_from(liquidity_amount).map_err(|_| ClmmError::ArithmeticOverflow)?;
+    let liquidity_delta = if add { liquidity_delta } else { -liquidity_delta };
+    let (lower_array_emptied, upper_array_emptied) =
+        tick_arrays.update_ticks(lower_tick, upper_tick, pool.tick_spacing, liquidity_delta)?;
+
+    let apply = |held: u128| {
+        if add {
+            held.checked_add(liquidity_amount)
+        } else {
+            held.checked_sub(liquidity_amount)
+        }
+        .ok_or(ClmmError::ArithmeticOverflow)
+    };
+    *liquidity = apply(*liquidity)?;
+
+    let lower_sqrt = tick_to_sqrt_price_x96(lower_tick)?;
+    let upper_sqrt = tick_to_sqrt_price_x96(upper_tick)?;
+    if pool.sqrt_price_x96 >= lower_sqrt && pool.sqrt_price_x96 < upper_sqrt {
+        pool.global_liquidity = apply(pool.global_liquidity)?;
+    }
+
+    let (amount_0, amount_1) = get_amounts_for_liquidity(
+        pool.sqrt_price_x96,
+        lower_sqrt,
+        upper_sqrt,
+        liquidity_amount,
+        add,
+    )?;
+
+    Ok(LiquidityChange {
+        amount_0,
+        amount_1,
+        lower_array_emptied,
+        upper_array_emptied,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{self, position, tick_array};
+
+    const SPACING: i32 = 10;
+
+    fn pool_at_tick(tick: i32) -> Pool {
+        test_utils::pool_at_tick(tick, SPACING)
+    }
+
+    #[test]
+    fn test_open_and_close_across_two_arrays() {
+        let mut pool = pool_at_tick(0);
+        let mut position = position(-100, 100);
+        let (mut lower, mut upper) = (tick_array(-600), tick_array(0));
+
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: Some(&mut upper),
+        };
+        let opened = open_position(&mut pool, &mut position, tick_arrays, 1_000_000).unwrap();
+        assert_eq!(position.liquidity, 1_000_000);
+        assert_eq!(pool.global_liquidity, 1_000_000);
+        assert!(!opened.lower_array_emptied && !opened.upper_array_emptied);
+
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: Some(&mut upper),
+        };
+        let closed = close_position(&mut pool, &mut position, tick_arrays).unwrap();
+        assert_eq!(position.liquidity, 0);
+        assert_eq!(pool.global_liquidity, 0);
+        assert!(closed.lower_array_emptied && closed.upper_array_emptied);
+        // deposits round up and withdrawals down
+        assert_eq!(opened.amount_0, closed.amount_0 + 1);
+        assert_eq!(opened.amount_1, closed.amount_1 + 1);
+    }
+
+    #[test]
+    fn test_open_and_close_in_a_shared_array() {
+        let mut pool = pool_at_tick(0);
+        let mut position = position(-300, -200);
+        let mut array = tick_array(-600);
+
+        let tick_arrays = PositionTickArrays {
+            lower: &mut array,
+            upper: None,
+        };
+        let opened = open_position(&mut pool, &mut position, tick_arrays, 500).unwrap();
+        // the range is below the price, so it only holds token_1 and is not active
+        assert_eq!(opened.amount_0, 0);
+        assert_eq!(pool.global_liquidity, 0);
+        assert_eq!({ array.get_tick_state(-300, SPACING).unwrap().net_liquidity }, 500);
+        assert_eq!({ array.get_tick_state(-200, SPACING).unwrap().net_liquidity }, -500);
+
+        let tick_arrays = PositionTickArrays {
+            lower: &mut array,
+            upper: None,
+        };
+        let closed = close_position(&mut pool, &mut position, tick_arrays).unwrap();
+        assert!(closed.lower_array_emptied && closed.upper_array_emptied);
+        assert!(array.is_empty());
+    }
+
+    #[test]
+    fn test_liquidity_errors() {
+        let mut pool = pool_at_tick(0);
+        let mut position = position(-300, -200);
+        let mut array = tick_array(-600);
+        fn shared(array: &mut TickArrayState) -> PositionTickArrays<'_> {
+            PositionTickArrays {
+                lower: array,
+                upper: None,
+            }
+        }
+
+        let result = open_position(&mut pool, &mut position, shared(&mut array), 0);
+        assert_eq!(result.unwrap_err(), ClmmError::ZeroAmount.into());
+        increase_liquidity(&mut pool, &mut position, shared(&mut array), 100).unwrap();
+        let result = decrease_liquidity(&mut pool, &mut position, shared(&mut array), 101);
+        assert_eq!(result.unwrap_err(), ClmmError::InvalidAmount.into());
+
+        pool.status = POOL_STATUS_DISABLE_DECREASE_LIQUIDITY;
+        let result = decrease_liquidity(&mut pool, &mut position, shared(&mut array), 100);
+        assert_eq!(result.unwrap_err(), ClmmError::PoolOperationDisabled.into());
+        let result = close_position(&mut pool, &mut position, shared(&mut array));
+        assert_eq!(result.unwrap_err(), ClmmError::PoolOperationDisabled.into());
+    }
+
+    #[test]
+    fn test_split_and_merge_keep_the_ticks() {
+        let mut pool = pool_at_tick(0);
+        let mut array = tick_array(-600);
+        let mut position = position(-300, -200);
+        let tick_arrays = PositionTickArrays {
+            lower: &mut array,
+            upper: None,
+        };
+        open_position(&mut pool, &mut position, tick_arrays, 1_000).unwrap();
+        let ticks_before = (
+            array.get_tick_state(-300, SPACING).unwrap().net_liquidity,
+            array.get_tick_state(-200, SPACING).unwrap().net_liquidity,
+        );
+
+        let mut other = Position {
+            owner: Pubkey::new_unique(),
+            ..position.clone()
+        };
+        other.liquidity = 0;
+        let result = split_position(&mut position, &mut other, 1_001);
+        assert_eq!(result.unwrap_err(), ClmmError::InvalidAmount.into());
+        split_position(&mut position, &mut other, 400).unwrap();
+        assert_eq!((position.liquidity, other.liquidity), (600, 400));
+
+        let mut elsewhere = Position {
+            lower_tick: -400,
+            ..other.clone()
+        };
+        let result = merge_positions(&mut position, &mut elsewhere);
+        assert_eq!(result.unwrap_err(), ClmmError::InvalidPositionRange.into());
+        merge_positions(&mut other, &mut position).unwrap();
+        assert_eq!((position.liquidity, other.liquidity), (1_000, 0));
+
+        let ticks_after = (
+            array.get_tick_state(-300, SPACING).unwrap().net_liquidity,
+            array.get_tick_state(-200, SPACING).unwrap().net_liquidity,
+        );
+        assert_eq!(ticks_before, ticks_after);
+    }
+}
@@ -0,0 +1,13 @@
+// the program's state transitions as plain functions on Pool, Position and TickArrayState,
+// without accounts or CPIs. each one returns the token amounts its caller has to move: the
+// instruction handlers load and check accounts, call into here and carry out the transfers,
+// and off-chain code such as an SDK or a simulator can run the same logic
+pub mod limit_order;
+pub mod liquidity;
+pub mod reposition;
+pub mod swap;
+
+pub use limit_order::*;
+pub use liquidity::*;
+pub use reposition::*;
+pub use swap::*;
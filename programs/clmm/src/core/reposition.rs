@@ -0,0 +1,96 @@
+use crate::core::{LiquidityChange, SwapResult};
+
+// what a reposition owes its owner, netted over its steps: withdrawing the old range, an
+// optional swap against the pool and depositing into the new range. a positive amount is paid
+// out to the owner and a negative one paid in, the tokens in between never leave the vaults
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RepositionBalance {
+    pub amount_0: i128,
+    pub amount_1: i128,
+}
+
+impl RepositionBalance {
+    pub fn withdraw(&mut self, change: &LiquidityChange) {
+        self.amount_0 += i128::from(change.amount_0);
+        self.amount_1 += i128::from(change.amount_1);
+    }
+
+    // the swap spends from the balance and adds its output to it
+    pub fn swap(&mut self, result: &SwapResult, a_to_b: bool) {
+        let amount_in = i128::from(result.amount_in);
+        let amount_out = i128::from(result.amount_out);
+        if a_to_b {
+            self.amount_0 -= amount_in;
+            self.amount_1 += amount_out;
+        } else {
+            self.amount_1 -= amount_in;
+            self.amount_0 += amount_out;
+        }
+    }
+
+    pub fn deposit(&mut self, change: &LiquidityChange) {
+        self.amount_0 -= i128::from(change.amount_0);
+        self.amount_1 -= i128::from(change.amount_1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{close_position, open_position, swap, PositionTickArrays},
+        test_utils::{pool_at_tick, position, tick_array},
+    };
+
+    const SPACING: i32 = 10;
+    const LIQUIDITY: u128 = 1_000_000_000;
+
+    // moves a position over [-100, 100] to [20, 120], which only holds token_0, swapping the
+    // withdrawn token_1 into token_0 on the way
+    #[test]
+    fn test_reposition_nets_its_steps() {
+        let mut pool = pool_at_tick(0, SPACING);
+        let (mut below, mut above) = (tick_array(-600), tick_array(0));
+        // another LP keeps liquidity around the price for the swap
+        let mut other = position(-100, 100);
+        let tick_arrays = PositionTickArrays {
+            lower: &mut below,
+            upper: Some(&mut above),
+        };
+        open_position(&mut pool, &mut other, tick_arrays, 100 * LIQUIDITY).unwrap();
+        let mut old = position(-100, 100);
+        let tick_arrays = PositionTickArrays {
+            lower: &mut below,
+            upper: Some(&mut above),
+        };
+        open_position(&mut pool, &mut old, tick_arrays, LIQUIDITY).unwrap();
+
+        let mut balance = RepositionBalance::default();
+        let tick_arrays = PositionTickArrays {
+            lower: &mut below,
+            upper: Some(&mut above),
+        };
+        let withdrawn = close_position(&mut pool, &mut old, tick_arrays).unwrap();
+        balance.withdraw(&withdrawn);
+        assert_eq!(pool.global_liquidity, 100 * LIQUIDITY);
+
+        let swapped = swap(&mut pool, Some(&mut above), withdrawn.amount_1, false).unwrap();
+        balance.swap(&swapped, false);
+        assert_eq!(balance.amount_1, 0);
+        assert_eq!(balance.amount_0, i128::from(withdrawn.amount_0 + swapped.amount_out));
+
+        let mut new = position(20, 120);
+        let tick_arrays = PositionTickArrays {
+            lower: &mut above,
+            upper: None,
+        };
+        let deposited = open_position(&mut pool, &mut new, tick_arrays, LIQUIDITY).unwrap();
+        balance.deposit(&deposited);
+        assert_eq!(deposited.amount_1, 0);
+        // the same liquidity over a range holding only token_0 takes about half of what the
+        // old range returned, the rest is paid out
+        assert!(balance.amount_0 > 0);
+        assert_eq!(balance.amount_1, 0);
+        assert_eq!(pool.global_liquidity, 100 * LIQUIDITY);
+    }
+}
@@ -0,0 +1,193 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    core::settle_crossed_orders,
+    errors::ClmmError,
+    states::{Pool, TickArrayState, POOL_STATUS_DISABLE_SWAP},
+    utils::{compute_swap_step, sqrt_price_x96_to_tick, tick_to_sqrt_price_x96},
+};
+
+#[derive(Debug)]
+pub struct SwapResult {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub sqrt_price_x96: u128,
+    pub tick: i32,
+    // pool liquidity once the swap has crossed its boundary tick, if it did
+    pub liquidity: u128,
+    // the boundary tick, if the swap crossed it
+    pub crossed_tick: Option<i32>,
+    // Token-2022 transfer fees, already included in amount_in and deducted from amount_out
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+}
+
+// adds the net liquidity of a crossed tick, signed for a price moving up
+fn cross_tick(
+    liquidity: u128,
+    tick_array: Option<&TickArrayState>,
+    tick: i32,
+    tick_spacing: i32,
+    price_up: bool,
+) -> Result<u128> {
+    let net_liquidity = match tick_array {
+        Some(tick_array) => tick_array.get_tick_state(tick, tick_spacing)?.net_liquidity,
+        None => 0,
+    };
+    let net_liquidity = if price_up { net_liquidity } else { -net_liquidity };
+
+    if net_liquidity >= 0 {
+        liquidity.checked_add(net_liquidity.unsigned_abs())
+    } else {
+        liquidity.checked_sub(net_liquidity.unsigned_abs())
+    }
+    .ok_or(ClmmError::ArithmeticOverflow.into())
+}
+
+// runs the swap math against the pool without touching any state,
+// shared by `swap` and `quote_swap`
+//
+// a swap takes a single step to the next spacing-aligned tick, and `tick_array` is the array
+// holding that boundary tick (None if it was never created). the active liquidity always
+// covers the positions with lower_tick <= current_tick < upper_tick:
+//  price going DOWN: a price sitting exactly on the boundary leaves it first, dropping the
+//   liquidity of positions starting there, then moves toward the boundary one spacing lower
+//  price going UP: reaching the boundary above adds the liquidity of positions starting there
+pub fn compute_swap(
+    pool: &Pool,
+    tick_array: Option<&TickArrayState>,
+    amount_in: u64,
+    a_to_b: bool,
+) -> Result<SwapResult> {
+    require!(amount_in > 0, ClmmError::ZeroAmount);
+    require!(pool.is_price_initialized(), ClmmError::PoolPriceUninitialized);
+
+    // example: current_tick = -57, spacing = 10 → a_to_b boundary -60, b_to_a boundary -50
+    let boundary_tick = pool.swap_boundary_tick(a_to_b);
+    let mut liquidity = pool.global_liquidity;
+    let mut crossed_tick = None;
+    let target_tick = if a_to_b && pool.sqrt_price_x96 == tick_to_sqrt_price_x96(boundary_tick)? {
+        liquidity = cross_tick(liquidity, tick_array, boundary_tick, pool.tick_spacing, false)?;
+        crossed_tick = Some(boundary_tick);
+        boundary_tick - pool.tick_spacing
+    } else {
+        boundary_tick
+    };
+    require!(liquidity > 0, ClmmError::InsufficientLiquidity);
+
+    let sqrt_price_target_x96 = tick_to_sqrt_price_x96(target_tick)?;
+
+    //  figures out if we can reach the target tick or stop partway
+    //  calculates exact input consumed and output produced
+    //
+    let (next_sqrt_price_x96, amount_consumed, amount_produced) = compute_swap_step(
+        pool.sqrt_price_x96,
+        sqrt_price_target_x96,
+        liquidity,
+        amount_in as u128,
+        a_to_b,
+    )?;
+
+    if !a_to_b && next_sqrt_price_x96 == sqrt_price_target_x96 {
+        liquidity = cross_tick(liquidity, tick_array, boundary_tick, pool.tick_spacing, true)?;
+        crossed_tick = Some(boundary_tick);
+    }
+
+    let amount_out: u64 = amount_produced
+        .try_into()
+        .map_err(|_| ClmmError::ArithmeticOverflow)?;
+    let amount_in_consumed: u64 = amount_consumed
+        .try_into()
+        .map_err(|_| ClmmError::ArithmeticOverflow)?;
+
+    Ok(SwapResult {
+        amount_in: amount_in_consumed,
+        amount_out,
+        sqrt_price_x96: next_sqrt_price_x96,
+        tick: sqrt_price_x96_to_tick(next_sqrt_price_x96)?,
+        liquidity,
+        crossed_tick,
+        transfer_fee_in: 0,
+        transfer_fee_out: 0,
+    })
+}
+
+// moves the pool to where a computed swap ends
+pub fn apply_swap(pool: &mut Pool, result: &SwapResult) {
+    pool.sqrt_price_x96 = result.sqrt_price_x96;
+    pool.current_tick = result.tick;
+    pool.global_liquidity = result.liquidity;
+}
+
+// a swap without transfer fees: the amounts are what the vaults receive and send
+pub fn swap(
+    pool: &mut Pool,
+    tick_array: Option<&mut TickArrayState>,
+    amount_in: u64,
+    a_to_b: bool,
+) -> Result<SwapResult> {
+    pool.require_enabled(POOL_STATUS_DISABLE_SWAP)?;
+    let result = compute_swap(pool, tick_array.as_deref(), amount_in, a_to_b)?;
+    apply_swap(pool, &result);
+    if let (Some(tick), Some(tick_array)) = (result.crossed_tick, tick_array) {
+        settle_crossed_orders(tick_array, tick, pool.tick_spacing, !a_to_b)?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{pool_at_tick, tick_array};
+
+    const SPACING: i32 = 10;
+    const LIQUIDITY: u128 = 1_000_000_000;
+
+    // a pool at tick 0 with one position over [0, 20]
+    fn setup() -> (Pool, TickArrayState) {
+        let mut tick_array = tick_array(0);
+        let delta = LIQUIDITY as i128;
+        tick_array.get_tick_state_mut(0, SPACING).unwrap().update_liquidity(delta, true).unwrap();
+        tick_array.get_tick_state_mut(20, SPACING).unwrap().update_liquidity(delta, false).unwrap();
+
+        let pool = Pool {
+            global_liquidity: LIQUIDITY,
+            ..pool_at_tick(0, SPACING)
+        };
+        (pool, tick_array)
+    }
+
+    #[test]
+    fn test_falling_price_leaves_the_tick_it_sits_on() {
+        let (mut pool, mut tick_array) = setup();
+
+        // the position starts at tick 0, below it there is no liquidity
+        let result = swap(&mut pool, Some(&mut tick_array), 1_000, true);
+        assert_eq!(result.unwrap_err(), ClmmError::InsufficientLiquidity.into());
+    }
+
+    #[test]
+    fn test_rising_price_crosses_ticks_it_reaches() {
+        let (mut pool, mut tick_array) = setup();
+
+        // to tick 10, which holds no liquidity change
+        let result = swap(&mut pool, Some(&mut tick_array), u32::MAX as u64, false).unwrap();
+        assert_eq!((result.tick, result.liquidity), (10, LIQUIDITY));
+        // to tick 20, where the position ends
+        let result = swap(&mut pool, Some(&mut tick_array), u32::MAX as u64, false).unwrap();
+        assert_eq!((result.tick, result.liquidity), (20, 0));
+        assert_eq!(pool.global_liquidity, 0);
+
+        // coming back down crosses tick 20 first and finds the position again
+        let result = swap(&mut pool, Some(&mut tick_array), 1_000, true).unwrap();
+        assert_eq!((result.tick, result.liquidity), (19, LIQUIDITY));
+    }
+
+    #[test]
+    fn test_uncreated_tick_array_holds_no_liquidity() {
+        let (mut pool, _) = setup();
+
+        let result = swap(&mut pool, None, u32::MAX as u64, false).unwrap();
+        assert_eq!((result.tick, result.liquidity), (10, LIQUIDITY));
+    }
+}
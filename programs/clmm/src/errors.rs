@@ -6,7 +6,7 @@ pub enum ClmmError {
     ArithmeticOverflow,
     #[msg("Invalid Token Pair")]
     InvalidTokenPair,
-    #[msg("Invalid Token Pair")]
+    #[msg("Tick spacing must be positive")]
     InvalidTickSpacing,
     #[msg("Invalid Token Order")]
     InvalidTokenOrder,
@@ -33,5 +33,83 @@ pub enum ClmmError {
     #[msg("No liquidity available for swap")]
     InsufficientLiquidity,
     #[msg("Output amount below minimum (slippage exceeded)")]
-    SlippageExceeded
+    SlippageExceeded,
+    #[msg("Tick array still has initialized ticks")]
+    TickArrayNotEmpty,
+    #[msg("Unsupported tick array layout version")]
+    InvalidTickArrayVersion,
+    #[msg("Rent must be returned to the tick array payer")]
+    InvalidTickArrayPayer,
+    #[msg("Unsupported token transfer fee configuration")]
+    UnsupportedTransferFee,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Transfer hook program is not on the allowlist")]
+    TransferHookNotAllowed,
+    #[msg("Transfer hook allowlist is full")]
+    TransferHookAllowlistFull,
+    #[msg("Mint transfer hook program changed since pool creation")]
+    TransferHookProgramChanged,
+    #[msg("Mint override list is full")]
+    MintOverrideListFull,
+    #[msg("Mint has a permanent delegate")]
+    MintHasPermanentDelegate,
+    #[msg("Mint is non-transferable")]
+    MintNonTransferable,
+    #[msg("Mint has confidential transfers enabled")]
+    MintConfidentialTransfer,
+    #[msg("Mint freezes new token accounts by default")]
+    MintDefaultStateFrozen,
+    #[msg("Mint is interest-bearing")]
+    MintInterestBearing,
+    #[msg("Mint is pausable")]
+    MintPausable,
+    #[msg("Unsupported mint extension")]
+    UnsupportedMintExtension,
+    #[msg("Pool has no native SOL side")]
+    NativeSolUnsupported,
+    #[msg("Operation is disabled by the pool status")]
+    PoolOperationDisabled,
+    #[msg("Invalid pool status")]
+    InvalidPoolStatus,
+    #[msg("Pool creator list is full")]
+    PoolCreatorListFull,
+    #[msg("Signer is not allowed to create pools")]
+    PoolCreationNotAllowed,
+    #[msg("Invalid pool creation mode")]
+    InvalidPoolCreationMode,
+    #[msg("Pool creation fee accounts missing or invalid")]
+    InvalidCreationFeeAccount,
+    #[msg("Pool price is not initialized")]
+    PoolPriceUninitialized,
+    #[msg("Pool price moved beyond the allowed slippage")]
+    PriceSlippageExceeded,
+    #[msg("Slippage must not exceed 10000 bps")]
+    InvalidSlippage,
+    #[msg("Tick spacing exceeds the maximum")]
+    TickSpacingTooLarge,
+    #[msg("Sqrt price is at or below the lowest usable tick")]
+    SqrtPriceBelowMinimum,
+    #[msg("Sqrt price is at or above the highest usable tick")]
+    SqrtPriceAboveMaximum,
+    #[msg("Tick is outside MIN_TICK..=MAX_TICK")]
+    TickOutOfBounds,
+    #[msg("Limit order range must fit in a single tick array")]
+    InvalidLimitOrderTick,
+    #[msg("Limit order range must be entirely on the side of the price it sells")]
+    LimitOrderCrossesPrice,
+    #[msg("Limit order is already filled")]
+    LimitOrderFilled,
+    #[msg("Limit order is not filled yet")]
+    LimitOrderNotFilled,
+    #[msg("Invalid position delegate permissions")]
+    InvalidDelegatePermissions,
+    #[msg("Operators can only withdraw to the position owner's token accounts")]
+    InvalidWithdrawalAccount,
+    #[msg("Expected pool price is required as the slippage bound")]
+    MissingExpectedPrice,
+    #[msg("Native SOL mode takes a temporary WSOL account, other modes must not pass one")]
+    InvalidNativeSolAccount,
+    #[msg("User token account is required for a non-native side")]
+    MissingTokenAccount
 }
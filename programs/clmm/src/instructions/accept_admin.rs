@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::ClmmError, states::GlobalConfig};
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    // a multisig or timelock PDA accepts by signing through its own program's CPI
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = pending_admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let global_config = &mut ctx.accounts.global_config;
+    global_config.admin = global_config.pending_admin;
+    global_config.pending_admin = Pubkey::default();
+
+    msg!("New admin: {}", global_config.admin);
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    states::{Pool, PositionDelegate, POSITION_DELEGATE_ALL},
+};
+
+#[derive(Accounts)]
+#[instruction(operator: Pubkey)]
+pub struct ApprovePositionDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+    init_if_needed,
+    payer = owner,
+    space = PositionDelegate::SPACE,
+    seeds = [
+        b"position_delegate",
+        pool.key().as_ref(),
+        owner.key().as_ref(),
+        operator.as_ref()
+    ],
+    bump
+)]
+    pub position_delegate: Account<'info, PositionDelegate>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// grants `operator` the POSITION_DELEGATE_* bits in `permissions` over the signer's positions
+// in the pool, replacing any earlier approval of the same operator
+pub fn approve_position_delegate(
+    ctx: Context<ApprovePositionDelegate>,
+    operator: Pubkey,
+    permissions: u8,
+) -> Result<()> {
+    require!(
+        permissions != 0 && permissions & !POSITION_DELEGATE_ALL == 0,
+        ClmmError::InvalidDelegatePermissions
+    );
+    require_keys_neq!(operator, ctx.accounts.owner.key(), ClmmError::InvalidDelegatePermissions);
+
+    ctx.accounts.position_delegate.set_inner(PositionDelegate {
+        pool: ctx.accounts.pool.key(),
+        owner: ctx.accounts.owner.key(),
+        operator,
+        permissions,
+        bump: ctx.bumps.position_delegate,
+    });
+
+    msg!("Position delegate: operator={}, permissions={:#06b}", operator, permissions);
+
+    Ok(())
+}
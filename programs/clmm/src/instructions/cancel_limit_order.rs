@@ -0,0 +1,138 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    core::{self, LiquidityChange},
+    errors::ClmmError,
+    instructions::{get_transfer_fee, transfer_from_pda},
+    states::{LimitOrder, Pool, TickArrayBitmap, TickArrayState},
+};
+
+#[derive(Accounts)]
+#[instruction(tick: i32, zero_for_one: bool, tick_array_start_index: i32)]
+pub struct CancelLimitOrder<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+    mut,
+    has_one = token_0,
+    has_one = token_1
+)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    pub token_0: Box<InterfaceAccount<'info, Mint>>,
+    pub token_1: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        &tick_array_start_index.to_le_bytes()
+    ],
+    bump
+)]
+    pub tick_array: AccountLoader<'info, TickArrayState>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
+
+    #[account(
+    mut,
+    close = signer,
+    seeds = [
+        b"limit_order",
+        pool.key().as_ref(),
+        signer.key().as_ref(),
+        tick.to_le_bytes().as_ref(),
+        &[zero_for_one as u8]
+    ],
+    bump = limit_order.bump
+)]
+    pub limit_order: Box<Account<'info, LimitOrder>>,
+    #[account(mut, token::mint = token_0)]
+    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = token_1)]
+    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+    mut,
+    token::mint = token_0,
+    token::authority = pool
+)]
+    pub pool_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+    mut,
+    token::mint = token_1,
+    token::authority = pool
+)]
+    pub pool_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// withdraws an unfilled order, returning what the price has converted so far along with the
+// rest of the deposit. returns the amounts received after any transfer fee
+pub fn cancel_limit_order<'info>(
+    ctx: Context<'_, '_, '_, 'info, CancelLimitOrder<'info>>,
+    _tick: i32,
+    _zero_for_one: bool,
+    tick_array_start_index: i32,
+) -> Result<(u64, u64)> {
+    let pool = &mut ctx.accounts.pool;
+
+    let LiquidityChange {
+        amount_0,
+        amount_1,
+        lower_array_emptied,
+        ..
+    } = {
+        let mut tick_array = TickArrayState::load_mut(ctx.accounts.tick_array.as_ref())?;
+        core::cancel_limit_order(pool, &mut ctx.accounts.limit_order, &mut tick_array)?
+    };
+
+    // both ticks live in the same array
+    if lower_array_emptied {
+        let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+        tick_array_bitmap.set_initialized(tick_array_start_index, pool.tick_spacing, false)?;
+    }
+
+    if amount_0 > 0 {
+        transfer_from_pda(
+            &ctx.accounts.pool_vault_0,
+            &ctx.accounts.user_0,
+            &amount_0,
+            &ctx.accounts.token_0,
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
+        )?;
+    }
+
+    if amount_1 > 0 {
+        transfer_from_pda(
+            &ctx.accounts.pool_vault_1,
+            &ctx.accounts.user_1,
+            &amount_1,
+            &ctx.accounts.token_1,
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
+        )?;
+    }
+
+    let received_0 = amount_0
+        .checked_sub(get_transfer_fee(&ctx.accounts.token_0, amount_0)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    let received_1 = amount_1
+        .checked_sub(get_transfer_fee(&ctx.accounts.token_1, amount_1)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    Ok((received_0, received_1))
+}
@@ -2,10 +2,13 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
+    core::{self, LiquidityChange, PositionTickArrays},
     errors::ClmmError,
-    instructions::transfer_from_pda,
-    states::{Pool, Position, TickArrayState},
-    utils::*,
+    instructions::{
+        close_native_sol_account, get_transfer_fee, native_mint_of, transfer_from_pda,
+        user_token_account, validate_native_sol,
+    },
+    states::{Pool, Position, TickArrayBitmap, TickArrayState},
 };
 
 #[derive(Accounts)]
@@ -32,7 +35,7 @@ pub struct ClosePosition<'info> {
     ],
     bump
 )]
-    pub lower_tick_array: Box<Account<'info, TickArrayState>>,
+    pub lower_tick_array: AccountLoader<'info, TickArrayState>,
     #[account(
     mut,
     seeds = [
@@ -42,7 +45,17 @@ pub struct ClosePosition<'info> {
     ],
     bump
 )]
-    pub upper_tick_array: Box<Account<'info, TickArrayState>>,
+    pub upper_tick_array: AccountLoader<'info, TickArrayState>,
+
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
 
     #[account(
     mut,
@@ -52,15 +65,35 @@ pub struct ClosePosition<'info> {
         pool.key().as_ref(),
         signer.key().as_ref(),
         lower_tick.to_le_bytes().as_ref(),
-        upper_tick.to_le_bytes().as_ref()
+        upper_tick.to_le_bytes().as_ref(),
+        position.id.to_le_bytes().as_ref()
     ],
-    bump
+    bump = position.bump
 )]
     pub position: Box<Account<'info, Position>>,
+    // the WSOL side may be left out in native SOL mode
     #[account(mut)]
-    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_0: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(mut)]
-    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_1: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // the pool's WSOL side, passed in native SOL mode to open the temporary account with
+    #[account(
+        address = native_mint_of(&token_0, &token_1).key() @ ClmmError::NativeSolUnsupported
+    )]
+    pub native_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    // temporary WSOL account for native SOL mode, closed back to the signer before returning
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"native_sol", signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = signer,
+        token::token_program = token_program
+    )]
+    pub native_sol_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
     mut,
@@ -80,69 +113,105 @@ pub struct ClosePosition<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn close_position(
-    ctx: Context<ClosePosition>,
+pub fn close_position<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClosePosition<'info>>,
     upper_tick: i32,
     lower_tick: i32,
+    native_sol: bool,
 ) -> Result<(u64, u64)> {
     let pool = &mut ctx.accounts.pool;
     let position = &mut ctx.accounts.position;
-    let liquidity_to_remove = position.liquidity;
 
-    let position_lower_tick = position.lower_tick;
-    let position_upper_tick = position.upper_tick;
+    validate_native_sol(pool, native_sol, ctx.accounts.native_sol_account.as_deref())?;
     require!(
-        upper_tick == position_upper_tick && lower_tick == position_lower_tick,
+        upper_tick == position.upper_tick && lower_tick == position.lower_tick,
         ClmmError::ZeroAmount
     );
-    let upper_tick_array = &mut ctx.accounts.upper_tick_array;
-    let lower_tick_array = &mut ctx.accounts.lower_tick_array;
 
-    let lower_tick_state = lower_tick_array.get_tick_state_mut(lower_tick, pool.tick_spacing)?;
-
-    let upper_tick_state = upper_tick_array.get_tick_state_mut(upper_tick, pool.tick_spacing)?;
-
-    lower_tick_state.update_liquidity(-(liquidity_to_remove as i128), true)?;
-    upper_tick_state.update_liquidity(-(liquidity_to_remove as i128), false)?;
-
-    let lower_sqrt = tick_to_sqrt_price_x96(lower_tick)?;
-    let upper_sqrt = tick_to_sqrt_price_x96(upper_tick)?;
-
-    if pool.sqrt_price_x96 >= lower_sqrt && pool.sqrt_price_x96 < upper_sqrt {
-        pool.global_liquidity = pool
-            .global_liquidity
-            .checked_sub(liquidity_to_remove)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+    let LiquidityChange {
+        amount_0,
+        amount_1,
+        lower_array_emptied,
+        upper_array_emptied,
+    } = {
+        let (mut lower, mut upper) = TickArrayState::load_position_arrays(
+            ctx.accounts.lower_tick_array.as_ref(),
+            ctx.accounts.upper_tick_array.as_ref(),
+        )?;
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: upper.as_deref_mut(),
+        };
+        core::close_position(pool, position, tick_arrays)?
+    };
+
+    // tick arrays left without any initialized tick are no longer worth visiting
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+    if lower_array_emptied {
+        tick_array_bitmap.set_initialized(
+            TickArrayState::get_start_tick_idx(lower_tick, pool.tick_spacing),
+            pool.tick_spacing,
+            false,
+        )?;
     }
+    if upper_array_emptied {
+        tick_array_bitmap.set_initialized(
+            TickArrayState::get_start_tick_idx(upper_tick, pool.tick_spacing),
+            pool.tick_spacing,
+            false,
+        )?;
+    }
+    drop(tick_array_bitmap);
 
-    let (amount_0, amount_1) = get_amounts_for_liquidity(
-        pool.sqrt_price_x96,
-        lower_sqrt,
-        upper_sqrt,
-        liquidity_to_remove,
+    let native_sol_account = ctx.accounts.native_sol_account.as_deref();
+    let user_0 = user_token_account(
+        ctx.accounts.user_0.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_0,
+    )?;
+    let user_1 = user_token_account(
+        ctx.accounts.user_1.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_1,
     )?;
 
     if amount_0 > 0 {
         transfer_from_pda(
             &ctx.accounts.pool_vault_0,
-            &ctx.accounts.user_0,
+            user_0,
             &amount_0,
             &ctx.accounts.token_0,
             &ctx.accounts.token_program,
             &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
     if amount_1 > 0 {
         transfer_from_pda(
             &ctx.accounts.pool_vault_1,
-            &ctx.accounts.user_1,
+            user_1,
             &amount_1,
             &ctx.accounts.token_1,
             &ctx.accounts.token_program,
             &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
-    Ok((amount_0, amount_1))
+    close_native_sol_account(
+        &ctx.accounts.signer,
+        native_sol_account,
+        &ctx.accounts.token_program,
+    )?;
+
+    // report what the user actually receives after any transfer fee
+    let received_0 = amount_0
+        .checked_sub(get_transfer_fee(&ctx.accounts.token_0, amount_0)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    let received_1 = amount_1
+        .checked_sub(get_transfer_fee(&ctx.accounts.token_1, amount_1)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    Ok((received_0, received_1))
 }
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    states::{Pool, TickArrayBitmap, TickArrayState},
+};
+
+#[derive(Accounts)]
+#[instruction(tick_array_start_index: i32)]
+pub struct CloseTickArray<'info> {
+    pub signer: Signer<'info>,
+
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+    mut,
+    close = payer,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        tick_array_start_index.to_le_bytes().as_ref()
+    ],
+    bump
+)]
+    pub tick_array: AccountLoader<'info, TickArrayState>,
+
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
+
+    // whoever paid for the tick array gets the rent back
+    /// CHECK: only receives lamports, validated against tick_array.payer
+    #[account(mut, address = tick_array.load()?.payer @ ClmmError::InvalidTickArrayPayer)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+pub fn close_tick_array(ctx: Context<CloseTickArray>, _tick_array_start_index: i32) -> Result<()> {
+    let tick_array = TickArrayState::load_mut(ctx.accounts.tick_array.as_ref())?;
+
+    require!(tick_array.is_empty(), ClmmError::TickArrayNotEmpty);
+
+    // the bit may still be set if a swap filled the array's last limit orders and they have not
+    // been settled yet
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+    tick_array_bitmap.set_initialized(
+        tick_array.starting_tick,
+        ctx.accounts.pool.tick_spacing,
+        false,
+    )?;
+    drop(tick_array);
+
+    Ok(())
+}
@@ -2,10 +2,16 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
+    core::{self, LiquidityChange, PositionTickArrays},
     errors::ClmmError,
-    instructions::transfer_from_pda,
-    states::{Pool, Position, TickArrayState},
-    utils::{get_amounts_for_liquidity, tick_to_sqrt_price_x96},
+    instructions::{
+        close_native_sol_account, get_transfer_fee, native_mint_of, transfer_from_pda,
+        user_token_account, validate_native_sol,
+    },
+    states::{
+        Pool, Position, PositionDelegate, TickArrayBitmap, TickArrayState,
+        POSITION_DELEGATE_DECREASE_LIQUIDITY,
+    },
 };
 
 #[derive(Accounts)]
@@ -29,7 +35,7 @@ pub struct DecreaseLiquidity<'info> {
     ],
     bump
 )]
-    pub lower_tick_array: Box<Account<'info, TickArrayState>>,
+    pub lower_tick_array: AccountLoader<'info, TickArrayState>,
     #[account(
     mut,
     seeds = [
@@ -39,18 +45,56 @@ pub struct DecreaseLiquidity<'info> {
     ],
     bump
 )]
-    pub upper_tick_array: Box<Account<'info, TickArrayState>>,
+    pub upper_tick_array: AccountLoader<'info, TickArrayState>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
 
     #[account(
         mut,
         constraint = position.pool == pool.key() @ ClmmError::InvalidPositionRange,
-        constraint = position.owner == signer.key() @ ClmmError::InvalidPositionOwner,
 )]
     pub position: Box<Account<'info, Position>>,
+    // the owner's approval when an operator signs, see Position::require_authority
+    #[account(
+    seeds = [
+        b"position_delegate",
+        pool.key().as_ref(),
+        position.owner.as_ref(),
+        signer.key().as_ref()
+    ],
+    bump = position_delegate.bump
+)]
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
+    // the WSOL side may be left out in native SOL mode
     #[account(mut, token::mint = token_0)]
-    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_0: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(mut, token::mint = token_1)]
-    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_1: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // the pool's WSOL side, passed in native SOL mode to open the temporary account with
+    #[account(
+        address = native_mint_of(&token_0, &token_1).key() @ ClmmError::NativeSolUnsupported
+    )]
+    pub native_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    // temporary WSOL account for native SOL mode, closed back to the signer before returning
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"native_sol", signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = signer,
+        token::token_program = token_program
+    )]
+    pub native_sol_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
     mut,
@@ -67,88 +111,125 @@ pub struct DecreaseLiquidity<'info> {
 
     pub token_0: Box<InterfaceAccount<'info, Mint>>,
     pub token_1: Box<InterfaceAccount<'info, Mint>>,
+    pub system_program: Program<'info, System>,
     pub token_program: Interface<'info, TokenInterface>,
 }
 
-pub fn decrease_liquidity(
-    ctx: Context<DecreaseLiquidity>,
+pub fn decrease_liquidity<'info>(
+    ctx: Context<'_, '_, '_, 'info, DecreaseLiquidity<'info>>,
     liquidity_amount: u128,
     upper_tick: i32,
     lower_tick: i32,
     _tick_array_lower_start_index: i32,
     _tick_array_upper_start_index: i32,
-) -> Result<()> {
+    native_sol: bool,
+) -> Result<(u64, u64)> {
     let pool = &mut ctx.accounts.pool;
     let position = &mut ctx.accounts.position;
 
-    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
-    require!(
-        liquidity_amount <= position.liquidity,
-        ClmmError::InvalidAmount
-    );
+    validate_native_sol(pool, native_sol, ctx.accounts.native_sol_account.as_deref())?;
     require!(
         lower_tick == position.lower_tick && upper_tick == position.upper_tick,
         ClmmError::InvalidTicks
     );
-    require!(
-        lower_tick < upper_tick
-            && lower_tick % pool.tick_spacing == 0
-            && upper_tick % pool.tick_spacing == 0,
-        ClmmError::InvalidTickRange
-    );
-    let upper_tick_array = &mut ctx.accounts.upper_tick_array;
-    let lower_tick_array = &mut ctx.accounts.lower_tick_array;
-
-    let lower_tick_state = lower_tick_array.get_tick_state_mut(lower_tick, pool.tick_spacing)?;
-
-    let upper_tick_state = upper_tick_array.get_tick_state_mut(upper_tick, pool.tick_spacing)?;
-
-    lower_tick_state.update_liquidity(-(liquidity_amount as i128), true)?;
-    upper_tick_state.update_liquidity(-(liquidity_amount as i128), false)?;
-
-    let lower_sqrt = tick_to_sqrt_price_x96(lower_tick)?;
-    let upper_sqrt = tick_to_sqrt_price_x96(upper_tick)?;
-
-    position.liquidity = position
-        .liquidity
-        .checked_sub(liquidity_amount)
-        .ok_or(ClmmError::ArithmeticOverflow)?;
+    position.require_authority(
+        &ctx.accounts.signer.key(),
+        ctx.accounts.position_delegate.as_deref(),
+        POSITION_DELEGATE_DECREASE_LIQUIDITY,
+    )?;
+    let native_sol_account = ctx.accounts.native_sol_account.as_deref();
+    let user_0 = user_token_account(
+        ctx.accounts.user_0.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_0,
+    )?;
+    let user_1 = user_token_account(
+        ctx.accounts.user_1.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_1,
+    )?;
 
-    if pool.sqrt_price_x96 >= lower_sqrt && pool.sqrt_price_x96 < upper_sqrt {
-        pool.global_liquidity = pool
-            .global_liquidity
-            .checked_sub(liquidity_amount)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+    // an operator may withdraw, but only to the owner, which also rules out native SOL mode
+    // as that pays the signer
+    if ctx.accounts.signer.key() != position.owner {
+        require!(
+            user_0.owner == position.owner && user_1.owner == position.owner,
+            ClmmError::InvalidWithdrawalAccount
+        );
     }
 
-    let (amount_0, amount_1) = get_amounts_for_liquidity(
-        pool.sqrt_price_x96,
-        lower_sqrt,
-        upper_sqrt,
-        liquidity_amount,
-    )?;
+    let LiquidityChange {
+        amount_0,
+        amount_1,
+        lower_array_emptied,
+        upper_array_emptied,
+    } = {
+        let (mut lower, mut upper) = TickArrayState::load_position_arrays(
+            ctx.accounts.lower_tick_array.as_ref(),
+            ctx.accounts.upper_tick_array.as_ref(),
+        )?;
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: upper.as_deref_mut(),
+        };
+        core::decrease_liquidity(pool, position, tick_arrays, liquidity_amount)?
+    };
+
+    // tick arrays left without any initialized tick are no longer worth visiting
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+    if lower_array_emptied {
+        tick_array_bitmap.set_initialized(
+            TickArrayState::get_start_tick_idx(lower_tick, pool.tick_spacing),
+            pool.tick_spacing,
+            false,
+        )?;
+    }
+    if upper_array_emptied {
+        tick_array_bitmap.set_initialized(
+            TickArrayState::get_start_tick_idx(upper_tick, pool.tick_spacing),
+            pool.tick_spacing,
+            false,
+        )?;
+    }
+    drop(tick_array_bitmap);
 
     if amount_0 > 0 {
         transfer_from_pda(
             &ctx.accounts.pool_vault_0,
-            &ctx.accounts.user_0,
+            user_0,
             &amount_0,
             &ctx.accounts.token_0,
             &ctx.accounts.token_program,
             &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
     if amount_1 > 0 {
         transfer_from_pda(
             &ctx.accounts.pool_vault_1,
-            &ctx.accounts.user_1,
+            user_1,
             &amount_1,
             &ctx.accounts.token_1,
             &ctx.accounts.token_program,
             &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
-    Ok(())
+    close_native_sol_account(
+        &ctx.accounts.signer,
+        native_sol_account,
+        &ctx.accounts.token_program,
+    )?;
+
+    // report what the user actually receives after any transfer fee
+    let received_0 = amount_0
+        .checked_sub(get_transfer_fee(&ctx.accounts.token_0, amount_0)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    let received_1 = amount_1
+        .checked_sub(get_transfer_fee(&ctx.accounts.token_1, amount_1)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    Ok((received_0, received_1))
 }
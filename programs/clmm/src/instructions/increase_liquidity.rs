@@ -2,10 +2,15 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
+    core::{self, LiquidityChange, PositionTickArrays},
     errors::ClmmError,
-    instructions::transfer_tokens,
-    states::{Pool, Position, TickArrayState},
-    utils::{get_amounts_for_liquidity, tick_to_sqrt_price_x96},
+    instructions::{
+        close_native_sol_account, get_transfer_inverse_fee, native_mint_of, transfer_tokens,
+        user_token_account, validate_native_sol, wrap_native_sol,
+    },
+    states::{
+        Pool, Position, PositionDelegate, TickArrayState, POSITION_DELEGATE_INCREASE_LIQUIDITY,
+    },
 };
 
 #[derive(Accounts)]
@@ -29,7 +34,7 @@ pub struct IncreaseLiquidity<'info> {
     ],
     bump
 )]
-    pub lower_tick_array: Box<Account<'info, TickArrayState>>,
+    pub lower_tick_array: AccountLoader<'info, TickArrayState>,
     #[account(
     mut,
     seeds = [
@@ -39,19 +44,48 @@ pub struct IncreaseLiquidity<'info> {
     ],
     bump
 )]
-    pub upper_tick_array: Box<Account<'info, TickArrayState>>,
+    pub upper_tick_array: AccountLoader<'info, TickArrayState>,
 
     #[account(
         mut,
         constraint = position.pool == pool.key() @ ClmmError::InvalidPositionRange,
-        constraint = position.owner == signer.key() @ ClmmError::InvalidPositionOwner,
 )]
     pub position: Box<Account<'info, Position>>,
+    // the owner's approval when an operator signs, see Position::require_authority
+    #[account(
+    seeds = [
+        b"position_delegate",
+        pool.key().as_ref(),
+        position.owner.as_ref(),
+        signer.key().as_ref()
+    ],
+    bump = position_delegate.bump
+)]
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
 
+    // the WSOL side may be left out in native SOL mode
     #[account(mut, token::mint = token_0)]
-    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_0: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(mut, token::mint = token_1)]
-    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_1: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // the pool's WSOL side, passed in native SOL mode to open the temporary account with
+    #[account(
+        address = native_mint_of(&token_0, &token_1).key() @ ClmmError::NativeSolUnsupported
+    )]
+    pub native_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    // temporary WSOL account for native SOL mode, closed back to the signer before returning
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"native_sol", signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = signer,
+        token::token_program = token_program
+    )]
+    pub native_sol_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
     mut,
@@ -73,81 +107,112 @@ pub struct IncreaseLiquidity<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn increase_liquidity(
-    ctx: Context<IncreaseLiquidity>,
+pub fn increase_liquidity<'info>(
+    ctx: Context<'_, '_, '_, 'info, IncreaseLiquidity<'info>>,
     liquidity_amount: u128,
     upper_tick: i32,
     lower_tick: i32,
     _tick_array_lower_start_index: i32,
     _tick_array_upper_start_index: i32,
+    native_sol: bool,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
     let position = &mut ctx.accounts.position;
 
-    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    validate_native_sol(pool, native_sol, ctx.accounts.native_sol_account.as_deref())?;
     require!(
         lower_tick == position.lower_tick && upper_tick == position.upper_tick,
         ClmmError::InvalidTicks
     );
-    require!(
-        lower_tick < upper_tick
-            && lower_tick % pool.tick_spacing == 0
-            && upper_tick % pool.tick_spacing == 0,
-        ClmmError::InvalidTickRange
-    );
-    let upper_tick_array = &mut ctx.accounts.upper_tick_array;
-    let lower_tick_array = &mut ctx.accounts.lower_tick_array;
-
-    let lower_tick_state = lower_tick_array.get_tick_state_mut(lower_tick, pool.tick_spacing)?;
-
-    let upper_tick_state = upper_tick_array.get_tick_state_mut(upper_tick, pool.tick_spacing)?;
-
-    lower_tick_state.update_liquidity(liquidity_amount as i128, true)?;
-    upper_tick_state.update_liquidity(liquidity_amount as i128, false)?;
-
-    let lower_sqrt = tick_to_sqrt_price_x96(lower_tick)?;
-    let upper_sqrt = tick_to_sqrt_price_x96(upper_tick)?;
+    position.require_authority(
+        &ctx.accounts.signer.key(),
+        ctx.accounts.position_delegate.as_deref(),
+        POSITION_DELEGATE_INCREASE_LIQUIDITY,
+    )?;
 
-    position.liquidity = position
-        .liquidity
-        .checked_add(liquidity_amount)
+    let LiquidityChange { amount_0, amount_1, .. } = {
+        let (mut lower, mut upper) = TickArrayState::load_position_arrays(
+            ctx.accounts.lower_tick_array.as_ref(),
+            ctx.accounts.upper_tick_array.as_ref(),
+        )?;
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: upper.as_deref_mut(),
+        };
+        core::increase_liquidity(pool, position, tick_arrays, liquidity_amount)?
+    };
+
+    // the vaults must receive the full amounts, so the user also covers any transfer fee
+    let amount_0 = amount_0
+        .checked_add(get_transfer_inverse_fee(&ctx.accounts.token_0, amount_0)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    let amount_1 = amount_1
+        .checked_add(get_transfer_inverse_fee(&ctx.accounts.token_1, amount_1)?)
         .ok_or(ClmmError::ArithmeticOverflow)?;
 
-    if pool.sqrt_price_x96 >= lower_sqrt && pool.sqrt_price_x96 < upper_sqrt {
-        pool.global_liquidity = pool
-            .global_liquidity
-            .checked_add(liquidity_amount)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-    }
-
-    let (amount_0, amount_1) = get_amounts_for_liquidity(
-        pool.sqrt_price_x96,
-        lower_sqrt,
-        upper_sqrt,
-        liquidity_amount,
+    let native_sol_account = ctx.accounts.native_sol_account.as_deref();
+    let user_0 = user_token_account(
+        ctx.accounts.user_0.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_0,
+    )?;
+    let user_1 = user_token_account(
+        ctx.accounts.user_1.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_1,
     )?;
 
     if amount_0 > 0 {
+        if native_sol {
+            wrap_native_sol(
+                &ctx.accounts.signer,
+                user_0,
+                &ctx.accounts.token_0,
+                amount_0,
+                &ctx.accounts.system_program,
+                &ctx.accounts.token_program,
+            )?;
+        }
         transfer_tokens(
-            &ctx.accounts.user_0,
+            user_0,
             &ctx.accounts.pool_vault_0,
             &amount_0,
             &ctx.accounts.token_0,
             &ctx.accounts.signer,
             &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
     if amount_1 > 0 {
+        if native_sol {
+            wrap_native_sol(
+                &ctx.accounts.signer,
+                user_1,
+                &ctx.accounts.token_1,
+                amount_1,
+                &ctx.accounts.system_program,
+                &ctx.accounts.token_program,
+            )?;
+        }
         transfer_tokens(
-            &ctx.accounts.user_1,
+            user_1,
             &ctx.accounts.pool_vault_1,
             &amount_1,
             &ctx.accounts.token_1,
             &ctx.accounts.signer,
             &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
+    close_native_sol_account(
+        &ctx.accounts.signer,
+        native_sol_account,
+        &ctx.accounts.token_program,
+    )?;
+
     Ok(())
 }
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    program::Clmm,
+    states::{
+        GlobalConfig, MAX_MINT_OVERRIDES, MAX_POOL_CREATORS, MAX_TRANSFER_HOOK_PROGRAMS,
+        POOL_CREATION_PERMISSIONLESS,
+    },
+};
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = GlobalConfig::SPACE,
+        seeds = [b"global_config".as_ref()],
+        bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    // only the upgrade authority can claim the config, otherwise anyone could front-run it
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Clmm>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(signer.key())
+            @ ClmmError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+    ctx.accounts.global_config.set_inner(GlobalConfig {
+        admin: ctx.accounts.signer.key(),
+        pending_admin: Pubkey::default(),
+        transfer_hook_programs: [Pubkey::default(); MAX_TRANSFER_HOOK_PROGRAMS],
+        mint_overrides: [Pubkey::default(); MAX_MINT_OVERRIDES],
+        pool_creation_mode: POOL_CREATION_PERMISSIONLESS,
+        pool_creators: [Pubkey::default(); MAX_POOL_CREATORS],
+        treasury: ctx.accounts.signer.key(),
+        creation_fee_mint: Pubkey::default(),
+        creation_fee_amount: 0,
+        bump: ctx.bumps.global_config,
+    });
+
+    Ok(())
+}
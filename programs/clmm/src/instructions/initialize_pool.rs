@@ -1,13 +1,29 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use anchor_lang::system_program::{self, Transfer};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
-use crate::{errors::ClmmError, states::Pool, utils::sqrt_price_x96_to_tick};
+use crate::{
+    errors::ClmmError,
+    instructions::{
+        get_transfer_hook_program, validate_mint_extensions, validate_transfer_fee_config,
+    },
+    states::{GlobalConfig, Pool, TickArrayBitmap},
+    utils::{sqrt_price_x96_to_tick, validate_sqrt_price, validate_tick_spacing},
+};
 #[derive(Accounts)]
 #[instruction(tick_spacing: i32)]
 pub struct InitializePool<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    #[account(
+    seeds = [b"global_config".as_ref()],
+    bump = global_config.bump
+)]
+    pub global_config: Account<'info, GlobalConfig>,
+
     pub token_0_mint: InterfaceAccount<'info, Mint>,
     pub token_1_mint: InterfaceAccount<'info, Mint>,
 
@@ -25,6 +41,18 @@ bump
 )]
     pub pool: Account<'info, Pool>,
 
+    #[account(
+    init,
+    payer = signer,
+    space = TickArrayBitmap::SPACE,
+    seeds = [
+        b"tick_array_bitmap".as_ref(),
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
+
     #[account(
     init,
     payer = signer,
@@ -41,6 +69,29 @@ bump
 )]
     pub token_1_vault: InterfaceAccount<'info, TokenAccount>,
 
+    // creation fee accounts, only needed when the config charges a fee:
+    // `treasury` for a lamport fee, the other four for a token fee
+    /// CHECK: must be the config treasury, only receives lamports
+    #[account(mut, address = global_config.treasury @ ClmmError::InvalidCreationFeeAccount)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+    #[account(mint::token_program = creation_fee_token_program)]
+    pub creation_fee_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+    mut,
+    token::mint = creation_fee_mint,
+    token::authority = signer,
+    token::token_program = creation_fee_token_program
+)]
+    pub creation_fee_payer_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+    mut,
+    token::mint = creation_fee_mint,
+    token::token_program = creation_fee_token_program
+)]
+    pub creation_fee_treasury_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    // the fee mint's own program, which need not be the pool's `token_program`
+    pub creation_fee_token_program: Option<Interface<'info, TokenInterface>>,
+
     pub system_program: Program<'info, System>,
     pub token_program: Interface<'info, TokenInterface>,
     pub rent: Sysvar<'info, Rent>,
@@ -52,14 +103,41 @@ pub fn init_pool(
     initialize_sqrt_price: u128,
 ) -> Result<()> {
     require!(
-        ctx.accounts.token_0_mint.key() < ctx.accounts.token_1_mint.key(),
-        ClmmError::InvalidTokenOrder
+        ctx.accounts
+            .global_config
+            .can_create_pool(&ctx.accounts.signer.key()),
+        ClmmError::PoolCreationNotAllowed
     );
-    require!(tick_spacing > 0, ClmmError::InvalidTickSpacing);
     require!(
         ctx.accounts.token_0_mint.key() != ctx.accounts.token_1_mint.key(),
         ClmmError::InvalidTokenPair
     );
+    require!(
+        ctx.accounts.token_0_mint.key() < ctx.accounts.token_1_mint.key(),
+        ClmmError::InvalidTokenOrder
+    );
+    validate_tick_spacing(tick_spacing)?;
+    require!(
+        TickArrayBitmap::supports_tick_spacing(tick_spacing),
+        ClmmError::InvalidTickSpacing
+    );
+    // a zero price leaves the pool uninitialized until the first open_position
+    if initialize_sqrt_price != 0 {
+        validate_sqrt_price(initialize_sqrt_price, tick_spacing)?;
+    }
+    for mint in [&ctx.accounts.token_0_mint, &ctx.accounts.token_1_mint] {
+        if !ctx.accounts.global_config.is_mint_overridden(&mint.key()) {
+            validate_mint_extensions(mint)?;
+        }
+    }
+    validate_transfer_fee_config(&ctx.accounts.token_0_mint)?;
+    validate_transfer_fee_config(&ctx.accounts.token_1_mint)?;
+    charge_creation_fee(ctx.accounts)?;
+
+    let transfer_hook_program_0 =
+        validate_transfer_hook(&ctx.accounts.global_config, &ctx.accounts.token_0_mint)?;
+    let transfer_hook_program_1 =
+        validate_transfer_hook(&ctx.accounts.global_config, &ctx.accounts.token_1_mint)?;
     let pool = &mut ctx.accounts.pool;
     pool.set_inner(Pool {
         token_0: ctx.accounts.token_0_mint.key(),
@@ -68,10 +146,93 @@ pub fn init_pool(
         token_vault_1: ctx.accounts.token_1_vault.key(),
         global_liquidity: 0,
         sqrt_price_x96: initialize_sqrt_price,
-        current_tick: sqrt_price_x96_to_tick(initialize_sqrt_price)?,
+        current_tick: if initialize_sqrt_price == 0 {
+            0
+        } else {
+            sqrt_price_x96_to_tick(initialize_sqrt_price)?
+        },
         tick_spacing,
         bump: ctx.bumps.pool,
+        transfer_hook_program_0,
+        transfer_hook_program_1,
+        status: 0,
+        limit_order_batch: 0,
     });
 
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_init()?;
+    tick_array_bitmap.pool = pool.key();
+
     Ok(())
 }
+
+// a hooked mint is only listed if the config allows its hook program,
+// returns the program to record on the pool or Pubkey::default() for no hook
+fn validate_transfer_hook(
+    global_config: &GlobalConfig,
+    mint: &InterfaceAccount<Mint>,
+) -> Result<Pubkey> {
+    match get_transfer_hook_program(mint)? {
+        Some(program_id) => {
+            require!(
+                global_config.is_transfer_hook_allowed(&program_id),
+                ClmmError::TransferHookNotAllowed
+            );
+            Ok(program_id)
+        }
+        None => Ok(Pubkey::default()),
+    }
+}
+
+// pays the config's creation fee from the signer to the treasury
+fn charge_creation_fee(accounts: &InitializePool) -> Result<()> {
+    let global_config = &accounts.global_config;
+    let amount = global_config.creation_fee_amount;
+    if amount == 0 {
+        return Ok(());
+    }
+
+    if global_config.creation_fee_mint == Pubkey::default() {
+        let treasury = accounts
+            .treasury
+            .as_ref()
+            .ok_or(ClmmError::InvalidCreationFeeAccount)?;
+        return system_program::transfer(
+            CpiContext::new(
+                accounts.system_program.to_account_info(),
+                Transfer {
+                    from: accounts.signer.to_account_info(),
+                    to: treasury.to_account_info(),
+                },
+            ),
+            amount,
+        );
+    }
+
+    // the account constraints tie the token accounts and the program to the mint
+    let (Some(mint), Some(from), Some(to), Some(token_program)) = (
+        accounts.creation_fee_mint.as_ref(),
+        accounts.creation_fee_payer_account.as_ref(),
+        accounts.creation_fee_treasury_account.as_ref(),
+        accounts.creation_fee_token_program.as_ref(),
+    ) else {
+        return err!(ClmmError::InvalidCreationFeeAccount);
+    };
+    require!(
+        mint.key() == global_config.creation_fee_mint && to.owner == global_config.treasury,
+        ClmmError::InvalidCreationFeeAccount
+    );
+
+    transfer_checked(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: accounts.signer.to_account_info(),
+            },
+        ),
+        amount,
+        mint.decimals,
+    )
+}
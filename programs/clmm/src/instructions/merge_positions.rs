@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    core,
+    errors::ClmmError,
+    states::{Pool, Position},
+};
+
+#[derive(Accounts)]
+#[instruction(upper_tick: i32, lower_tick: i32)]
+pub struct MergePositions<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+    mut,
+    close = signer,
+    seeds = [
+        b"position",
+        pool.key().as_ref(),
+        signer.key().as_ref(),
+        lower_tick.to_le_bytes().as_ref(),
+        upper_tick.to_le_bytes().as_ref(),
+        position.id.to_le_bytes().as_ref()
+    ],
+    bump = position.bump
+)]
+    pub position: Box<Account<'info, Position>>,
+    // any position on the same range, whoever owns it
+    #[account(
+        mut,
+        constraint = destination.pool == pool.key() @ ClmmError::InvalidPositionRange,
+)]
+    pub destination: Box<Account<'info, Position>>,
+}
+
+// moves all of the signer's position into `destination` and closes it, no tokens move and
+// the tick arrays are untouched
+pub fn merge_positions(ctx: Context<MergePositions>) -> Result<()> {
+    require_keys_neq!(
+        ctx.accounts.position.key(),
+        ctx.accounts.destination.key(),
+        ClmmError::InvalidPositionRange
+    );
+    core::merge_positions(&mut ctx.accounts.position, &mut ctx.accounts.destination)?;
+
+    msg!("Merged position into {}", ctx.accounts.destination.key());
+
+    Ok(())
+}
@@ -1,15 +1,51 @@
+pub mod accept_admin;
+pub mod approve_position_delegate;
+pub mod cancel_limit_order;
 pub mod close_position;
+pub mod close_tick_array;
 pub mod decrease_liquidity;
 pub mod increase_liquidity;
+pub mod initialize_config;
 pub mod initialize_pool;
+pub mod merge_positions;
+pub mod open_limit_order;
 pub mod open_position;
+pub mod propose_admin;
+pub mod quote_swap;
+pub mod reposition;
+pub mod revoke_position_delegate;
+pub mod set_mint_override;
+pub mod set_pool_creation_config;
+pub mod set_pool_creator;
+pub mod set_pool_status;
+pub mod set_transfer_hook_program;
+pub mod settle_limit_order;
 pub mod shared_functions;
+pub mod split_position;
 pub mod swap;
 
+pub use accept_admin::*;
+pub use approve_position_delegate::*;
+pub use cancel_limit_order::*;
 pub use close_position::*;
+pub use close_tick_array::*;
 pub use decrease_liquidity::*;
 pub use increase_liquidity::*;
+pub use initialize_config::*;
 pub use initialize_pool::*;
+pub use merge_positions::*;
+pub use open_limit_order::*;
 pub use open_position::*;
+pub use propose_admin::*;
+pub use quote_swap::*;
+pub use reposition::*;
+pub use revoke_position_delegate::*;
+pub use set_mint_override::*;
+pub use set_pool_creation_config::*;
+pub use set_pool_creator::*;
+pub use set_pool_status::*;
+pub use set_transfer_hook_program::*;
+pub use settle_limit_order::*;
 pub use shared_functions::*;
+pub use split_position::*;
 pub use swap::*;
\ No newline at end of file
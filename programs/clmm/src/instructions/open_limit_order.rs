@@ -0,0 +1,150 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    core::{self, LiquidityChange},
+    errors::ClmmError,
+    instructions::{get_transfer_inverse_fee, transfer_tokens},
+    states::{LimitOrder, Pool, TickArrayBitmap, TickArrayState},
+};
+
+#[derive(Accounts)]
+#[instruction(tick: i32, zero_for_one: bool, tick_array_start_index: i32)]
+pub struct OpenLimitOrder<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+    mut,
+    has_one = token_0,
+    has_one = token_1
+)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    pub token_0: Box<InterfaceAccount<'info, Mint>>,
+    pub token_1: Box<InterfaceAccount<'info, Mint>>,
+
+    // holds both ticks of the order
+    /// CHECK: created on first use by TickArrayState::get_or_create
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        &tick_array_start_index.to_le_bytes()
+    ],
+    bump
+)]
+    pub tick_array: UncheckedAccount<'info>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
+
+    #[account(
+    init,
+    payer = signer,
+    space = LimitOrder::SPACE,
+    seeds = [
+        b"limit_order",
+        pool.key().as_ref(),
+        signer.key().as_ref(),
+        tick.to_le_bytes().as_ref(),
+        &[zero_for_one as u8]
+    ],
+    bump
+)]
+    pub limit_order: Box<Account<'info, LimitOrder>>,
+    #[account(mut, token::mint = token_0)]
+    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = token_1)]
+    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+    mut,
+    token::mint = token_0,
+    token::authority = pool
+)]
+    pub pool_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+    mut,
+    token::mint = token_1,
+    token::authority = pool
+)]
+    pub pool_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+// returns the amount of the sold token deposited, including any transfer fee
+pub fn open_limit_order<'info>(
+    ctx: Context<'_, '_, '_, 'info, OpenLimitOrder<'info>>,
+    tick: i32,
+    zero_for_one: bool,
+    tick_array_start_index: i32,
+    liquidity_amount: u128,
+) -> Result<u64> {
+    let pool = &mut ctx.accounts.pool;
+    let limit_order = &mut ctx.accounts.limit_order;
+    require!(pool.is_price_initialized(), ClmmError::PoolPriceUninitialized);
+
+    let tick_array = ctx.accounts.tick_array.to_account_info();
+    TickArrayState::get_or_create(
+        &tick_array,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        pool.key(),
+        tick_array_start_index,
+        ctx.bumps.tick_array,
+    )?;
+
+    limit_order.set_inner(LimitOrder {
+        pool: pool.key(),
+        owner: ctx.accounts.signer.key(),
+        tick,
+        zero_for_one,
+        liquidity: 0,
+        batch: 0,
+        bump: ctx.bumps.limit_order,
+    });
+
+    let LiquidityChange { amount_0, amount_1, .. } = {
+        let mut tick_array = TickArrayState::load_mut(&tick_array)?;
+        core::open_limit_order(pool, limit_order, &mut tick_array, liquidity_amount)?
+    };
+
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+    tick_array_bitmap.set_initialized(tick_array_start_index, pool.tick_spacing, true)?;
+    drop(tick_array_bitmap);
+
+    // the order sits on one side of the price, so it only takes the token it sells
+    let (amount, user, vault, mint) = if zero_for_one {
+        (amount_0, &ctx.accounts.user_0, &ctx.accounts.pool_vault_0, &ctx.accounts.token_0)
+    } else {
+        (amount_1, &ctx.accounts.user_1, &ctx.accounts.pool_vault_1, &ctx.accounts.token_1)
+    };
+    let amount = amount
+        .checked_add(get_transfer_inverse_fee(mint, amount)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    if amount > 0 {
+        transfer_tokens(
+            user,
+            vault,
+            &amount,
+            mint,
+            &ctx.accounts.signer,
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
+        )?;
+    }
+
+    Ok(amount)
+}
@@ -2,14 +2,17 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
+    core::{self, LiquidityChange, PositionTickArrays},
     errors::ClmmError,
-    instructions::transfer_tokens,
-    states::{Pool, Position, TickArrayState},
-    utils::*,
+    instructions::{
+        close_native_sol_account, get_transfer_inverse_fee, native_mint_of, transfer_tokens,
+        user_token_account, validate_native_sol, wrap_native_sol,
+    },
+    states::{Pool, Position, TickArrayBitmap, TickArrayState},
 };
 
 #[derive(Accounts)]
-#[instruction(upper_tick: i32, lower_tick: i32, tick_array_lower_start_index:i32, tick_array_upper_start_index:i32)]
+#[instruction(upper_tick: i32, lower_tick: i32, tick_array_lower_start_index:i32, tick_array_upper_start_index:i32, position_id: u64)]
 pub struct OpenPosition<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
@@ -23,10 +26,9 @@ pub struct OpenPosition<'info> {
     pub token_0: Box<InterfaceAccount<'info, Mint>>,
     pub token_1: Box<InterfaceAccount<'info, Mint>>,
 
+    /// CHECK: created on first use by TickArrayState::get_or_create
     #[account(
-    init_if_needed,
-    payer = signer,
-    space = TickArrayState::SPACE,
+    mut,
     seeds = [
         b"tick_array",
         pool.key().as_ref(),
@@ -34,11 +36,10 @@ pub struct OpenPosition<'info> {
     ],
     bump
 )]
-    pub lower_tick_array: Box<Account<'info, TickArrayState>>,
+    pub lower_tick_array: UncheckedAccount<'info>,
+    /// CHECK: created on first use by TickArrayState::get_or_create
     #[account(
-    init_if_needed,
-    payer = signer,
-    space = TickArrayState::SPACE,
+    mut,
     seeds = [
         b"tick_array",
         pool.key().as_ref(),
@@ -46,7 +47,16 @@ pub struct OpenPosition<'info> {
     ],
     bump
 )]
-    pub upper_tick_array: Box<Account<'info, TickArrayState>>,
+    pub upper_tick_array: UncheckedAccount<'info>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
 
     #[account(
     init,
@@ -57,15 +67,35 @@ pub struct OpenPosition<'info> {
         pool.key().as_ref(),
         signer.key().as_ref(),
         lower_tick.to_le_bytes().as_ref(),
-        upper_tick.to_le_bytes().as_ref()
+        upper_tick.to_le_bytes().as_ref(),
+        position_id.to_le_bytes().as_ref()
     ],
     bump
 )]
     pub position: Box<Account<'info, Position>>,
+    // the WSOL side may be left out in native SOL mode
     #[account(mut)]
-    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_0: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(mut)]
-    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_1: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // the pool's WSOL side, passed in native SOL mode to open the temporary account with
+    #[account(
+        address = native_mint_of(&token_0, &token_1).key() @ ClmmError::NativeSolUnsupported
+    )]
+    pub native_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    // temporary WSOL account for native SOL mode, closed back to the signer before returning
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"native_sol", signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = signer,
+        token::token_program = token_program
+    )]
+    pub native_sol_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
     mut,
@@ -85,86 +115,141 @@ pub struct OpenPosition<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn open_position(
-    ctx: Context<OpenPosition>,
+#[allow(clippy::too_many_arguments)]
+pub fn open_position<'info>(
+    ctx: Context<'_, '_, '_, 'info, OpenPosition<'info>>,
     upper_tick: i32,
     lower_tick: i32,
     tick_array_lower_start_index: i32,
     tick_array_upper_start_index: i32,
+    position_id: u64,
     liquidity_amount: u128,
+    native_sol: bool,
+    sqrt_price_x96: u128,
+    max_slippage_bps: u16,
 ) -> Result<(u64, u64)> {
     let pool = &mut ctx.accounts.pool;
     let position = &mut ctx.accounts.position;
 
-    require!(liquidity_amount > 0, ClmmError::ZeroAmount);
+    validate_native_sol(pool, native_sol, ctx.accounts.native_sol_account.as_deref())?;
+    pool.initialize_or_check_price(sqrt_price_x96, max_slippage_bps)?;
 
-    let upper_tick_array = &mut ctx.accounts.upper_tick_array;
-    let lower_tick_array = &mut ctx.accounts.lower_tick_array;
+    let lower_tick_array = ctx.accounts.lower_tick_array.to_account_info();
+    let upper_tick_array = ctx.accounts.upper_tick_array.to_account_info();
 
-    if lower_tick_array.starting_tick == 0 && lower_tick_array.pool == Pubkey::default() {
-        lower_tick_array.pool = pool.key();
-        lower_tick_array.starting_tick = tick_array_lower_start_index;
-    }
-
-    if upper_tick_array.starting_tick == 0 && upper_tick_array.pool == Pubkey::default() {
-        upper_tick_array.pool = pool.key();
-        upper_tick_array.starting_tick = tick_array_upper_start_index;
-    }
-
-    let lower_tick_state = lower_tick_array.get_tick_state_mut(lower_tick, pool.tick_spacing)?;
-
-    let upper_tick_state = upper_tick_array.get_tick_state_mut(upper_tick, pool.tick_spacing)?;
-
-    lower_tick_state.update_liquidity(liquidity_amount as i128, true)?;
-    upper_tick_state.update_liquidity(liquidity_amount as i128, false)?;
+    TickArrayState::get_or_create(
+        &lower_tick_array,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        pool.key(),
+        tick_array_lower_start_index,
+        ctx.bumps.lower_tick_array,
+    )?;
+    TickArrayState::get_or_create(
+        &upper_tick_array,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        pool.key(),
+        tick_array_upper_start_index,
+        ctx.bumps.upper_tick_array,
+    )?;
 
     position.set_inner(Position {
-        liquidity: liquidity_amount,
+        liquidity: 0,
         lower_tick,
         upper_tick,
         owner: ctx.accounts.signer.key(),
         pool: pool.key(),
+        id: position_id,
         bump: ctx.bumps.position,
     });
 
-    let lower_sqrt = tick_to_sqrt_price_x96(lower_tick)?;
-    let upper_sqrt = tick_to_sqrt_price_x96(upper_tick)?;
-
-    if pool.sqrt_price_x96 >= lower_sqrt && pool.sqrt_price_x96 < upper_sqrt {
-        pool.global_liquidity = pool
-            .global_liquidity
-            .checked_add(liquidity_amount)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-    }
-
-    let (amount_0, amount_1) = get_amounts_for_liquidity(
-        pool.sqrt_price_x96,
-        lower_sqrt,
-        upper_sqrt,
-        liquidity_amount,
+    let LiquidityChange { amount_0, amount_1, .. } = {
+        let (mut lower, mut upper) =
+            TickArrayState::load_position_arrays(&lower_tick_array, &upper_tick_array)?;
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: upper.as_deref_mut(),
+        };
+        core::open_position(pool, position, tick_arrays, liquidity_amount)?
+    };
+
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+    tick_array_bitmap.set_initialized(tick_array_lower_start_index, pool.tick_spacing, true)?;
+    tick_array_bitmap.set_initialized(tick_array_upper_start_index, pool.tick_spacing, true)?;
+    drop(tick_array_bitmap);
+
+    // the vaults must receive the full amounts, so the user also covers any transfer fee
+    let amount_0 = amount_0
+        .checked_add(get_transfer_inverse_fee(&ctx.accounts.token_0, amount_0)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    let amount_1 = amount_1
+        .checked_add(get_transfer_inverse_fee(&ctx.accounts.token_1, amount_1)?)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    let native_sol_account = ctx.accounts.native_sol_account.as_deref();
+    let user_0 = user_token_account(
+        ctx.accounts.user_0.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_0,
+    )?;
+    let user_1 = user_token_account(
+        ctx.accounts.user_1.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_1,
     )?;
 
     if amount_0 > 0 {
+        if native_sol {
+            wrap_native_sol(
+                &ctx.accounts.signer,
+                user_0,
+                &ctx.accounts.token_0,
+                amount_0,
+                &ctx.accounts.system_program,
+                &ctx.accounts.token_program,
+            )?;
+        }
         transfer_tokens(
-            &ctx.accounts.user_0,
+            user_0,
             &ctx.accounts.pool_vault_0,
             &amount_0,
             &ctx.accounts.token_0,
             &ctx.accounts.signer,
             &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
     if amount_1 > 0 {
+        if native_sol {
+            wrap_native_sol(
+                &ctx.accounts.signer,
+                user_1,
+                &ctx.accounts.token_1,
+                amount_1,
+                &ctx.accounts.system_program,
+                &ctx.accounts.token_program,
+            )?;
+        }
         transfer_tokens(
-            &ctx.accounts.user_1,
+            user_1,
             &ctx.accounts.pool_vault_1,
             &amount_1,
             &ctx.accounts.token_1,
             &ctx.accounts.signer,
             &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
         )?;
     }
 
+    close_native_sol_account(
+        &ctx.accounts.signer,
+        native_sol_account,
+        &ctx.accounts.token_program,
+    )?;
+
     Ok((amount_0, amount_1))
 }
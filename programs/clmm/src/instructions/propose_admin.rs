@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::ClmmError, states::GlobalConfig};
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+// first step of an admin transfer, nothing changes until `new_admin` signs accept_admin,
+// so a mistyped key can be replaced or cleared with Pubkey::default()
+pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+    ctx.accounts.global_config.pending_admin = new_admin;
+
+    msg!("Proposed admin: {}", new_admin);
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    errors::ClmmError,
+    instructions::compute_swap_with_transfer_fees,
+    states::{Pool, TickArrayState},
+};
+
+#[derive(Accounts)]
+#[instruction(amount_in: u64, a_to_b: bool)]
+pub struct QuoteSwap<'info> {
+    #[account(
+        has_one = token_0,
+        has_one = token_1
+    )]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: address checked here, may not exist yet, see TickArrayState::load_if_created
+    #[account(
+        address = TickArrayState::swap_tick_array(&pool, a_to_b) @ ClmmError::InvalidTickArrayAccount
+    )]
+    pub tick_array: UncheckedAccount<'info>,
+
+    pub token_0: Box<InterfaceAccount<'info, Mint>>,
+    pub token_1: Box<InterfaceAccount<'info, Mint>>,
+}
+
+// previews a swap without moving tokens or writing to the pool
+// returns (amount_in, amount_out, end_sqrt_price_x96, end_tick), with amounts
+// including any Token-2022 transfer fees the same way `swap` charges them
+pub fn quote_swap(
+    ctx: Context<QuoteSwap>,
+    amount_in: u64,
+    a_to_b: bool,
+) -> Result<(u64, u64, u128, i32)> {
+    let (mint_in, mint_out) = if a_to_b {
+        (&ctx.accounts.token_0, &ctx.accounts.token_1)
+    } else {
+        (&ctx.accounts.token_1, &ctx.accounts.token_0)
+    };
+    let tick_array = TickArrayState::load_if_created(&ctx.accounts.tick_array)?;
+    let result = compute_swap_with_transfer_fees(
+        &ctx.accounts.pool,
+        tick_array.as_deref(),
+        mint_in,
+        mint_out,
+        amount_in,
+        a_to_b,
+    )?;
+
+    Ok((
+        result.amount_in,
+        result.amount_out,
+        result.sqrt_price_x96,
+        result.tick,
+    ))
+}
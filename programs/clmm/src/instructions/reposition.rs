@@ -0,0 +1,423 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    core::{self, LiquidityChange, PositionTickArrays, RepositionBalance},
+    errors::ClmmError,
+    instructions::{
+        close_native_sol_account, get_transfer_fee, get_transfer_inverse_fee, native_mint_of,
+        transfer_from_pda, transfer_tokens, user_token_account, validate_native_sol,
+        wrap_native_sol,
+    },
+    states::{
+        Pool, Position, PositionDelegate, TickArrayBitmap, TickArrayState,
+        POSITION_DELEGATE_REPOSITION,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(
+    upper_tick: i32,
+    lower_tick: i32,
+    new_upper_tick: i32,
+    new_lower_tick: i32,
+    tick_array_lower_start_index: i32,
+    tick_array_upper_start_index: i32,
+    new_tick_array_lower_start_index: i32,
+    new_tick_array_upper_start_index: i32,
+    swap_amount_in: u64,
+    a_to_b: bool
+)]
+pub struct Reposition<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+    mut,
+    has_one = token_0,
+    has_one = token_1
+)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    pub token_0: Box<InterfaceAccount<'info, Mint>>,
+    pub token_1: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        &tick_array_lower_start_index.to_le_bytes()
+    ],
+    bump
+)]
+    pub lower_tick_array: AccountLoader<'info, TickArrayState>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        tick_array_upper_start_index.to_le_bytes().as_ref()
+    ],
+    bump
+)]
+    pub upper_tick_array: AccountLoader<'info, TickArrayState>,
+    /// CHECK: created on first use by TickArrayState::get_or_create
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        &new_tick_array_lower_start_index.to_le_bytes()
+    ],
+    bump
+)]
+    pub new_lower_tick_array: UncheckedAccount<'info>,
+    /// CHECK: created on first use by TickArrayState::get_or_create
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        new_tick_array_upper_start_index.to_le_bytes().as_ref()
+    ],
+    bump
+)]
+    pub new_upper_tick_array: UncheckedAccount<'info>,
+    // array holding the tick the swap crosses, see core::compute_swap, unused without a swap
+    /// CHECK: address checked here, may not exist yet, see TickArrayState::load_mut_if_created
+    #[account(
+        mut,
+        address = TickArrayState::swap_tick_array(&pool, a_to_b) @ ClmmError::InvalidTickArrayAccount
+    )]
+    pub swap_tick_array: UncheckedAccount<'info>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
+
+    /// CHECK: the position owner, who gets the old position's rent back
+    #[account(mut, address = position.owner @ ClmmError::InvalidPositionOwner)]
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+    mut,
+    close = owner,
+    seeds = [
+        b"position",
+        pool.key().as_ref(),
+        position.owner.as_ref(),
+        lower_tick.to_le_bytes().as_ref(),
+        upper_tick.to_le_bytes().as_ref(),
+        position.id.to_le_bytes().as_ref()
+    ],
+    bump = position.bump
+)]
+    pub position: Box<Account<'info, Position>>,
+    // the owner's approval when an operator signs, see Position::require_authority
+    #[account(
+    seeds = [
+        b"position_delegate",
+        pool.key().as_ref(),
+        position.owner.as_ref(),
+        signer.key().as_ref()
+    ],
+    bump = position_delegate.bump
+)]
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
+    // the position on the new range keeps the old one's id
+    #[account(
+    init,
+    payer = signer,
+    space = Position::SPACE,
+    seeds = [
+        b"position",
+        pool.key().as_ref(),
+        position.owner.as_ref(),
+        new_lower_tick.to_le_bytes().as_ref(),
+        new_upper_tick.to_le_bytes().as_ref(),
+        position.id.to_le_bytes().as_ref()
+    ],
+    bump
+)]
+    pub new_position: Box<Account<'info, Position>>,
+    // the WSOL side may be left out in native SOL mode
+    #[account(mut, token::mint = token_0)]
+    pub user_0: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, token::mint = token_1)]
+    pub user_1: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // the pool's WSOL side, passed in native SOL mode to open the temporary account with
+    #[account(
+        address = native_mint_of(&token_0, &token_1).key() @ ClmmError::NativeSolUnsupported
+    )]
+    pub native_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    // temporary WSOL account for native SOL mode, closed back to the signer before returning
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"native_sol", signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = signer,
+        token::token_program = token_program
+    )]
+    pub native_sol_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+    mut,
+    token::mint = token_0,
+    token::authority = pool
+)]
+    pub pool_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+    mut,
+    token::mint = token_1,
+    token::authority = pool
+)]
+    pub pool_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+// moves all of a position's liquidity to a new range in one step: withdraws the old range,
+// optionally swaps `swap_amount_in` against the pool to reach the new range's token ratio, for
+// at least `min_amount_out`, and opens the new range with `liquidity_amount`, all without the tokens leaving the vaults. the
+// owner only settles the difference, returned as what they received per token (negative when
+// they paid, transfer fees included either way)
+#[allow(clippy::too_many_arguments)]
+pub fn reposition<'info>(
+    ctx: Context<'_, '_, '_, 'info, Reposition<'info>>,
+    new_upper_tick: i32,
+    new_lower_tick: i32,
+    new_tick_array_lower_start_index: i32,
+    new_tick_array_upper_start_index: i32,
+    swap_amount_in: u64,
+    a_to_b: bool,
+    min_amount_out: u64,
+    liquidity_amount: u128,
+    sqrt_price_x96: u128,
+    max_slippage_bps: u16,
+    native_sol: bool,
+) -> Result<(i64, i64)> {
+    let pool = &mut ctx.accounts.pool;
+    let position = &mut ctx.accounts.position;
+    let new_position = &mut ctx.accounts.new_position;
+
+    validate_native_sol(pool, native_sol, ctx.accounts.native_sol_account.as_deref())?;
+    position.require_authority(
+        &ctx.accounts.signer.key(),
+        ctx.accounts.position_delegate.as_deref(),
+        POSITION_DELEGATE_REPOSITION,
+    )?;
+    // an operator settles with the owner's accounts, paying in through a token delegation,
+    // which also rules out native SOL mode as that settles with the signer
+    if ctx.accounts.signer.key() != position.owner {
+        let native_sol_account = ctx.accounts.native_sol_account.as_deref();
+        let user_0 = user_token_account(
+            ctx.accounts.user_0.as_deref(),
+            native_sol_account,
+            &ctx.accounts.token_0,
+        )?;
+        let user_1 = user_token_account(
+            ctx.accounts.user_1.as_deref(),
+            native_sol_account,
+            &ctx.accounts.token_1,
+        )?;
+        require!(
+            user_0.owner == position.owner && user_1.owner == position.owner,
+            ClmmError::InvalidWithdrawalAccount
+        );
+    }
+    // bounds every step, which all follow from the price the reposition starts at, so unlike
+    // open_position a zero expected price does not skip it
+    pool.check_price(sqrt_price_x96, max_slippage_bps)?;
+
+    let mut balance = RepositionBalance::default();
+    let withdrawn = {
+        let (mut lower, mut upper) = TickArrayState::load_position_arrays(
+            ctx.accounts.lower_tick_array.as_ref(),
+            ctx.accounts.upper_tick_array.as_ref(),
+        )?;
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: upper.as_deref_mut(),
+        };
+        core::close_position(pool, position, tick_arrays)?
+    };
+    balance.withdraw(&withdrawn);
+
+    if swap_amount_in > 0 {
+        // tokens swapped inside the vaults pay no transfer fee
+        let mut tick_array = TickArrayState::load_mut_if_created(&ctx.accounts.swap_tick_array)?;
+        let result = core::swap(pool, tick_array.as_deref_mut(), swap_amount_in, a_to_b)?;
+        require!(result.amount_out >= min_amount_out, ClmmError::SlippageExceeded);
+        balance.swap(&result, a_to_b);
+    }
+
+    let new_lower_tick_array = ctx.accounts.new_lower_tick_array.to_account_info();
+    let new_upper_tick_array = ctx.accounts.new_upper_tick_array.to_account_info();
+    TickArrayState::get_or_create(
+        &new_lower_tick_array,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        pool.key(),
+        new_tick_array_lower_start_index,
+        ctx.bumps.new_lower_tick_array,
+    )?;
+    TickArrayState::get_or_create(
+        &new_upper_tick_array,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        pool.key(),
+        new_tick_array_upper_start_index,
+        ctx.bumps.new_upper_tick_array,
+    )?;
+
+    new_position.set_inner(Position {
+        liquidity: 0,
+        lower_tick: new_lower_tick,
+        upper_tick: new_upper_tick,
+        owner: position.owner,
+        pool: pool.key(),
+        id: position.id,
+        bump: ctx.bumps.new_position,
+    });
+    let deposited = {
+        let (mut lower, mut upper) =
+            TickArrayState::load_position_arrays(&new_lower_tick_array, &new_upper_tick_array)?;
+        let tick_arrays = PositionTickArrays {
+            lower: &mut lower,
+            upper: upper.as_deref_mut(),
+        };
+        core::open_position(pool, new_position, tick_arrays, liquidity_amount)?
+    };
+    balance.deposit(&deposited);
+
+    // clear the arrays the old range emptied before marking the new range's, they may overlap
+    let LiquidityChange {
+        lower_array_emptied,
+        upper_array_emptied,
+        ..
+    } = withdrawn;
+    let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+    if lower_array_emptied {
+        tick_array_bitmap.set_initialized(
+            TickArrayState::get_start_tick_idx(position.lower_tick, pool.tick_spacing),
+            pool.tick_spacing,
+            false,
+        )?;
+    }
+    if upper_array_emptied {
+        tick_array_bitmap.set_initialized(
+            TickArrayState::get_start_tick_idx(position.upper_tick, pool.tick_spacing),
+            pool.tick_spacing,
+            false,
+        )?;
+    }
+    tick_array_bitmap.set_initialized(
+        new_tick_array_lower_start_index,
+        pool.tick_spacing,
+        true,
+    )?;
+    tick_array_bitmap.set_initialized(
+        new_tick_array_upper_start_index,
+        pool.tick_spacing,
+        true,
+    )?;
+    drop(tick_array_bitmap);
+
+    let received_0 = settle_balance(
+        ctx.accounts,
+        ctx.remaining_accounts,
+        balance.amount_0,
+        true,
+        native_sol,
+    )?;
+    let received_1 = settle_balance(
+        ctx.accounts,
+        ctx.remaining_accounts,
+        balance.amount_1,
+        false,
+        native_sol,
+    )?;
+
+    close_native_sol_account(
+        &ctx.accounts.signer,
+        ctx.accounts.native_sol_account.as_deref(),
+        &ctx.accounts.token_program,
+    )?;
+
+    Ok((received_0, received_1))
+}
+
+// settles one token's side of the balance: pays a positive amount out of the vault, or pulls
+// a negative one from the owner grossed up by any transfer fee
+fn settle_balance<'info>(
+    accounts: &Reposition<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    balance: i128,
+    token_0: bool,
+    native_sol: bool,
+) -> Result<i64> {
+    let (user, vault, mint) = if token_0 {
+        (accounts.user_0.as_deref(), &accounts.pool_vault_0, &accounts.token_0)
+    } else {
+        (accounts.user_1.as_deref(), &accounts.pool_vault_1, &accounts.token_1)
+    };
+    let user = user_token_account(user, accounts.native_sol_account.as_deref(), mint)?;
+    let amount =
+        u64::try_from(balance.unsigned_abs()).map_err(|_| ClmmError::ArithmeticOverflow)?;
+    if amount == 0 {
+        return Ok(0);
+    }
+
+    if balance > 0 {
+        transfer_from_pda(
+            vault,
+            user,
+            &amount,
+            mint,
+            &accounts.token_program,
+            &accounts.pool,
+            remaining_accounts,
+        )?;
+        let received = amount
+            .checked_sub(get_transfer_fee(mint, amount)?)
+            .ok_or(ClmmError::ArithmeticOverflow)?;
+        i64::try_from(received).map_err(|_| ClmmError::ArithmeticOverflow.into())
+    } else {
+        let amount = amount
+            .checked_add(get_transfer_inverse_fee(mint, amount)?)
+            .ok_or(ClmmError::ArithmeticOverflow)?;
+        if native_sol {
+            wrap_native_sol(
+                &accounts.signer,
+                user,
+                mint,
+                amount,
+                &accounts.system_program,
+                &accounts.token_program,
+            )?;
+        }
+        transfer_tokens(
+            user,
+            vault,
+            &amount,
+            mint,
+            &accounts.signer,
+            &accounts.token_program,
+            &accounts.pool,
+            remaining_accounts,
+        )?;
+        i64::try_from(amount)
+            .map(|paid| -paid)
+            .map_err(|_| ClmmError::ArithmeticOverflow.into())
+    }
+}
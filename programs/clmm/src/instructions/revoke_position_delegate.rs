@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+use crate::states::{Pool, PositionDelegate};
+
+#[derive(Accounts)]
+#[instruction(operator: Pubkey)]
+pub struct RevokePositionDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+    mut,
+    close = owner,
+    seeds = [
+        b"position_delegate",
+        pool.key().as_ref(),
+        owner.key().as_ref(),
+        operator.as_ref()
+    ],
+    bump = position_delegate.bump
+)]
+    pub position_delegate: Account<'info, PositionDelegate>,
+}
+
+// withdraws every permission of `operator` over the signer's positions in the pool
+pub fn revoke_position_delegate(
+    _ctx: Context<RevokePositionDelegate>,
+    operator: Pubkey,
+) -> Result<()> {
+    msg!("Position delegate revoked: operator={}", operator);
+
+    Ok(())
+}
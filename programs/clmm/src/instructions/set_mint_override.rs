@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::ClmmError, states::GlobalConfig};
+
+#[derive(Accounts)]
+pub struct SetMintOverride<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+// lets a vetted mint be listed despite extensions `initialize_pool` would reject,
+// transfer fee and transfer hook checks still apply to it
+pub fn set_mint_override(ctx: Context<SetMintOverride>, mint: Pubkey, allowed: bool) -> Result<()> {
+    ctx.accounts.global_config.set_mint_override(mint, allowed)
+}
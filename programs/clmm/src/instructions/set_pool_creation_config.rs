@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    states::{GlobalConfig, POOL_CREATION_ALLOWLISTED},
+};
+
+#[derive(Accounts)]
+pub struct SetPoolCreationConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+// sets who may create pools and what it costs, a zero fee amount disables the fee and
+// a default fee mint charges it in lamports
+pub fn set_pool_creation_config(
+    ctx: Context<SetPoolCreationConfig>,
+    pool_creation_mode: u8,
+    treasury: Pubkey,
+    creation_fee_mint: Pubkey,
+    creation_fee_amount: u64,
+) -> Result<()> {
+    require!(
+        pool_creation_mode <= POOL_CREATION_ALLOWLISTED,
+        ClmmError::InvalidPoolCreationMode
+    );
+
+    let global_config = &mut ctx.accounts.global_config;
+    global_config.pool_creation_mode = pool_creation_mode;
+    global_config.treasury = treasury;
+    global_config.creation_fee_mint = creation_fee_mint;
+    global_config.creation_fee_amount = creation_fee_amount;
+
+    Ok(())
+}
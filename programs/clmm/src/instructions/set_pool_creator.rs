@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::ClmmError, states::GlobalConfig};
+
+#[derive(Accounts)]
+pub struct SetPoolCreator<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+// adds or removes a creator used by POOL_CREATION_ALLOWLISTED mode
+pub fn set_pool_creator(ctx: Context<SetPoolCreator>, creator: Pubkey, allowed: bool) -> Result<()> {
+    ctx.accounts.global_config.set_pool_creator(creator, allowed)
+}
@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    states::{GlobalConfig, Pool, POOL_STATUS_ALL},
+};
+
+#[derive(Accounts)]
+pub struct SetPoolStatus<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+}
+
+// replaces the pool's POOL_STATUS_* bits, e.g. POOL_STATUS_EMERGENCY freezes swaps and
+// deposits while LPs can still withdraw, 0 re-enables everything
+pub fn set_pool_status(ctx: Context<SetPoolStatus>, status: u8) -> Result<()> {
+    require!(status & !POOL_STATUS_ALL == 0, ClmmError::InvalidPoolStatus);
+    ctx.accounts.pool.status = status;
+
+    msg!("Pool status: {:#06b}", status);
+
+    Ok(())
+}
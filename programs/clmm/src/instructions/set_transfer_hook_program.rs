@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::ClmmError, states::GlobalConfig};
+
+#[derive(Accounts)]
+pub struct SetTransferHookProgram<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config".as_ref()],
+        bump = global_config.bump,
+        has_one = admin @ ClmmError::Unauthorized
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+// adds or removes a program from the transfer hook allowlist,
+// pools already holding a hooked mint keep the program they recorded at creation
+pub fn set_transfer_hook_program(
+    ctx: Context<SetTransferHookProgram>,
+    program_id: Pubkey,
+    allowed: bool,
+) -> Result<()> {
+    ctx.accounts
+        .global_config
+        .set_transfer_hook_program(program_id, allowed)
+}
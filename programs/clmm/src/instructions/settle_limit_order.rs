@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    core::{self, LiquidityChange},
+    errors::ClmmError,
+    instructions::{get_transfer_fee, transfer_from_pda},
+    states::{LimitOrder, Pool, TickArrayBitmap, TickArrayState},
+};
+
+// callable by anyone: the output goes to the owner's token account and the rent to the owner
+#[derive(Accounts)]
+#[instruction(tick_array_start_index: i32)]
+pub struct SettleLimitOrder<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+    mut,
+    has_one = token_0,
+    has_one = token_1
+)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    pub token_0: Box<InterfaceAccount<'info, Mint>>,
+    pub token_1: Box<InterfaceAccount<'info, Mint>>,
+
+    // may have been closed after a swap filled the order
+    /// CHECK: address checked here, may not exist yet, see TickArrayState::load_if_created
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array",
+        pool.key().as_ref(),
+        &tick_array_start_index.to_le_bytes()
+    ],
+    bump
+)]
+    pub tick_array: UncheckedAccount<'info>,
+    #[account(
+    mut,
+    seeds = [
+        b"tick_array_bitmap",
+        pool.key().as_ref()
+    ],
+    bump
+)]
+    pub tick_array_bitmap: AccountLoader<'info, TickArrayBitmap>,
+
+    #[account(
+    mut,
+    close = owner,
+    has_one = owner,
+    constraint = limit_order.pool == pool.key() @ ClmmError::InvalidPositionRange,
+)]
+    pub limit_order: Box<Account<'info, LimitOrder>>,
+    /// CHECK: only receives the order's rent, checked against limit_order.owner
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    // receives the token the order bought
+    #[account(
+        mut,
+        token::mint = if limit_order.zero_for_one { token_1.key() } else { token_0.key() },
+        token::authority = owner
+    )]
+    pub owner_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+    mut,
+    token::mint = token_0,
+    token::authority = pool
+)]
+    pub pool_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+    mut,
+    token::mint = token_1,
+    token::authority = pool
+)]
+    pub pool_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// pays a filled order out to its owner and closes it, returns the amount the owner receives
+// after any transfer fee
+pub fn settle_limit_order<'info>(
+    ctx: Context<'_, '_, '_, 'info, SettleLimitOrder<'info>>,
+    tick_array_start_index: i32,
+) -> Result<u64> {
+    let pool = &mut ctx.accounts.pool;
+    let zero_for_one = ctx.accounts.limit_order.zero_for_one;
+    // a missing array counts as filled, so it must be the one holding the order
+    require!(
+        tick_array_start_index
+            == TickArrayState::get_start_tick_idx(ctx.accounts.limit_order.tick, pool.tick_spacing),
+        ClmmError::InvalidTickArrayAccount
+    );
+
+    let (LiquidityChange { amount_0, amount_1, .. }, array_emptied) = {
+        let mut tick_array = TickArrayState::load_mut_if_created(&ctx.accounts.tick_array)?;
+        let limit_order = &mut ctx.accounts.limit_order;
+        let change = core::settle_limit_order(pool, limit_order, tick_array.as_deref_mut())?;
+        // a swap filling the array's last orders emptied it without access to the bitmap
+        let array_emptied = change.lower_array_emptied
+            || tick_array.as_deref().is_some_and(TickArrayState::is_empty);
+        (change, array_emptied)
+    };
+
+    if array_emptied {
+        let mut tick_array_bitmap = ctx.accounts.tick_array_bitmap.load_mut()?;
+        tick_array_bitmap.set_initialized(tick_array_start_index, pool.tick_spacing, false)?;
+    }
+
+    let (amount, vault, mint) = if zero_for_one {
+        (amount_1, &ctx.accounts.pool_vault_1, &ctx.accounts.token_1)
+    } else {
+        (amount_0, &ctx.accounts.pool_vault_0, &ctx.accounts.token_0)
+    };
+    if amount > 0 {
+        transfer_from_pda(
+            vault,
+            &ctx.accounts.owner_token_account,
+            &amount,
+            mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool,
+            ctx.remaining_accounts,
+        )?;
+    }
+
+    amount
+        .checked_sub(get_transfer_fee(mint, amount)?)
+        .ok_or(ClmmError::ArithmeticOverflow.into())
+}
@@ -1,10 +1,27 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+use anchor_spl::{
+    token::{spl_token, ID as TOKEN_PROGRAM_ID},
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            default_account_state::DefaultAccountState,
+            transfer_fee::{TransferFee, TransferFeeConfig, MAX_FEE_BASIS_POINTS},
+            transfer_hook, BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+        state::AccountState,
+        onchain::invoke_transfer_checked,
+    },
+    token_interface::{
+        close_account, spl_token_2022::native_mint, sync_native, CloseAccount, Mint,
+        SyncNative, TokenAccount, TokenInterface,
+    },
 };
 
-use crate::states::Pool;
+use anchor_lang::system_program::{transfer, Transfer};
 
+use crate::{errors::ClmmError, states::Pool};
+
+#[allow(clippy::too_many_arguments)]
 pub fn transfer_tokens<'info>(
     from: &InterfaceAccount<'info, TokenAccount>,
     to: &InterfaceAccount<'info, TokenAccount>,
@@ -12,17 +29,24 @@ pub fn transfer_tokens<'info>(
     mint: &InterfaceAccount<'info, Mint>,
     authority: &Signer<'info>,
     token_program: &Interface<'info, TokenInterface>,
+    pool: &Account<'info, Pool>,
+    remaining_accounts: &[AccountInfo<'info>],
 ) -> Result<()> {
-    let transfer_account_options = TransferChecked {
-        from: from.to_account_info(),
-        mint: mint.to_account_info(),
-        to: to.to_account_info(),
-        authority: authority.to_account_info(),
-    };
+    check_transfer_hook(pool, mint)?;
 
-    let cpi_context = CpiContext::new(token_program.to_account_info(), transfer_account_options);
-
-    transfer_checked(cpi_context, *amount, mint.decimals)
+    // resolves the hook's ExtraAccountMetaList out of `remaining_accounts`
+    invoke_transfer_checked(
+        token_program.key,
+        from.to_account_info(),
+        mint.to_account_info(),
+        to.to_account_info(),
+        authority.to_account_info(),
+        remaining_accounts,
+        *amount,
+        mint.decimals,
+        &[],
+    )?;
+    Ok(())
 }
 
 pub fn transfer_from_pda<'info>(
@@ -32,13 +56,9 @@ pub fn transfer_from_pda<'info>(
     mint: &InterfaceAccount<'info, Mint>,
     token_program: &Interface<'info, TokenInterface>,
     pool: &Account<'info, Pool>,
+    remaining_accounts: &[AccountInfo<'info>],
 ) -> Result<()> {
-    let transfer_account_options = TransferChecked {
-        from: from.to_account_info(),
-        mint: mint.to_account_info(),
-        to: to.to_account_info(),
-        authority: pool.to_account_info(),
-    };
+    check_transfer_hook(pool, mint)?;
 
     let seeds: &[&[u8]] = &[
         b"pool",
@@ -48,11 +68,318 @@ pub fn transfer_from_pda<'info>(
         &[pool.bump],
     ];
 
-    let signer_seeds = &[seeds];
-    let cpi_ctx = CpiContext::new_with_signer(
+    invoke_transfer_checked(
+        token_program.key,
+        from.to_account_info(),
+        mint.to_account_info(),
+        to.to_account_info(),
+        pool.to_account_info(),
+        remaining_accounts,
+        *amount,
+        mint.decimals,
+        &[seeds],
+    )?;
+    Ok(())
+}
+
+// reads the TransferHook extension of a Token-2022 mint, classic SPL mints have none
+pub fn get_transfer_hook_program(mint: &InterfaceAccount<Mint>) -> Result<Option<Pubkey>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner == TOKEN_PROGRAM_ID {
+        return Ok(None);
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    Ok(transfer_hook::get_program_id(&mint_state))
+}
+
+// the hook authority can repoint a mint at any program after the pool was created,
+// only the program vetted at creation is ever invoked from a vault transfer
+fn check_transfer_hook(pool: &Pool, mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let current = get_transfer_hook_program(mint)?.unwrap_or_default();
+    require_keys_eq!(
+        current,
+        pool.transfer_hook_program(&mint.key())?,
+        ClmmError::TransferHookProgramChanged
+    );
+    Ok(())
+}
+
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == native_mint::ID
+}
+
+// native SOL mode only makes sense for pools with a wrapped SOL side, and moves that side
+// through the instruction's own temporary account, which is only opened in that mode
+pub fn validate_native_sol(
+    pool: &Pool,
+    native_sol: bool,
+    native_sol_account: Option<&InterfaceAccount<TokenAccount>>,
+) -> Result<()> {
+    require!(
+        !native_sol || is_native_mint(&pool.token_0) || is_native_mint(&pool.token_1),
+        ClmmError::NativeSolUnsupported
+    );
+    require!(
+        native_sol == native_sol_account.is_some(),
+        ClmmError::InvalidNativeSolAccount
+    );
+    Ok(())
+}
+
+// the WSOL side of a pool, the mint a native SOL instruction opens its temporary account for
+pub fn native_mint_of<'a, 'info>(
+    token_0: &'a InterfaceAccount<'info, Mint>,
+    token_1: &'a InterfaceAccount<'info, Mint>,
+) -> &'a InterfaceAccount<'info, Mint> {
+    if is_native_mint(&token_0.key()) {
+        token_0
+    } else {
+        token_1
+    }
+}
+
+// the account the user's side of `mint` moves through: the temporary WSOL account in native
+// SOL mode, the user's own token account otherwise
+pub fn user_token_account<'a, 'info>(
+    user: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    native_sol_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    mint: &InterfaceAccount<'info, Mint>,
+) -> Result<&'a InterfaceAccount<'info, TokenAccount>> {
+    match native_sol_account {
+        Some(account) if account.mint == mint.key() => Ok(account),
+        _ => user.ok_or_else(|| error!(ClmmError::MissingTokenAccount)),
+    }
+}
+
+// moves `amount` lamports from the signer into the temporary WSOL account and syncs its
+// token balance, so the following transfer can pull them, no-op for other mints
+pub fn wrap_native_sol<'info>(
+    signer: &Signer<'info>,
+    account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    amount: u64,
+    system_program: &Program<'info, System>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    if !is_native_mint(&mint.key()) || amount == 0 {
+        return Ok(());
+    }
+
+    transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            Transfer {
+                from: signer.to_account_info(),
+                to: account.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+    sync_native(CpiContext::new(
         token_program.to_account_info(),
-        transfer_account_options,
-        signer_seeds,
+        SyncNative {
+            account: account.to_account_info(),
+        },
+    ))
+}
+
+// closes the temporary WSOL account opened for this instruction, paying whatever it received
+// and its rent back to the signer as SOL, no-op outside native SOL mode
+pub fn close_native_sol_account<'info>(
+    signer: &Signer<'info>,
+    native_sol_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let Some(account) = native_sol_account else {
+        return Ok(());
+    };
+
+    close_account(CpiContext::new(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: account.to_account_info(),
+            destination: signer.to_account_info(),
+            authority: signer.to_account_info(),
+        },
+    ))
+}
+
+// rejects Token-2022 mints whose extensions can freeze, drain or reprice the vaults,
+// only extensions the pool knows how to handle are let through
+pub fn validate_mint_extensions(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner == TOKEN_PROGRAM_ID {
+        return Ok(());
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    check_mint_extensions(&mint_state)
+}
+
+fn check_mint_extensions<S: BaseStateWithExtensions<spl_token_2022::state::Mint>>(
+    mint_state: &S,
+) -> Result<()> {
+    for extension_type in mint_state.get_extension_types()? {
+        match extension_type {
+            // fee and hook have their own checks, the rest only carry metadata
+            ExtensionType::TransferFeeConfig
+            | ExtensionType::TransferHook
+            | ExtensionType::MintCloseAuthority
+            | ExtensionType::MetadataPointer
+            | ExtensionType::TokenMetadata
+            | ExtensionType::GroupPointer
+            | ExtensionType::TokenGroup
+            | ExtensionType::GroupMemberPointer
+            | ExtensionType::TokenGroupMember => {}
+            ExtensionType::DefaultAccountState => {
+                let default_state = mint_state.get_extension::<DefaultAccountState>()?;
+                require!(
+                    default_state.state != AccountState::Frozen as u8,
+                    ClmmError::MintDefaultStateFrozen
+                );
+            }
+            ExtensionType::PermanentDelegate => return err!(ClmmError::MintHasPermanentDelegate),
+            ExtensionType::NonTransferable => return err!(ClmmError::MintNonTransferable),
+            ExtensionType::ConfidentialTransferMint
+            | ExtensionType::ConfidentialTransferFeeConfig
+            | ExtensionType::ConfidentialMintBurn => {
+                return err!(ClmmError::MintConfidentialTransfer)
+            }
+            ExtensionType::InterestBearingConfig | ExtensionType::ScaledUiAmount => {
+                return err!(ClmmError::MintInterestBearing)
+            }
+            ExtensionType::Pausable => return err!(ClmmError::MintPausable),
+            _ => return err!(ClmmError::UnsupportedMintExtension),
+        }
+    }
+    Ok(())
+}
+
+// reads the TransferFee extension of a Token-2022 mint, classic SPL mints have none
+fn get_transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner == TOKEN_PROGRAM_ID {
+        return Ok(None);
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    Ok(mint_state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+// a 100% fee leaves nothing to gross up for, so the pool cannot price transfers with it
+fn check_transfer_fee(transfer_fee: &TransferFee) -> Result<()> {
+    require!(
+        u16::from(transfer_fee.transfer_fee_basis_points) < MAX_FEE_BASIS_POINTS,
+        ClmmError::UnsupportedTransferFee
     );
-    transfer_checked(cpi_ctx, *amount, mint.decimals)
+    Ok(())
+}
+
+// rejects mints whose current or scheduled transfer fee the pool cannot handle
+pub fn validate_transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    if let Some(transfer_fee_config) = get_transfer_fee_config(mint)? {
+        check_transfer_fee(&transfer_fee_config.older_transfer_fee)?;
+        check_transfer_fee(&transfer_fee_config.newer_transfer_fee)?;
+    }
+    Ok(())
+}
+
+// fee withheld by the mint when `amount` is transferred, the receiver gets amount - fee
+pub fn get_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match get_transfer_fee_config(mint)? {
+        Some(transfer_fee_config) => {
+            let transfer_fee = transfer_fee_config.get_epoch_fee(Clock::get()?.epoch);
+            check_transfer_fee(transfer_fee)?;
+            Ok(transfer_fee
+                .calculate_fee(amount)
+                .ok_or(ClmmError::ArithmeticOverflow)?)
+        }
+        None => Ok(0),
+    }
+}
+
+// fee to add on top of `amount` so the receiver ends up with exactly `amount`
+pub fn get_transfer_inverse_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match get_transfer_fee_config(mint)? {
+        Some(transfer_fee_config) => {
+            let transfer_fee = transfer_fee_config.get_epoch_fee(Clock::get()?.epoch);
+            check_transfer_fee(transfer_fee)?;
+            Ok(transfer_fee
+                .calculate_inverse_fee(amount)
+                .ok_or(ClmmError::ArithmeticOverflow)?)
+        }
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token_2022::spl_token_2022::{
+        extension::{
+            interest_bearing_mint::InterestBearingConfig, permanent_delegate::PermanentDelegate,
+            transfer_hook::TransferHook, BaseStateWithExtensionsMut, StateWithExtensionsMut,
+        },
+        state::Mint as MintState,
+    };
+
+    // builds an initialized mint carrying `extensions`, configured by `init`
+    fn mint_with(
+        extensions: &[ExtensionType],
+        init: impl FnOnce(&mut StateWithExtensionsMut<MintState>),
+    ) -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<MintState>(extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state =
+            StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        init(&mut state);
+        state.base.decimals = 6;
+        state.base.is_initialized = true;
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    fn check(data: &[u8]) -> Result<()> {
+        check_mint_extensions(&StateWithExtensions::<MintState>::unpack(data).unwrap())
+    }
+
+    #[test]
+    fn test_allows_supported_extensions() {
+        let data = mint_with(&[ExtensionType::TransferHook], |state| {
+            state.init_extension::<TransferHook>(true).unwrap();
+        });
+        assert!(check(&data).is_ok());
+
+        let data = mint_with(&[ExtensionType::DefaultAccountState], |state| {
+            state.init_extension::<DefaultAccountState>(true).unwrap().state =
+                AccountState::Initialized as u8;
+        });
+        assert!(check(&data).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_dangerous_extensions() {
+        let data = mint_with(&[ExtensionType::PermanentDelegate], |state| {
+            state.init_extension::<PermanentDelegate>(true).unwrap();
+        });
+        assert_eq!(check(&data).unwrap_err(), ClmmError::MintHasPermanentDelegate.into());
+
+        let data = mint_with(&[ExtensionType::InterestBearingConfig], |state| {
+            state.init_extension::<InterestBearingConfig>(true).unwrap();
+        });
+        assert_eq!(check(&data).unwrap_err(), ClmmError::MintInterestBearing.into());
+
+        let data = mint_with(&[ExtensionType::DefaultAccountState], |state| {
+            state.init_extension::<DefaultAccountState>(true).unwrap().state =
+                AccountState::Frozen as u8;
+        });
+        assert_eq!(check(&data).unwrap_err(), ClmmError::MintDefaultStateFrozen.into());
+    }
 }
@@ -0,0 +1,83 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    core,
+    errors::ClmmError,
+    states::{Pool, Position},
+};
+
+#[derive(Accounts)]
+#[instruction(upper_tick: i32, lower_tick: i32, recipient: Pubkey, new_position_id: u64)]
+pub struct SplitPosition<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+    mut,
+    seeds = [
+        b"position",
+        pool.key().as_ref(),
+        signer.key().as_ref(),
+        lower_tick.to_le_bytes().as_ref(),
+        upper_tick.to_le_bytes().as_ref(),
+        position.id.to_le_bytes().as_ref()
+    ],
+    bump = position.bump
+)]
+    pub position: Box<Account<'info, Position>>,
+    // the recipient's position `new_position_id` on the same range, created if it does not exist
+    #[account(
+    init_if_needed,
+    payer = signer,
+    space = Position::SPACE,
+    seeds = [
+        b"position",
+        pool.key().as_ref(),
+        recipient.as_ref(),
+        lower_tick.to_le_bytes().as_ref(),
+        upper_tick.to_le_bytes().as_ref(),
+        new_position_id.to_le_bytes().as_ref()
+    ],
+    bump
+)]
+    pub new_position: Box<Account<'info, Position>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// moves `liquidity_amount` of the signer's position to `recipient`'s position `new_position_id`
+// on the same range, no tokens move and the tick arrays are untouched
+pub fn split_position(
+    ctx: Context<SplitPosition>,
+    upper_tick: i32,
+    lower_tick: i32,
+    recipient: Pubkey,
+    new_position_id: u64,
+    liquidity_amount: u128,
+) -> Result<()> {
+    // the same position twice would be written back twice, the last write winning
+    require_keys_neq!(
+        ctx.accounts.position.key(),
+        ctx.accounts.new_position.key(),
+        ClmmError::InvalidPositionRange
+    );
+
+    let new_position = &mut ctx.accounts.new_position;
+    if new_position.owner == Pubkey::default() {
+        new_position.set_inner(Position {
+            liquidity: 0,
+            lower_tick,
+            upper_tick,
+            owner: recipient,
+            pool: ctx.accounts.pool.key(),
+            id: new_position_id,
+            bump: ctx.bumps.new_position,
+        });
+    }
+    core::split_position(&mut ctx.accounts.position, new_position, liquidity_amount)?;
+
+    msg!("Split position: recipient={}, liquidity={}", recipient, liquidity_amount);
+
+    Ok(())
+}
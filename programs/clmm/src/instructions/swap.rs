@@ -2,13 +2,18 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
+    core::{apply_swap, compute_swap, settle_crossed_orders, SwapResult},
     errors::ClmmError,
-    instructions::{transfer_from_pda, transfer_tokens},
-    states::{Pool, TickArrayState},
-    utils::{compute_swap_step, sqrt_price_x96_to_tick, tick_to_sqrt_price_x96},
+    instructions::{
+        close_native_sol_account, get_transfer_fee, get_transfer_inverse_fee, native_mint_of,
+        transfer_from_pda, transfer_tokens, user_token_account, validate_native_sol,
+        wrap_native_sol,
+    },
+    states::{Pool, TickArrayState, POOL_STATUS_DISABLE_SWAP},
 };
 
 #[derive(Accounts)]
+#[instruction(amount_in: u64, a_to_b: bool)]
 pub struct Swap<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
@@ -20,32 +25,46 @@ pub struct Swap<'info> {
     )]
     pub pool: Box<Account<'info, Pool>>,
 
+    // array holding the tick the swap crosses, see core::compute_swap
+    /// CHECK: address checked here, may not exist yet, see TickArrayState::load_if_created
     #[account(
         mut,
-        constraint = tick_array.key() == Pubkey::find_program_address(
-            &[
-                b"tick_array",
-                pool.key().as_ref(),
-                &TickArrayState::get_start_tick_idx(pool.current_tick, pool.tick_spacing).to_le_bytes()
-            ],
-            &crate::ID
-        ).0 @ ClmmError::InvalidTickArrayAccount
+        address = TickArrayState::swap_tick_array(&pool, a_to_b) @ ClmmError::InvalidTickArrayAccount
     )]
-    pub tick_array: Box<Account<'info, TickArrayState>>,
+    pub tick_array: UncheckedAccount<'info>,
 
+    // the WSOL side may be left out in native SOL mode
     #[account(
         mut,
         token::mint = token_0,
         token::authority = signer
     )]
-    pub user_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_0: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
         mut,
         token::mint = token_1,
         token::authority = signer
     )]
-    pub user_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub user_1: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // the pool's WSOL side, passed in native SOL mode to open the temporary account with
+    #[account(
+        address = native_mint_of(&token_0, &token_1).key() @ ClmmError::NativeSolUnsupported
+    )]
+    pub native_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    // temporary WSOL account for native SOL mode, closed back to the signer before returning
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"native_sol", signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = signer,
+        token::token_program = token_program
+    )]
+    pub native_sol_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
         mut,
@@ -69,118 +88,185 @@ pub struct Swap<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn swap(
-    ctx: Context<Swap>,
+// compute_swap for mints that may charge a transfer fee
+//  the vault only receives the input minus the fee, so the math runs on the net input
+//  and the user pays the consumed amount grossed up by the fee
+//  the user receives the output minus the fee, which is what amount_out reports
+pub fn compute_swap_with_transfer_fees(
+    pool: &Pool,
+    tick_array: Option<&TickArrayState>,
+    mint_in: &InterfaceAccount<Mint>,
+    mint_out: &InterfaceAccount<Mint>,
+    amount_in: u64,
+    a_to_b: bool,
+) -> Result<SwapResult> {
+    let transfer_fee_in = get_transfer_fee(mint_in, amount_in)?;
+    let amount_in_net = amount_in
+        .checked_sub(transfer_fee_in)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    let mut result = compute_swap(pool, tick_array, amount_in_net, a_to_b)?;
+
+    // if the price stopped at the tick boundary only part of the input is used
+    result.transfer_fee_in = if result.amount_in < amount_in_net {
+        get_transfer_inverse_fee(mint_in, result.amount_in)?
+    } else {
+        transfer_fee_in
+    };
+    result.amount_in = result
+        .amount_in
+        .checked_add(result.transfer_fee_in)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    result.transfer_fee_out = get_transfer_fee(mint_out, result.amount_out)?;
+    result.amount_out = result
+        .amount_out
+        .checked_sub(result.transfer_fee_out)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+
+    Ok(result)
+}
+
+pub fn swap<'info>(
+    ctx: Context<'_, '_, '_, 'info, Swap<'info>>,
     amount_in: u64,
     a_to_b: bool,
     min_amount_out: u64,
+    native_sol: bool,
 ) -> Result<()> {
-
-    let (amount_in_consumed, amount_out, new_tick) = {
+    let result = {
         let pool = &mut ctx.accounts.pool;
+        pool.require_enabled(POOL_STATUS_DISABLE_SWAP)?;
+        validate_native_sol(pool, native_sol, ctx.accounts.native_sol_account.as_deref())?;
 
-        require!(amount_in > 0, ClmmError::ZeroAmount);
-        require!(pool.global_liquidity > 0, ClmmError::InsufficientLiquidity);
-
-        // when swapping a_to_b:
-        //  price goes DOWN, target is the next LOWER tick boundary
-        //  next_tick = current_tick rounded down to tick_spacing
-        //
-        // when swapping b_to_a:
-        //  price goes UP, target is the next UPPER tick boundary
-        //  next_tick = current_tick rounded up to tick_spacing
-        //
-        let target_tick = if a_to_b {
-            // example: current_tick = 57, spacing = 10 → target = 50
-            let tick = (pool.current_tick / pool.tick_spacing) * pool.tick_spacing;
-            // if we are exactly on a tick boundary, go one spacing lower
-            if tick == pool.current_tick {
-                tick - pool.tick_spacing
-            } else {
-                tick
-            }
+        let (mint_in, mint_out) = if a_to_b {
+            (&ctx.accounts.token_0, &ctx.accounts.token_1)
         } else {
-            // example: current_tick = 57, spacing = 10 → target = 60
-            ((pool.current_tick / pool.tick_spacing) + 1) * pool.tick_spacing
+            (&ctx.accounts.token_1, &ctx.accounts.token_0)
         };
-
-        let sqrt_price_target_x96 = tick_to_sqrt_price_x96(target_tick)?;
-
-        //  figures out if we can reach the target tick or stop partway
-        //  calculates exact input consumed and output produced
-        //
-        let (next_sqrt_price_x96, amount_consumed, amount_produced) = compute_swap_step(
-            pool.sqrt_price_x96,
-            sqrt_price_target_x96,
-            pool.global_liquidity,
-            amount_in as u128,
+        let tick_array = TickArrayState::load_if_created(&ctx.accounts.tick_array)?;
+        let result = compute_swap_with_transfer_fees(
+            pool,
+            tick_array.as_deref(),
+            mint_in,
+            mint_out,
+            amount_in,
             a_to_b,
         )?;
 
-        // update pool state
-        // after the swap, the price has moved, so we update both
-        pool.sqrt_price_x96 = next_sqrt_price_x96;
-        pool.current_tick = sqrt_price_x96_to_tick(next_sqrt_price_x96)?;
+        // check slippage on what the user actually receives
+        require!(
+            result.amount_out >= min_amount_out,
+            ClmmError::SlippageExceeded
+        );
+
+        apply_swap(pool, &result);
 
-        // check slippage
-        let amount_out: u64 = amount_produced
-            .try_into()
-            .map_err(|_| ClmmError::ArithmeticOverflow)?;
-        let amount_in_consumed: u64 = amount_consumed
-            .try_into()
-            .map_err(|_| ClmmError::ArithmeticOverflow)?;
+        // fill the limit orders waiting on the tick the swap crossed
+        drop(tick_array);
+        if let Some(tick) = result.crossed_tick {
+            let tick_array = TickArrayState::load_mut_if_created(&ctx.accounts.tick_array)?;
+            if let Some(mut tick_array) = tick_array {
+                settle_crossed_orders(&mut tick_array, tick, pool.tick_spacing, !a_to_b)?;
+            }
+        }
+
+        result
+    };
+    let amount_in_consumed = result.amount_in;
+    let amount_out = result.amount_out;
+    let new_tick = result.tick;
+    // the vault also sends the fee the mint withholds from the output
+    let vault_amount_out = amount_out
+        .checked_add(result.transfer_fee_out)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
 
-        require!(amount_out >= min_amount_out, ClmmError::SlippageExceeded);
+    let native_sol_account = ctx.accounts.native_sol_account.as_deref();
+    let user_0 = user_token_account(
+        ctx.accounts.user_0.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_0,
+    )?;
+    let user_1 = user_token_account(
+        ctx.accounts.user_1.as_deref(),
+        native_sol_account,
+        &ctx.accounts.token_1,
+    )?;
 
-        let tick = pool.current_tick;
-        (amount_in_consumed, amount_out, tick)
+    let (user_in, mint_in) = if a_to_b {
+        (user_0, &ctx.accounts.token_0)
+    } else {
+        (user_1, &ctx.accounts.token_1)
     };
+    if native_sol {
+        wrap_native_sol(
+            &ctx.accounts.signer,
+            user_in,
+            mint_in,
+            amount_in_consumed,
+            &ctx.accounts.system_program,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
     if a_to_b {
         if amount_in_consumed > 0 {
             transfer_tokens(
-                &ctx.accounts.user_0,
+                user_0,
                 &ctx.accounts.token_vault_0,
                 &amount_in_consumed,
                 &ctx.accounts.token_0,
                 &ctx.accounts.signer,
                 &ctx.accounts.token_program,
+                &ctx.accounts.pool,
+                ctx.remaining_accounts,
             )?;
         }
 
-        if amount_out > 0 {
+        if vault_amount_out > 0 {
             transfer_from_pda(
                 &ctx.accounts.token_vault_1,
-                &ctx.accounts.user_1,
-                &amount_out,
+                user_1,
+                &vault_amount_out,
                 &ctx.accounts.token_1,
                 &ctx.accounts.token_program,
                 &ctx.accounts.pool,
+                ctx.remaining_accounts,
             )?;
         }
     } else {
         if amount_in_consumed > 0 {
             transfer_tokens(
-                &ctx.accounts.user_1,
+                user_1,
                 &ctx.accounts.token_vault_1,
                 &amount_in_consumed,
                 &ctx.accounts.token_1,
                 &ctx.accounts.signer,
                 &ctx.accounts.token_program,
+                &ctx.accounts.pool,
+                ctx.remaining_accounts,
             )?;
         }
 
-        if amount_out > 0 {
+        if vault_amount_out > 0 {
             transfer_from_pda(
                 &ctx.accounts.token_vault_0,
-                &ctx.accounts.user_0,
-                &amount_out,
+                user_0,
+                &vault_amount_out,
                 &ctx.accounts.token_0,
                 &ctx.accounts.token_program,
                 &ctx.accounts.pool,
+                ctx.remaining_accounts,
             )?;
         }
     }
 
+    close_native_sol_account(
+        &ctx.accounts.signer,
+        native_sol_account,
+        &ctx.accounts.token_program,
+    )?;
+
     msg!(
         "Swap: a_to_b={}, in={}, out={}, new_tick={}",
         a_to_b,
@@ -190,4 +276,4 @@ pub fn swap(
     );
 
     Ok(())
-}
\ No newline at end of file
+}
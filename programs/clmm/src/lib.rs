@@ -1,7 +1,10 @@
 use anchor_lang::prelude::*;
+pub mod core;
 pub mod errors;
 pub mod instructions;
 pub mod states;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod utils;
 
 use instructions::*;
@@ -12,6 +15,61 @@ declare_id!("HvVeBmuPRReNPaMXXVWsz8UmtMSbUXnkGoDNN57brQcH");
 pub mod clmm {
     use super::*;
 
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        instructions::initialize_config::initialize_config(ctx)
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::propose_admin::propose_admin(ctx, new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin::accept_admin(ctx)
+    }
+
+    pub fn set_transfer_hook_program(
+        ctx: Context<SetTransferHookProgram>,
+        program_id: Pubkey,
+        allowed: bool,
+    ) -> Result<()> {
+        instructions::set_transfer_hook_program::set_transfer_hook_program(ctx, program_id, allowed)
+    }
+
+    pub fn set_mint_override(
+        ctx: Context<SetMintOverride>,
+        mint: Pubkey,
+        allowed: bool,
+    ) -> Result<()> {
+        instructions::set_mint_override::set_mint_override(ctx, mint, allowed)
+    }
+
+    pub fn set_pool_creation_config(
+        ctx: Context<SetPoolCreationConfig>,
+        pool_creation_mode: u8,
+        treasury: Pubkey,
+        creation_fee_mint: Pubkey,
+        creation_fee_amount: u64,
+    ) -> Result<()> {
+        instructions::set_pool_creation_config::set_pool_creation_config(
+            ctx,
+            pool_creation_mode,
+            treasury,
+            creation_fee_mint,
+            creation_fee_amount,
+        )
+    }
+
+    pub fn set_pool_creator(
+        ctx: Context<SetPoolCreator>,
+        creator: Pubkey,
+        allowed: bool,
+    ) -> Result<()> {
+        instructions::set_pool_creator::set_pool_creator(ctx, creator, allowed)
+    }
+
+    pub fn set_pool_status(ctx: Context<SetPoolStatus>, status: u8) -> Result<()> {
+        instructions::set_pool_status::set_pool_status(ctx, status)
+    }
 
     pub fn initialize_pool(
         ctx: Context<InitializePool>,
@@ -21,13 +79,18 @@ pub mod clmm {
         instructions::initialize_pool::init_pool(ctx, tick_spacing, initialize_sqrt_price)
     }
 
-    pub fn open_position(
-        ctx: Context<OpenPosition>,
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_position<'info>(
+        ctx: Context<'_, '_, '_, 'info, OpenPosition<'info>>,
         upper_tick: i32,
         lower_tick: i32,
         tick_array_lower_start_index: i32,
         tick_array_upper_start_index: i32,
+        position_id: u64,
         liquidity_amount: u128,
+        native_sol: bool,
+        sqrt_price_x96: u128,
+        max_slippage_bps: u16,
     ) -> Result<(u64, u64)> {
         instructions::open_position::open_position(
             ctx,
@@ -35,26 +98,40 @@ pub mod clmm {
             lower_tick,
             tick_array_lower_start_index,
             tick_array_upper_start_index,
+            position_id,
             liquidity_amount,
+            native_sol,
+            sqrt_price_x96,
+            max_slippage_bps,
         )
     }
 
-    pub fn swap(
-        ctx: Context<Swap>,
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, Swap<'info>>,
         amount_in: u64,
         a_to_b: bool,
         min_amount_out: u64,
+        native_sol: bool,
     ) -> Result<()> {
-        instructions::swap::swap(ctx, amount_in, a_to_b, min_amount_out)
+        instructions::swap::swap(ctx, amount_in, a_to_b, min_amount_out, native_sol)
+    }
+
+    pub fn quote_swap(
+        ctx: Context<QuoteSwap>,
+        amount_in: u64,
+        a_to_b: bool,
+    ) -> Result<(u64, u64, u128, i32)> {
+        instructions::quote_swap::quote_swap(ctx, amount_in, a_to_b)
     }
 
-    pub fn increase_liquidity(
-        ctx: Context<IncreaseLiquidity>,
+    pub fn increase_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, IncreaseLiquidity<'info>>,
         liquidity_amount: u128,
         upper_tick: i32,
         lower_tick: i32,
         tick_array_lower_start_index: i32,
         tick_array_upper_start_index: i32,
+        native_sol: bool,
     ) -> Result<()> {
         instructions::increase_liquidity::increase_liquidity(
             ctx,
@@ -63,17 +140,19 @@ pub mod clmm {
             lower_tick,
             tick_array_lower_start_index,
             tick_array_upper_start_index,
+            native_sol,
         )
     }
 
-    pub fn decrease_liquidity(
-        ctx: Context<DecreaseLiquidity>,
+    pub fn decrease_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, DecreaseLiquidity<'info>>,
         liquidity_amount: u128,
         upper_tick: i32,
         lower_tick: i32,
         tick_array_lower_start_index: i32,
         tick_array_upper_start_index: i32,
-    ) -> Result<()> {
+        native_sol: bool,
+    ) -> Result<(u64, u64)> {
         instructions::decrease_liquidity::decrease_liquidity(
             ctx,
             liquidity_amount,
@@ -81,16 +160,142 @@ pub mod clmm {
             lower_tick,
             tick_array_lower_start_index,
             tick_array_upper_start_index,
+            native_sol,
         )
     }
 
-    pub fn close_position(
-        ctx: Context<ClosePosition>,
+    pub fn close_position<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClosePosition<'info>>,
         upper_tick: i32,
         lower_tick: i32,
         _tick_array_lower_start_index: i32,
         _tick_array_upper_start_index: i32,
+        native_sol: bool,
     ) -> Result<(u64, u64)> {
-        instructions::close_position::close_position(ctx, upper_tick, lower_tick)
+        instructions::close_position::close_position(ctx, upper_tick, lower_tick, native_sol)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn reposition<'info>(
+        ctx: Context<'_, '_, '_, 'info, Reposition<'info>>,
+        _upper_tick: i32,
+        _lower_tick: i32,
+        new_upper_tick: i32,
+        new_lower_tick: i32,
+        _tick_array_lower_start_index: i32,
+        _tick_array_upper_start_index: i32,
+        new_tick_array_lower_start_index: i32,
+        new_tick_array_upper_start_index: i32,
+        swap_amount_in: u64,
+        a_to_b: bool,
+        min_amount_out: u64,
+        liquidity_amount: u128,
+        sqrt_price_x96: u128,
+        max_slippage_bps: u16,
+        native_sol: bool,
+    ) -> Result<(i64, i64)> {
+        instructions::reposition::reposition(
+            ctx,
+            new_upper_tick,
+            new_lower_tick,
+            new_tick_array_lower_start_index,
+            new_tick_array_upper_start_index,
+            swap_amount_in,
+            a_to_b,
+            min_amount_out,
+            liquidity_amount,
+            sqrt_price_x96,
+            max_slippage_bps,
+            native_sol,
+        )
+    }
+
+    pub fn split_position(
+        ctx: Context<SplitPosition>,
+        upper_tick: i32,
+        lower_tick: i32,
+        recipient: Pubkey,
+        new_position_id: u64,
+        liquidity_amount: u128,
+    ) -> Result<()> {
+        instructions::split_position::split_position(
+            ctx,
+            upper_tick,
+            lower_tick,
+            recipient,
+            new_position_id,
+            liquidity_amount,
+        )
+    }
+
+    pub fn merge_positions(
+        ctx: Context<MergePositions>,
+        _upper_tick: i32,
+        _lower_tick: i32,
+    ) -> Result<()> {
+        instructions::merge_positions::merge_positions(ctx)
+    }
+
+    pub fn approve_position_delegate(
+        ctx: Context<ApprovePositionDelegate>,
+        operator: Pubkey,
+        permissions: u8,
+    ) -> Result<()> {
+        instructions::approve_position_delegate::approve_position_delegate(
+            ctx,
+            operator,
+            permissions,
+        )
+    }
+
+    pub fn revoke_position_delegate(
+        ctx: Context<RevokePositionDelegate>,
+        operator: Pubkey,
+    ) -> Result<()> {
+        instructions::revoke_position_delegate::revoke_position_delegate(ctx, operator)
+    }
+
+    pub fn open_limit_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, OpenLimitOrder<'info>>,
+        tick: i32,
+        zero_for_one: bool,
+        tick_array_start_index: i32,
+        liquidity_amount: u128,
+    ) -> Result<u64> {
+        instructions::open_limit_order::open_limit_order(
+            ctx,
+            tick,
+            zero_for_one,
+            tick_array_start_index,
+            liquidity_amount,
+        )
+    }
+
+    pub fn cancel_limit_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelLimitOrder<'info>>,
+        tick: i32,
+        zero_for_one: bool,
+        tick_array_start_index: i32,
+    ) -> Result<(u64, u64)> {
+        instructions::cancel_limit_order::cancel_limit_order(
+            ctx,
+            tick,
+            zero_for_one,
+            tick_array_start_index,
+        )
+    }
+
+    pub fn settle_limit_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleLimitOrder<'info>>,
+        tick_array_start_index: i32,
+    ) -> Result<u64> {
+        instructions::settle_limit_order::settle_limit_order(ctx, tick_array_start_index)
+    }
+
+    pub fn close_tick_array(
+        ctx: Context<CloseTickArray>,
+        tick_array_start_index: i32,
+    ) -> Result<()> {
+        instructions::close_tick_array::close_tick_array(ctx, tick_array_start_index)
     }
 }
@@ -0,0 +1,184 @@
+use crate::{errors::ClmmError, utils::ANCHOR_DISCRIMINATOR};
+use anchor_lang::prelude::*;
+
+pub const MAX_TRANSFER_HOOK_PROGRAMS: usize = 8;
+pub const MAX_MINT_OVERRIDES: usize = 16;
+pub const MAX_POOL_CREATORS: usize = 16;
+
+// who may call initialize_pool
+pub const POOL_CREATION_PERMISSIONLESS: u8 = 0;
+pub const POOL_CREATION_ADMIN_ONLY: u8 = 1;
+pub const POOL_CREATION_ALLOWLISTED: u8 = 2;
+
+// program wide settings, a single PDA at ["global_config"]
+#[account]
+pub struct GlobalConfig {
+    pub admin: Pubkey,
+    // proposed by the admin, becomes admin once it signs accept_admin,
+    // Pubkey::default() when no transfer is pending
+    pub pending_admin: Pubkey,
+    // hook programs a Token-2022 mint may point at to be listed in a pool,
+    // unused slots are Pubkey::default()
+    pub transfer_hook_programs: [Pubkey; MAX_TRANSFER_HOOK_PROGRAMS],
+    // vetted mints that skip the mint extension checks at pool creation
+    pub mint_overrides: [Pubkey; MAX_MINT_OVERRIDES],
+    // one of POOL_CREATION_*
+    pub pool_creation_mode: u8,
+    // creators allowed besides the admin in POOL_CREATION_ALLOWLISTED mode
+    pub pool_creators: [Pubkey; MAX_POOL_CREATORS],
+    // receives the pool creation fee
+    pub treasury: Pubkey,
+    // Pubkey::default() charges the fee in lamports, otherwise in this mint
+    pub creation_fee_mint: Pubkey,
+    // 0 disables the fee
+    pub creation_fee_amount: u64,
+    pub bump: u8,
+}
+
+impl GlobalConfig {
+    pub const SPACE: usize = ANCHOR_DISCRIMINATOR +
+    32 + // admin
+    32 + // pending_admin
+    32 * MAX_TRANSFER_HOOK_PROGRAMS + // transfer_hook_programs
+    32 * MAX_MINT_OVERRIDES + // mint_overrides
+    1 + // pool_creation_mode
+    32 * MAX_POOL_CREATORS + // pool_creators
+    32 + // treasury
+    32 + // creation_fee_mint
+    8 + // creation_fee_amount
+    1; // bump
+
+    pub fn is_transfer_hook_allowed(&self, program_id: &Pubkey) -> bool {
+        contains_key(&self.transfer_hook_programs, program_id)
+    }
+
+    pub fn set_transfer_hook_program(&mut self, program_id: Pubkey, allowed: bool) -> Result<()> {
+        require_keys_neq!(program_id, Pubkey::default(), ClmmError::TransferHookNotAllowed);
+        set_key(&mut self.transfer_hook_programs, program_id, allowed)
+            .ok_or(error!(ClmmError::TransferHookAllowlistFull))
+    }
+
+    pub fn is_mint_overridden(&self, mint: &Pubkey) -> bool {
+        contains_key(&self.mint_overrides, mint)
+    }
+
+    pub fn set_mint_override(&mut self, mint: Pubkey, allowed: bool) -> Result<()> {
+        require_keys_neq!(mint, Pubkey::default(), ClmmError::InvalidTokenPair);
+        set_key(&mut self.mint_overrides, mint, allowed)
+            .ok_or(error!(ClmmError::MintOverrideListFull))
+    }
+
+    pub fn can_create_pool(&self, creator: &Pubkey) -> bool {
+        match self.pool_creation_mode {
+            POOL_CREATION_PERMISSIONLESS => true,
+            POOL_CREATION_ADMIN_ONLY => *creator == self.admin,
+            POOL_CREATION_ALLOWLISTED => {
+                *creator == self.admin || contains_key(&self.pool_creators, creator)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn set_pool_creator(&mut self, creator: Pubkey, allowed: bool) -> Result<()> {
+        require_keys_neq!(creator, Pubkey::default(), ClmmError::Unauthorized);
+        set_key(&mut self.pool_creators, creator, allowed)
+            .ok_or(error!(ClmmError::PoolCreatorListFull))
+    }
+}
+
+fn contains_key(list: &[Pubkey], key: &Pubkey) -> bool {
+    *key != Pubkey::default() && list.contains(key)
+}
+
+// adds `key` to the first free slot or clears it, None if there is no free slot left
+fn set_key(list: &mut [Pubkey], key: Pubkey, allowed: bool) -> Option<()> {
+    if !allowed {
+        for slot in list.iter_mut().filter(|slot| **slot == key) {
+            *slot = Pubkey::default();
+        }
+        return Some(());
+    }
+    if !list.contains(&key) {
+        *list.iter_mut().find(|slot| **slot == Pubkey::default())? = key;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_config() -> GlobalConfig {
+        GlobalConfig {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            transfer_hook_programs: [Pubkey::default(); MAX_TRANSFER_HOOK_PROGRAMS],
+            mint_overrides: [Pubkey::default(); MAX_MINT_OVERRIDES],
+            pool_creation_mode: POOL_CREATION_PERMISSIONLESS,
+            pool_creators: [Pubkey::default(); MAX_POOL_CREATORS],
+            treasury: Pubkey::default(),
+            creation_fee_mint: Pubkey::default(),
+            creation_fee_amount: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn test_set_transfer_hook_program() {
+        let mut config = empty_config();
+        let hook = Pubkey::new_unique();
+        assert!(!config.is_transfer_hook_allowed(&hook));
+        assert!(!config.is_transfer_hook_allowed(&Pubkey::default()));
+
+        config.set_transfer_hook_program(hook, true).unwrap();
+        config.set_transfer_hook_program(hook, true).unwrap();
+        assert!(config.is_transfer_hook_allowed(&hook));
+        assert_eq!(config.transfer_hook_programs.iter().filter(|p| **p == hook).count(), 1);
+
+        config.set_transfer_hook_program(hook, false).unwrap();
+        assert!(!config.is_transfer_hook_allowed(&hook));
+    }
+
+    #[test]
+    fn test_transfer_hook_allowlist_full() {
+        let mut config = empty_config();
+        for _ in 0..MAX_TRANSFER_HOOK_PROGRAMS {
+            config.set_transfer_hook_program(Pubkey::new_unique(), true).unwrap();
+        }
+        assert!(config.set_transfer_hook_program(Pubkey::new_unique(), true).is_err());
+    }
+
+    #[test]
+    fn test_set_mint_override() {
+        let mut config = empty_config();
+        let mint = Pubkey::new_unique();
+        config.set_mint_override(mint, true).unwrap();
+        assert!(config.is_mint_overridden(&mint));
+        assert!(!config.is_transfer_hook_allowed(&mint));
+
+        config.set_mint_override(mint, false).unwrap();
+        assert!(!config.is_mint_overridden(&mint));
+        assert!(config.set_mint_override(Pubkey::default(), true).is_err());
+    }
+
+    #[test]
+    fn test_can_create_pool() {
+        let mut config = empty_config();
+        let admin = config.admin;
+        let creator = Pubkey::new_unique();
+        assert!(config.can_create_pool(&creator));
+
+        config.pool_creation_mode = POOL_CREATION_ADMIN_ONLY;
+        config.set_pool_creator(creator, true).unwrap();
+        assert!(config.can_create_pool(&admin));
+        assert!(!config.can_create_pool(&creator));
+
+        config.pool_creation_mode = POOL_CREATION_ALLOWLISTED;
+        assert!(config.can_create_pool(&admin));
+        assert!(config.can_create_pool(&creator));
+        assert!(!config.can_create_pool(&Pubkey::new_unique()));
+
+        config.pool_creation_mode = 3;
+        assert!(!config.can_create_pool(&admin));
+    }
+}
@@ -0,0 +1,42 @@
+use crate::utils::ANCHOR_DISCRIMINATOR;
+use anchor_lang::prelude::*;
+
+// a one-spacing range [tick, tick + tick_spacing] placed entirely on one side of the price,
+// which the price turns from the token it sells into the other one as it moves through
+#[account]
+pub struct LimitOrder {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub tick: i32,
+    // sells token_0 for token_1 above the price, or token_1 for token_0 below it
+    pub zero_for_one: bool,
+    pub liquidity: u128,
+    // batch joined at the settlement tick, see TickState::pending_orders
+    pub batch: u64,
+    pub bump: u8,
+}
+
+impl LimitOrder {
+    pub const SPACE: usize = ANCHOR_DISCRIMINATOR +
+32 + // pool
+32 + // owner
+4 + // tick
+1 + // zero_for_one
+16 + // liquidity
+8 + // batch
+1; // bump
+
+    pub fn upper_tick(&self, tick_spacing: i32) -> i32 {
+        self.tick.saturating_add(tick_spacing)
+    }
+
+    // the tick whose crossing fills the order: its upper tick for a rising price, its lower
+    // tick for a falling one
+    pub fn settlement_tick(&self, tick_spacing: i32) -> i32 {
+        if self.zero_for_one {
+            self.upper_tick(tick_spacing)
+        } else {
+            self.tick
+        }
+    }
+}
@@ -1,7 +1,15 @@
+pub mod global_config;
+pub mod limit_order;
 pub mod pool;
 pub mod position;
+pub mod position_delegate;
+pub mod tick_array_bitmap;
 pub mod ticks;
 
+pub use global_config::*;
+pub use limit_order::*;
 pub use pool::*;
 pub use position::*;
+pub use position_delegate::*;
+pub use tick_array_bitmap::*;
 pub use ticks::*;
@@ -1,6 +1,24 @@
-use crate::utils::ANCHOR_DISCRIMINATOR;
+use crate::{
+    errors::ClmmError,
+    utils::{sqrt_price_x96_to_tick, validate_sqrt_price, ANCHOR_DISCRIMINATOR, BPS_DENOMINATOR},
+};
 use anchor_lang::prelude::*;
 
+// pool status bits, a set bit disables the operation
+pub const POOL_STATUS_DISABLE_SWAP: u8 = 1 << 0;
+pub const POOL_STATUS_DISABLE_OPEN_POSITION: u8 = 1 << 1;
+pub const POOL_STATUS_DISABLE_INCREASE_LIQUIDITY: u8 = 1 << 2;
+// covers decrease_liquidity and close_position
+pub const POOL_STATUS_DISABLE_DECREASE_LIQUIDITY: u8 = 1 << 3;
+pub const POOL_STATUS_ALL: u8 = POOL_STATUS_DISABLE_SWAP
+    | POOL_STATUS_DISABLE_OPEN_POSITION
+    | POOL_STATUS_DISABLE_INCREASE_LIQUIDITY
+    | POOL_STATUS_DISABLE_DECREASE_LIQUIDITY;
+// emergency mode: trading and new deposits stop, LPs can still withdraw
+pub const POOL_STATUS_EMERGENCY: u8 = POOL_STATUS_DISABLE_SWAP
+    | POOL_STATUS_DISABLE_OPEN_POSITION
+    | POOL_STATUS_DISABLE_INCREASE_LIQUIDITY;
+
 #[account]
 #[derive(InitSpace)]
 pub struct Pool {
@@ -17,6 +35,13 @@ pub struct Pool {
     //the interval between ticks which will allow lps to choose their lower and upper positions
     pub tick_spacing: i32,
     pub bump: u8,
+    //Token-2022 transfer hook programs of each mint at creation, Pubkey::default() if none
+    pub transfer_hook_program_0: Pubkey,
+    pub transfer_hook_program_1: Pubkey,
+    //POOL_STATUS_* bits set by the admin
+    pub status: u8,
+    //last limit order batch handed out, batches are never reused within a pool
+    pub limit_order_batch: u64,
 }
 
 impl Pool {
@@ -29,5 +54,146 @@ impl Pool {
     16 + // sqrt_price_x64
     4 +  // current_tick
     4 +  // tick_spacing
-    1; // bump
+    1 + // bump
+    32 + // transfer_hook_program_0
+    32 + // transfer_hook_program_1
+    1 + // status
+    8; // limit_order_batch
+
+    // pools created with a zero price wait for the first open_position to set it,
+    // so a front-run initialize_pool cannot pin a bad price on the first LP
+    pub fn is_price_initialized(&self) -> bool {
+        self.sqrt_price_x96 != 0
+    }
+
+    // sets the price of an uninitialized pool to `sqrt_price_x96`, otherwise requires the
+    // pool price to be within `max_slippage_bps` of it, a zero expected price skips the check
+    pub fn initialize_or_check_price(
+        &mut self,
+        sqrt_price_x96: u128,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        if !self.is_price_initialized() {
+            require!(sqrt_price_x96 > 0, ClmmError::PoolPriceUninitialized);
+            validate_sqrt_price(sqrt_price_x96, self.tick_spacing)?;
+            self.current_tick = sqrt_price_x96_to_tick(sqrt_price_x96)?;
+            self.sqrt_price_x96 = sqrt_price_x96;
+            return Ok(());
+        }
+        if sqrt_price_x96 == 0 {
+            return Ok(());
+        }
+        self.check_price(sqrt_price_x96, max_slippage_bps)
+    }
+
+    // requires the initialized pool price to be within `max_slippage_bps` of `sqrt_price_x96`,
+    // for callers whose only slippage bound is this price a zero expected price is rejected
+    pub fn check_price(&self, sqrt_price_x96: u128, max_slippage_bps: u16) -> Result<()> {
+        require!(self.is_price_initialized(), ClmmError::PoolPriceUninitialized);
+        require!(sqrt_price_x96 > 0, ClmmError::MissingExpectedPrice);
+        require!(max_slippage_bps <= BPS_DENOMINATOR, ClmmError::InvalidSlippage);
+        // divide first, sqrt prices can use the full u128 range
+        let tolerance = (sqrt_price_x96 / BPS_DENOMINATOR as u128)
+            .checked_mul(max_slippage_bps as u128)
+            .ok_or(ClmmError::ArithmeticOverflow)?;
+        require!(
+            self.sqrt_price_x96.abs_diff(sqrt_price_x96) <= tolerance,
+            ClmmError::PriceSlippageExceeded
+        );
+        Ok(())
+    }
+
+    // the spacing-aligned tick a swap in this direction leaves from or moves toward: the one at
+    // or below the current tick when the price falls, the next one above when it rises
+    pub fn swap_boundary_tick(&self, a_to_b: bool) -> i32 {
+        let boundary = self.current_tick.div_euclid(self.tick_spacing) * self.tick_spacing;
+        if a_to_b {
+            boundary
+        } else {
+            boundary + self.tick_spacing
+        }
+    }
+
+    pub fn require_enabled(&self, flag: u8) -> Result<()> {
+        require!(self.status & flag == 0, ClmmError::PoolOperationDisabled);
+        Ok(())
+    }
+
+    // hook program recorded for `mint` when the pool was created
+    pub fn transfer_hook_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        if *mint == self.token_0 {
+            Ok(self.transfer_hook_program_0)
+        } else if *mint == self.token_1 {
+            Ok(self.transfer_hook_program_1)
+        } else {
+            err!(ClmmError::InvalidTokenPair)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::pool_at_tick;
+
+    fn pool_with_price(sqrt_price_x96: u128) -> Pool {
+        Pool {
+            sqrt_price_x96,
+            ..pool_at_tick(0, 60)
+        }
+    }
+
+    #[test]
+    fn test_emergency_mode_allows_withdrawals() {
+        let mut pool = pool_with_price(1 << 96);
+        pool.status = POOL_STATUS_EMERGENCY;
+        assert!(pool.require_enabled(POOL_STATUS_DISABLE_SWAP).is_err());
+        assert!(pool.require_enabled(POOL_STATUS_DISABLE_OPEN_POSITION).is_err());
+        assert!(pool.require_enabled(POOL_STATUS_DISABLE_INCREASE_LIQUIDITY).is_err());
+        assert!(pool.require_enabled(POOL_STATUS_DISABLE_DECREASE_LIQUIDITY).is_ok());
+    }
+
+    #[test]
+    fn test_first_deposit_sets_uninitialized_price() {
+        let mut pool = pool_with_price(0);
+        assert!(!pool.is_price_initialized());
+        assert!(pool.initialize_or_check_price(0, 100).is_err());
+
+        pool.initialize_or_check_price(1 << 96, 0).unwrap();
+        assert_eq!(pool.sqrt_price_x96, 1 << 96);
+        assert_eq!(pool.current_tick, 0);
+    }
+
+    #[test]
+    fn test_price_slippage_bounds() {
+        let mut pool = pool_with_price(1 << 96);
+        let one_percent_higher = (1u128 << 96) / 100 * 101;
+        assert!(pool.initialize_or_check_price(one_percent_higher, 100).is_ok());
+        assert!(pool.initialize_or_check_price(one_percent_higher, 99).is_err());
+        assert!(pool.initialize_or_check_price(0, 0).is_ok());
+        assert!(pool.initialize_or_check_price(1 << 96, 10_001).is_err());
+        assert_eq!(pool.sqrt_price_x96, 1 << 96);
+    }
+
+    #[test]
+    fn test_swap_boundary_tick_is_spacing_aligned() {
+        let mut pool = pool_with_price(1 << 96);
+        // a falling price leaves from the tick at or below, a rising one moves to the next above
+        for (current_tick, below, above) in [(0, 0, 60), (59, 0, 60), (-1, -60, 0), (-60, -60, 0)] {
+            pool.current_tick = current_tick;
+            assert_eq!(pool.swap_boundary_tick(true), below);
+            assert_eq!(pool.swap_boundary_tick(false), above);
+        }
+    }
+
+    #[test]
+    fn test_strict_price_check_needs_an_expected_price() {
+        let pool = pool_with_price(1 << 96);
+        assert!(pool.check_price(1 << 96, 0).is_ok());
+        let result = pool.check_price(0, 100);
+        assert_eq!(result.unwrap_err(), ClmmError::MissingExpectedPrice.into());
+
+        let result = pool_with_price(0).check_price(1 << 96, 100);
+        assert_eq!(result.unwrap_err(), ClmmError::PoolPriceUninitialized.into());
+    }
 }
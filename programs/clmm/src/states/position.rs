@@ -1,7 +1,8 @@
 use crate::utils::ANCHOR_DISCRIMINATOR;
 use anchor_lang::prelude::*;
 
-// TODO: Add fees flow in the V2
+// TODO: Add fees flow in the V2, then a compound_position turning tokens_owed_0/1 back into
+// liquidity through utils::get_liquidity_for_amounts, the dust staying owed
 #[account]
 pub struct Position {
     pub liquidity: u128,
@@ -9,6 +10,8 @@ pub struct Position {
     pub upper_tick: i32,
     pub owner: Pubkey,
     pub pool: Pubkey,
+    // chosen by the owner, part of the address so one owner can hold several positions on a range
+    pub id: u64,
     pub bump: u8,
 }
 
@@ -19,5 +22,6 @@ impl Position {
 4 + // upper_tick
 32 + // owner
 32 + // pool
+8 + // id
 1; // bump
 }
@@ -0,0 +1,94 @@
+use crate::{errors::ClmmError, states::Position, utils::ANCHOR_DISCRIMINATOR};
+use anchor_lang::prelude::*;
+
+// position delegate permission bits, a set bit lets the operator do the operation
+pub const POSITION_DELEGATE_INCREASE_LIQUIDITY: u8 = 1 << 0;
+pub const POSITION_DELEGATE_DECREASE_LIQUIDITY: u8 = 1 << 1;
+// 1 << 2 is unassigned, approvals granting it are rejected
+pub const POSITION_DELEGATE_REPOSITION: u8 = 1 << 3;
+pub const POSITION_DELEGATE_ALL: u8 = POSITION_DELEGATE_INCREASE_LIQUIDITY
+    | POSITION_DELEGATE_DECREASE_LIQUIDITY
+    | POSITION_DELEGATE_REPOSITION;
+
+// an owner's approval of an operator, such as a keeper or a strategy program PDA, over all of
+// the owner's positions in one pool. tokens an operator withdraws still only go to the owner
+#[account]
+pub struct PositionDelegate {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub operator: Pubkey,
+    //POSITION_DELEGATE_* bits granted by the owner
+    pub permissions: u8,
+    pub bump: u8,
+}
+
+impl PositionDelegate {
+    pub const SPACE: usize = ANCHOR_DISCRIMINATOR +
+32 + // pool
+32 + // owner
+32 + // operator
+1 + // permissions
+1; // bump
+}
+
+impl Position {
+    // the owner may always act on its position, another signer needs the owner's delegate
+    // account for the pool granting `permission`
+    pub fn require_authority(
+        &self,
+        signer: &Pubkey,
+        delegate: Option<&PositionDelegate>,
+        permission: u8,
+    ) -> Result<()> {
+        if *signer == self.owner {
+            return Ok(());
+        }
+        let approved = delegate.is_some_and(|delegate| {
+            delegate.pool == self.pool
+                && delegate.owner == self.owner
+                && delegate.operator == *signer
+                && delegate.permissions & permission == permission
+        });
+        require!(approved, ClmmError::InvalidPositionOwner);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::position;
+
+    #[test]
+    fn test_operator_needs_the_permission() {
+        let (owner, operator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let position = Position {
+            owner,
+            pool: Pubkey::new_unique(),
+            ..position(-60, 60)
+        };
+        let delegate = PositionDelegate {
+            pool: position.pool,
+            owner,
+            operator,
+            permissions: POSITION_DELEGATE_DECREASE_LIQUIDITY,
+            bump: 0,
+        };
+        let decrease = POSITION_DELEGATE_DECREASE_LIQUIDITY;
+
+        assert!(position.require_authority(&owner, None, decrease).is_ok());
+        assert!(position.require_authority(&operator, Some(&delegate), decrease).is_ok());
+        assert!(position.require_authority(&operator, None, decrease).is_err());
+        let increase = POSITION_DELEGATE_INCREASE_LIQUIDITY;
+        assert!(position.require_authority(&operator, Some(&delegate), increase).is_err());
+
+        // an approval only covers its own pool, owner and operator
+        let stranger = Pubkey::new_unique();
+        assert!(position.require_authority(&stranger, Some(&delegate), decrease).is_err());
+        let other_pool = PositionDelegate {
+            pool: Pubkey::new_unique(),
+            ..delegate
+        };
+        assert!(position.require_authority(&operator, Some(&other_pool), decrease).is_err());
+    }
+}
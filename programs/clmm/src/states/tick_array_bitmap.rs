@@ -0,0 +1,200 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ClmmError,
+    utils::{ANCHOR_DISCRIMINATOR, MAX_TICK, TICKS_PER_ARRAY, TICK_ARRAY_BITMAP_WORDS},
+};
+
+pub const TICK_ARRAY_BITMAP_BITS: usize = TICK_ARRAY_BITMAP_WORDS * 64;
+
+// bit i tracks the tick array whose index (start_tick / array width) is i - TICK_ARRAY_BITMAP_BITS / 2,
+// so the bitmap covers arrays on both sides of tick 0
+#[account(zero_copy)]
+pub struct TickArrayBitmap {
+    pub pool: Pubkey,
+    pub bitmap: [u64; TICK_ARRAY_BITMAP_WORDS],
+}
+
+impl TickArrayBitmap {
+    pub const SPACE: usize = ANCHOR_DISCRIMINATOR +
+32 + // pool
+TICK_ARRAY_BITMAP_WORDS * 8; // bitmap words
+
+    // checks that every tick array of a pool with this spacing has a bit in the bitmap
+    pub fn supports_tick_spacing(tick_spacing: i32) -> bool {
+        let array_width = tick_spacing as i64 * TICKS_PER_ARRAY as i64;
+        (MAX_TICK as i64 / array_width) < (TICK_ARRAY_BITMAP_BITS / 2) as i64
+    }
+
+    // this function maps the start index of a tick array to its bit in the bitmap
+    fn bit_index(start_tick: i32, tick_spacing: i32) -> Result<usize> {
+        let array_width = tick_spacing
+            .checked_mul(TICKS_PER_ARRAY as i32)
+            .ok_or(ClmmError::ArithmeticOverflow)?;
+        require!(
+            start_tick % array_width == 0,
+            ClmmError::InvalidTickArrayAccount
+        );
+
+        let bit = (start_tick / array_width) as i64 + (TICK_ARRAY_BITMAP_BITS / 2) as i64;
+        require!(
+            (0..TICK_ARRAY_BITMAP_BITS as i64).contains(&bit),
+            ClmmError::InvalidTickArrayAccount
+        );
+
+        Ok(bit as usize)
+    }
+
+    fn start_tick_from_bit(bit: usize, tick_spacing: i32) -> i32 {
+        (bit as i32 - (TICK_ARRAY_BITMAP_BITS / 2) as i32) * tick_spacing * TICKS_PER_ARRAY as i32
+    }
+
+    pub fn is_initialized(&self, start_tick: i32, tick_spacing: i32) -> Result<bool> {
+        let bit = Self::bit_index(start_tick, tick_spacing)?;
+        Ok(self.bitmap[bit / 64] & (1u64 << (bit % 64)) != 0)
+    }
+
+    // marks the tick array as holding (or no longer holding) initialized ticks
+    // returns true if the bit changed
+    pub fn set_initialized(
+        &mut self,
+        start_tick: i32,
+        tick_spacing: i32,
+        initialized: bool,
+    ) -> Result<bool> {
+        let bit = Self::bit_index(start_tick, tick_spacing)?;
+        let word = &mut self.bitmap[bit / 64];
+        let mask = 1u64 << (bit % 64);
+        let was_initialized = *word & mask != 0;
+
+        if initialized {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+
+        Ok(was_initialized != initialized)
+    }
+
+    // finds the start index of the closest initialized tick array after `start_tick`
+    // (exclusive) in the direction of the swap:
+    //  a_to_b → price goes down, search towards lower ticks
+    //  b_to_a → price goes up, search towards higher ticks
+    pub fn next_initialized_tick_array(
+        &self,
+        start_tick: i32,
+        tick_spacing: i32,
+        a_to_b: bool,
+    ) -> Result<Option<i32>> {
+        let bit = Self::bit_index(start_tick, tick_spacing)?;
+
+        let found = if a_to_b {
+            next_set_bit_below(&self.bitmap, bit)
+        } else {
+            next_set_bit_above(&self.bitmap, bit)
+        };
+
+        Ok(found.map(|bit| Self::start_tick_from_bit(bit, tick_spacing)))
+    }
+}
+
+fn next_set_bit_above(bitmap: &[u64], bit: usize) -> Option<usize> {
+    let mut current = bit + 1;
+
+    while current < bitmap.len() * 64 {
+        let word = current / 64;
+        let masked = bitmap[word] >> (current % 64);
+        if masked != 0 {
+            return Some(current + masked.trailing_zeros() as usize);
+        }
+        current = (word + 1) * 64;
+    }
+
+    None
+}
+
+fn next_set_bit_below(bitmap: &[u64], bit: usize) -> Option<usize> {
+    let mut current = bit.checked_sub(1)?;
+
+    loop {
+        let word = current / 64;
+        let masked = bitmap[word] << (63 - current % 64);
+        if masked != 0 {
+            return Some(current - masked.leading_zeros() as usize);
+        }
+        current = (word * 64).checked_sub(1)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACING: i32 = 60;
+    const WIDTH: i32 = SPACING * TICKS_PER_ARRAY as i32;
+
+    fn empty_bitmap() -> TickArrayBitmap {
+        TickArrayBitmap {
+            pool: Pubkey::default(),
+            bitmap: [0; TICK_ARRAY_BITMAP_WORDS],
+        }
+    }
+
+    #[test]
+    fn test_set_and_clear_bit() {
+        let mut bitmap = empty_bitmap();
+
+        assert!(bitmap.set_initialized(-WIDTH, SPACING, true).unwrap());
+        assert!(bitmap.is_initialized(-WIDTH, SPACING).unwrap());
+        // setting again does not flip
+        assert!(!bitmap.set_initialized(-WIDTH, SPACING, true).unwrap());
+
+        assert!(bitmap.set_initialized(-WIDTH, SPACING, false).unwrap());
+        assert!(!bitmap.is_initialized(-WIDTH, SPACING).unwrap());
+    }
+
+    #[test]
+    fn test_misaligned_start_tick_fails() {
+        let bitmap = empty_bitmap();
+        assert!(bitmap.is_initialized(WIDTH + SPACING, SPACING).is_err());
+    }
+
+    #[test]
+    fn test_next_initialized_in_both_directions() {
+        let mut bitmap = empty_bitmap();
+        bitmap.set_initialized(-100 * WIDTH, SPACING, true).unwrap();
+        bitmap.set_initialized(0, SPACING, true).unwrap();
+        bitmap.set_initialized(70 * WIDTH, SPACING, true).unwrap();
+
+        // searching excludes the starting array itself
+        assert_eq!(
+            bitmap.next_initialized_tick_array(0, SPACING, false).unwrap(),
+            Some(70 * WIDTH)
+        );
+        assert_eq!(
+            bitmap.next_initialized_tick_array(0, SPACING, true).unwrap(),
+            Some(-100 * WIDTH)
+        );
+        assert_eq!(
+            bitmap.next_initialized_tick_array(5 * WIDTH, SPACING, true).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            bitmap.next_initialized_tick_array(70 * WIDTH, SPACING, false).unwrap(),
+            None
+        );
+        assert_eq!(
+            bitmap.next_initialized_tick_array(-100 * WIDTH, SPACING, true).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tick_spacing_support() {
+        assert!(TickArrayBitmap::supports_tick_spacing(SPACING));
+        let max_index = MAX_TICK / WIDTH;
+        let bitmap = empty_bitmap();
+        assert!(bitmap.is_initialized(max_index * WIDTH, SPACING).is_ok());
+        assert!(bitmap.is_initialized(-max_index * WIDTH, SPACING).is_ok());
+    }
+}
@@ -1,31 +1,75 @@
-use anchor_lang::prelude::*;
+use std::cell::{Ref, RefMut};
+
+use anchor_lang::{prelude::*, system_program, Discriminator};
 
 use crate::{
     errors::ClmmError,
+    states::Pool,
     utils::{ANCHOR_DISCRIMINATOR, TICKS_PER_ARRAY},
 };
 
-#[account]
+// tick arrays are zero-copy and packed: there is no padding to keep in sync between
+// the host and SBF layouts, and the 128-bit fields can sit at any offset
+#[zero_copy(unsafe)]
+#[repr(C, packed)]
 pub struct TickState {
-    pub initialized: bool,
     pub gross_liquidity: u128,
     pub net_liquidity: i128,
+    pub initialized: u8,
+    // pending limit orders this tick fills, by the token they sell: token_0 orders end at this
+    // tick and fill when the price rises through it, token_1 orders start here and fill when it
+    // falls through it. the liquidity is also counted in gross / net above
+    pub order_liquidity_0: u128,
+    pub order_liquidity_1: u128,
+    // batch the pending orders joined, 0 while no batch is open, see core::limit_order
+    pub order_batch_0: u64,
+    pub order_batch_1: u64,
 }
 impl TickState {
-    pub const SPACE: usize = ANCHOR_DISCRIMINATOR +
+    pub const SPACE: usize = 16 + // gross liquidity
+16 + // net liquidity
 1 + // initialized
-16 + // gross liquidity
-16; // net liquidity
+16 + // order liquidity 0
+16 + // order liquidity 1
+8 + // order batch 0
+8; // order batch 1
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized != 0
+    }
+
+    // liquidity and batch of the pending orders selling token_0 (zero_for_one) or token_1
+    pub fn pending_orders(&self, zero_for_one: bool) -> (u128, u64) {
+        if zero_for_one {
+            (self.order_liquidity_0, self.order_batch_0)
+        } else {
+            (self.order_liquidity_1, self.order_batch_1)
+        }
+    }
 
-    pub fn update_liquidity(&mut self, liquidity_delta: i128, is_lower: bool) -> Result<()> {
-        if !self.initialized {
-            self.initialized = true;
+    pub fn set_pending_orders(&mut self, zero_for_one: bool, liquidity: u128, batch: u64) {
+        if zero_for_one {
+            self.order_liquidity_0 = liquidity;
+            self.order_batch_0 = batch;
+        } else {
+            self.order_liquidity_1 = liquidity;
+            self.order_batch_1 = batch;
         }
+    }
 
-        self.gross_liquidity = self
-            .gross_liquidity
-            .checked_add(liquidity_delta.unsigned_abs())
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+    // applies a liquidity change to the tick and returns true if the tick flipped
+    // between initialized and uninitialized
+    pub fn update_liquidity(&mut self, liquidity_delta: i128, is_lower: bool) -> Result<bool> {
+        let gross_liquidity_before = self.gross_liquidity;
+
+        // gross liquidity counts every position referencing this tick,
+        // so it grows on deposits and shrinks on withdrawals
+        self.gross_liquidity = if liquidity_delta >= 0 {
+            gross_liquidity_before.checked_add(liquidity_delta.unsigned_abs())
+        } else {
+            gross_liquidity_before.checked_sub(liquidity_delta.unsigned_abs())
+        }
+        .ok_or(ClmmError::ArithmeticOverflow)?;
 
         if is_lower {
             self.net_liquidity = self
@@ -39,61 +83,381 @@ impl TickState {
                 .ok_or(ClmmError::ArithmeticOverflow)?;
         }
 
-        Ok(())
+        let flipped = (self.gross_liquidity == 0) != (gross_liquidity_before == 0);
+        self.initialized = (self.gross_liquidity > 0) as u8;
+
+        Ok(flipped)
     }
 }
 
-#[account]
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
 pub struct TickArrayState {
     pub pool: Pubkey,
+    // account that paid the rent, refunded by close_tick_array
+    pub payer: Pubkey,
     pub starting_tick: i32,
-    pub ticks: [TickState; TICKS_PER_ARRAY],
+    // layout version, bumped whenever the account layout changes
+    pub version: u8,
     pub bump: u8,
+    pub ticks: [TickState; TICKS_PER_ARRAY],
 }
 
 impl TickArrayState {
+    // 1: zero-copy with TICKS_PER_ARRAY ticks, start indices of negative ticks round down,
+    //    which moved every array below tick 0 to a new address, see get_start_tick_idx
+    // 2: pending limit orders per tick
+    pub const VERSION: u8 = 2;
+
     pub const SPACE: usize = ANCHOR_DISCRIMINATOR +
 32 + // pool
+32 + // payer
 4 + // starting tick
-TICKS_PER_ARRAY * 41 + // one tick is 41 bytes
-1; // bump
+1 + // version
+1 + // bump
+TICKS_PER_ARRAY * TickState::SPACE;
 }
 
 impl TickArrayState {
     // this function finds the start index of the array in which the provided tick is present
+    // arrays are aligned to multiples of (tick_spacing * TICKS_PER_ARRAY), rounding towards
+    // negative infinity so negative ticks land in the array below zero. unversioned arrays
+    // rounded towards zero instead, the start index is part of the PDA seeds so changing the
+    // rounding again moves arrays, see VERSION
     pub fn get_start_tick_idx(tick: i32, tick_spacing: i32) -> i32 {
-        let ticks_per_array = TICKS_PER_ARRAY as i32;
-
-        let array_idx = tick
-            .checked_div(tick_spacing)
-            .expect("Tick spacing: division by 0")
-            .checked_div(ticks_per_array)
-            .expect("Ticks per array: division by 0");
-
-        let start_tick = array_idx
-            .checked_mul(tick_spacing)
-            .expect("tick spacing: multiplication oveflow")
-            .checked_mul(ticks_per_array)
+        let array_width = tick_spacing
+            .checked_mul(TICKS_PER_ARRAY as i32)
             .expect("ticks per array: multiplication overflow");
 
-        start_tick
+        tick.div_euclid(array_width)
+            .checked_mul(array_width)
+            .expect("array width: multiplication overflow")
     }
-    // this function finds the tick state of the given tick from the aray and returns it mutably
-    pub fn get_tick_state_mut(&mut self, tick: i32, tick_spacing: i32) -> Result<&mut TickState> {
-        let ticks_per_array = TICKS_PER_ARRAY as i32;
-
-        let offset = (tick
-            .checked_div(tick_spacing) // position of the tick in the sequence
-            .ok_or(ClmmError::ArithmeticOverflow)?)
-        .checked_sub(
-            self.starting_tick
-                .checked_div(tick_spacing) // distance from the first idx
-                .ok_or(ClmmError::ArithmeticOverflow)?,
+
+    pub fn address(pool: &Pubkey, start_tick: i32) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"tick_array", pool.as_ref(), &start_tick.to_le_bytes()],
+            &crate::ID,
         )
-        .ok_or(ClmmError::ArithmeticOverflow)?
-        .checked_rem(ticks_per_array) // to make sure offset stays in the range
-        .ok_or(ClmmError::ArithmeticOverflow)? as usize;
+        .0
+    }
+
+    // the array a swap in this direction reads its boundary tick from
+    pub fn swap_tick_array(pool: &Account<Pool>, a_to_b: bool) -> Pubkey {
+        let boundary_tick = pool.swap_boundary_tick(a_to_b);
+        Self::address(
+            &pool.key(),
+            Self::get_start_tick_idx(boundary_tick, pool.tick_spacing),
+        )
+    }
+
+    // creates the tick array PDA if it does not exist yet
+    // used instead of init_if_needed so both ticks of a position can live in the same array
+    pub fn get_or_create<'info>(
+        tick_array: &AccountInfo<'info>,
+        payer: &AccountInfo<'info>,
+        system_program: &AccountInfo<'info>,
+        pool: Pubkey,
+        starting_tick: i32,
+        bump: u8,
+    ) -> Result<()> {
+        if tick_array.owner == &crate::ID {
+            return Ok(());
+        }
+
+        let starting_tick_bytes = starting_tick.to_le_bytes();
+        let seeds: &[&[u8]] = &[b"tick_array", pool.as_ref(), &starting_tick_bytes, &[bump]];
+        let signer_seeds = &[seeds];
+
+        // someone may have sent lamports to the address already, which would make
+        // create_account fail, so top up, allocate and assign in that case
+        let required_lamports = Rent::get()?.minimum_balance(Self::SPACE);
+        let current_lamports = tick_array.lamports();
+        if current_lamports == 0 {
+            system_program::create_account(
+                CpiContext::new_with_signer(
+                    system_program.clone(),
+                    system_program::CreateAccount {
+                        from: payer.clone(),
+                        to: tick_array.clone(),
+                    },
+                    signer_seeds,
+                ),
+                required_lamports,
+                Self::SPACE as u64,
+                &crate::ID,
+            )?;
+        } else {
+            if current_lamports < required_lamports {
+                system_program::transfer(
+                    CpiContext::new(
+                        system_program.clone(),
+                        system_program::Transfer {
+                            from: payer.clone(),
+                            to: tick_array.clone(),
+                        },
+                    ),
+                    required_lamports - current_lamports,
+                )?;
+            }
+            system_program::allocate(
+                CpiContext::new_with_signer(
+                    system_program.clone(),
+                    system_program::Allocate {
+                        account_to_allocate: tick_array.clone(),
+                    },
+                    signer_seeds,
+                ),
+                Self::SPACE as u64,
+            )?;
+            system_program::assign(
+                CpiContext::new_with_signer(
+                    system_program.clone(),
+                    system_program::Assign {
+                        account_to_assign: tick_array.clone(),
+                    },
+                    signer_seeds,
+                ),
+                &crate::ID,
+            )?;
+        }
+
+        // write the discriminator right away so a second reference to the same
+        // array in this instruction already sees an initialized account
+        let mut data = tick_array.try_borrow_mut_data()?;
+        data[..ANCHOR_DISCRIMINATOR].copy_from_slice(Self::DISCRIMINATOR);
+        let tick_array_state: &mut Self =
+            bytemuck::from_bytes_mut(&mut data[ANCHOR_DISCRIMINATOR..Self::SPACE]);
+        tick_array_state.initialize(pool, payer.key(), starting_tick, bump);
+
+        Ok(())
+    }
+
+    // loads a created tick array for writing, rejecting accounts with an unknown layout version
+    pub fn load_mut<'a>(tick_array: &'a AccountInfo) -> Result<RefMut<'a, Self>> {
+        require_keys_eq!(
+            *tick_array.owner,
+            crate::ID,
+            ClmmError::InvalidTickArrayAccount
+        );
+
+        let data = tick_array.try_borrow_mut_data()?;
+        require!(
+            data.len() == Self::SPACE && data[..ANCHOR_DISCRIMINATOR] == *Self::DISCRIMINATOR,
+            ClmmError::InvalidTickArrayAccount
+        );
+
+        let tick_array_state = RefMut::map(data, |data| {
+            bytemuck::from_bytes_mut::<Self>(&mut data[ANCHOR_DISCRIMINATOR..Self::SPACE])
+        });
+        require!(
+            tick_array_state.version == Self::VERSION,
+            ClmmError::InvalidTickArrayVersion
+        );
+
+        Ok(tick_array_state)
+    }
+
+    // load_mut for an array that may not have been created yet, see load_if_created
+    pub fn load_mut_if_created<'a>(
+        tick_array: &'a AccountInfo,
+    ) -> Result<Option<RefMut<'a, Self>>> {
+        if tick_array.owner == &system_program::ID && tick_array.data_is_empty() {
+            return Ok(None);
+        }
+        Self::load_mut(tick_array).map(Some)
+    }
+
+    // loads a tick array for reading, None while its PDA has not been created, which
+    // means none of its ticks were ever initialized
+    pub fn load_if_created<'a>(tick_array: &'a AccountInfo) -> Result<Option<Ref<'a, Self>>> {
+        if tick_array.owner == &system_program::ID && tick_array.data_is_empty() {
+            return Ok(None);
+        }
+        require_keys_eq!(
+            *tick_array.owner,
+            crate::ID,
+            ClmmError::InvalidTickArrayAccount
+        );
+
+        let data = tick_array.try_borrow_data()?;
+        require!(
+            data.len() == Self::SPACE && data[..ANCHOR_DISCRIMINATOR] == *Self::DISCRIMINATOR,
+            ClmmError::InvalidTickArrayAccount
+        );
 
+        let tick_array_state = Ref::map(data, |data| {
+            bytemuck::from_bytes::<Self>(&data[ANCHOR_DISCRIMINATOR..Self::SPACE])
+        });
+        require!(
+            tick_array_state.version == Self::VERSION,
+            ClmmError::InvalidTickArrayVersion
+        );
+
+        Ok(Some(tick_array_state))
+    }
+
+    pub fn initialize(&mut self, pool: Pubkey, payer: Pubkey, starting_tick: i32, bump: u8) {
+        self.pool = pool;
+        self.payer = payer;
+        self.starting_tick = starting_tick;
+        self.version = Self::VERSION;
+        self.bump = bump;
+    }
+
+    // an array is empty once none of its ticks hold liquidity
+    pub fn is_empty(&self) -> bool {
+        self.ticks.iter().all(|tick| !tick.is_initialized())
+    }
+
+    // position of the given tick in the array
+    fn tick_offset(&self, tick: i32, tick_spacing: i32) -> Result<usize> {
+        let offset = tick
+            .checked_sub(self.starting_tick) // distance from the first tick of the array
+            .ok_or(ClmmError::ArithmeticOverflow)?
+            .checked_div(tick_spacing) // position of the tick in the array
+            .ok_or(ClmmError::ArithmeticOverflow)?;
+
+        require!(
+            tick % tick_spacing == 0 && (0..TICKS_PER_ARRAY as i32).contains(&offset),
+            ClmmError::InvalidTickArrayAccount
+        );
+
+        Ok(offset as usize)
+    }
+
+    // this function finds the tick state of the given tick from the aray and returns it mutably
+    pub fn get_tick_state_mut(&mut self, tick: i32, tick_spacing: i32) -> Result<&mut TickState> {
+        let offset = self.tick_offset(tick, tick_spacing)?;
         Ok(&mut self.ticks[offset])
     }
+
+    pub fn get_tick_state(&self, tick: i32, tick_spacing: i32) -> Result<&TickState> {
+        let offset = self.tick_offset(tick, tick_spacing)?;
+        Ok(&self.ticks[offset])
+    }
+
+    // loads the arrays holding a position's ticks for writing, the upper one is None when both
+    // ticks live in the same array
+    pub fn load_position_arrays<'a>(
+        lower_tick_array: &'a AccountInfo,
+        upper_tick_array: &'a AccountInfo,
+    ) -> Result<(RefMut<'a, Self>, Option<RefMut<'a, Self>>)> {
+        let lower = Self::load_mut(lower_tick_array)?;
+        let upper = if lower_tick_array.key() == upper_tick_array.key() {
+            None
+        } else {
+            Some(Self::load_mut(upper_tick_array)?)
+        };
+        Ok((lower, upper))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tick_array;
+
+    fn empty_tick() -> TickState {
+        TickState {
+            gross_liquidity: 0,
+            net_liquidity: 0,
+            initialized: 0,
+            order_liquidity_0: 0,
+            order_liquidity_1: 0,
+            order_batch_0: 0,
+            order_batch_1: 0,
+        }
+    }
+
+    #[test]
+    fn test_add_liquidity_initializes_tick() {
+        let mut tick = empty_tick();
+
+        assert!(tick.update_liquidity(100, true).unwrap());
+        assert!(tick.is_initialized());
+        assert_eq!({ tick.gross_liquidity }, 100);
+        assert_eq!({ tick.net_liquidity }, 100);
+
+        // a second deposit does not flip the tick
+        assert!(!tick.update_liquidity(50, false).unwrap());
+        assert_eq!({ tick.gross_liquidity }, 150);
+        assert_eq!({ tick.net_liquidity }, 50);
+    }
+
+    #[test]
+    fn test_remove_liquidity_reduces_gross() {
+        let mut tick = empty_tick();
+        tick.update_liquidity(100, true).unwrap();
+
+        assert!(!tick.update_liquidity(-40, true).unwrap());
+        assert!(tick.is_initialized());
+        assert_eq!({ tick.gross_liquidity }, 60);
+        assert_eq!({ tick.net_liquidity }, 60);
+    }
+
+    #[test]
+    fn test_remove_all_liquidity_uninitializes_tick() {
+        let mut tick = empty_tick();
+        tick.update_liquidity(100, false).unwrap();
+
+        assert!(tick.update_liquidity(-100, false).unwrap());
+        assert!(!tick.is_initialized());
+        assert_eq!({ tick.gross_liquidity }, 0);
+        assert_eq!({ tick.net_liquidity }, 0);
+    }
+
+    #[test]
+    fn test_remove_more_than_gross_fails() {
+        let mut tick = empty_tick();
+        tick.update_liquidity(100, true).unwrap();
+
+        assert!(tick.update_liquidity(-101, true).is_err());
+    }
+
+    #[test]
+    fn test_space_matches_layout() {
+        assert_eq!(
+            TickArrayState::SPACE,
+            ANCHOR_DISCRIMINATOR + std::mem::size_of::<TickArrayState>()
+        );
+    }
+
+    #[test]
+    fn test_start_tick_idx_rounds_down() {
+        let width = 60 * TICKS_PER_ARRAY as i32;
+        assert_eq!(TickArrayState::get_start_tick_idx(0, 60), 0);
+        assert_eq!(TickArrayState::get_start_tick_idx(width - 1, 60), 0);
+        assert_eq!(TickArrayState::get_start_tick_idx(-1, 60), -width);
+        assert_eq!(TickArrayState::get_start_tick_idx(-width, 60), -width);
+        assert_eq!(TickArrayState::get_start_tick_idx(-width - 1, 60), -2 * width);
+    }
+
+    // pins the seeds of an array below tick 0, the unversioned truncating derivation put
+    // tick -1 in the array at 0
+    #[test]
+    fn test_negative_tick_array_address() {
+        let pool = Pubkey::new_unique();
+        let start = TickArrayState::get_start_tick_idx(-1, 60);
+        assert_eq!(start, -3600);
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"tick_array", pool.as_ref(), &(-3600i32).to_le_bytes()],
+            &crate::ID,
+        );
+        assert_eq!(TickArrayState::address(&pool, start), expected);
+        assert_ne!(TickArrayState::address(&pool, start), TickArrayState::address(&pool, 0));
+    }
+
+    #[test]
+    fn test_get_tick_state_checks_array_bounds() {
+        let width = 60 * TICKS_PER_ARRAY as i32;
+        let mut tick_array = tick_array(-width);
+
+        assert!(tick_array.get_tick_state_mut(-width, 60).is_ok());
+        assert!(tick_array.get_tick_state_mut(-60, 60).is_ok());
+        assert!(tick_array.get_tick_state_mut(0, 60).is_err());
+        assert!(tick_array.get_tick_state_mut(-width - 60, 60).is_err());
+        // ticks must sit on the spacing grid
+        assert!(tick_array.get_tick_state_mut(-30, 60).is_err());
+    }
 }
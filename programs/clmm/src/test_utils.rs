@@ -0,0 +1,47 @@
+// builders for the accounts the unit tests work on, a new field on one of them is only added
+// here. keys are left at their defaults, tests set the ones they care about
+use anchor_lang::prelude::Pubkey;
+
+use crate::{
+    states::{Pool, Position, TickArrayState},
+    utils::tick_to_sqrt_price_x96,
+};
+
+// a pool without liquidity whose price sits exactly on `tick`
+pub fn pool_at_tick(tick: i32, tick_spacing: i32) -> Pool {
+    Pool {
+        token_0: Pubkey::default(),
+        token_1: Pubkey::default(),
+        token_vault_0: Pubkey::default(),
+        token_vault_1: Pubkey::default(),
+        global_liquidity: 0,
+        sqrt_price_x96: tick_to_sqrt_price_x96(tick).unwrap(),
+        current_tick: tick,
+        tick_spacing,
+        bump: 0,
+        transfer_hook_program_0: Pubkey::default(),
+        transfer_hook_program_1: Pubkey::default(),
+        status: 0,
+        limit_order_batch: 0,
+    }
+}
+
+// an initialized, empty tick array starting at `start_tick`
+pub fn tick_array(start_tick: i32) -> TickArrayState {
+    let mut tick_array: TickArrayState = bytemuck::Zeroable::zeroed();
+    tick_array.initialize(Pubkey::default(), Pubkey::default(), start_tick, 0);
+    tick_array
+}
+
+// an empty position over [lower_tick, upper_tick]
+pub fn position(lower_tick: i32, upper_tick: i32) -> Position {
+    Position {
+        liquidity: 0,
+        lower_tick,
+        upper_tick,
+        owner: Pubkey::default(),
+        pool: Pubkey::default(),
+        id: 0,
+        bump: 0,
+    }
+}
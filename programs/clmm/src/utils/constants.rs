@@ -1,2 +1,11 @@
+use crate::utils::MAX_TICK;
+
 pub const ANCHOR_DISCRIMINATOR: usize = 8;
-pub const TICKS_PER_ARRAY: usize = 10;
+pub const TICKS_PER_ARRAY: usize = 60;
+// number of u64 words in a pool's TickArrayBitmap, one bit per tick array
+// sized so a pool with tick spacing 1 has a bit for every array on both sides of tick 0
+pub const TICK_ARRAY_BITMAP_WORDS: usize = ((MAX_TICK as usize / TICKS_PER_ARRAY + 1) * 2).div_ceil(64);
+// widest tick spacing a pool can use, keeps plenty of usable ticks inside MIN_TICK..=MAX_TICK
+pub const MAX_TICK_SPACING: i32 = 16384;
+// basis points in 100%
+pub const BPS_DENOMINATOR: u16 = 10_000;
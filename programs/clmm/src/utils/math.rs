@@ -21,7 +21,7 @@ pub fn integer_sqrt(value: u128) -> u64 {
         return 0;
     }
     let mut x = value;
-    let mut y = (value + 1) / 2;
+    let mut y = value.div_ceil(2);
 
     while y < x {
         x = y;
@@ -38,7 +38,7 @@ pub fn price_to_sqrt_price_x96(price: u64) -> Result<u128> {
     let price_scaled = price as u128;
 
     let mut x = price_scaled;
-    let mut y = (price_scaled + 1) / 2;
+    let mut y = price_scaled.div_ceil(2);
 
     while y < x {
         x = y;
@@ -51,7 +51,7 @@ pub fn price_to_sqrt_price_x96(price: u64) -> Result<u128> {
 }
 
 pub fn tick_to_sqrt_price_x96(tick: i32) -> Result<u128> {
-    let abs_tick = tick.abs() as u32;
+    let abs_tick = tick.unsigned_abs();
     require!(abs_tick <= MAX_TICK as u32, ClmmError::TickUpperOverflow);
 
     let mut ratio: u128 = if abs_tick & 0x1 != 0 {
@@ -124,7 +124,7 @@ pub fn tick_to_sqrt_price_x96(tick: i32) -> Result<u128> {
 
 pub fn sqrt_price_x96_to_tick(sqrt_price_x96: u128) -> Result<i32> {
     require!(
-        sqrt_price_x96 >= MIN_SQRT_PRICE_X96 && sqrt_price_x96 < MAX_SQRT_PRICE_X96,
+        (MIN_SQRT_PRICE_X96..=MAX_SQRT_PRICE_X96).contains(&sqrt_price_x96),
         ClmmError::SqrtPriceX96
     );
 
@@ -152,101 +152,152 @@ pub fn sqrt_price_x96_to_tick(sqrt_price_x96: u128) -> Result<i32> {
 
     let log2p_x64 = log2p_integer_x64 + log2p_fraction_x64;
 
-    let log_base: i128 = 1330580271462080i128;
-    let tick_approx = (log2p_x64 / log_base) as i32;
+    // log2(sqrt(1.0001)) as Q64, rounded to nearest
+    let log_base: i128 = 1330584781654115i128;
+    // floor, truncating would round negative ticks up
+    let tick_approx = log2p_x64.div_euclid(log_base) as i32;
+
+    // the truncated log2 leaves the estimate at most one tick off, settle on the greatest tick
+    // whose sqrt price is at or below the input with a single check either way
+    let tick = tick_approx.clamp(MIN_TICK, MAX_TICK);
+    if tick > MIN_TICK && tick_to_sqrt_price_x96(tick)? > sqrt_price_x96 {
+        Ok(tick - 1)
+    } else if tick < MAX_TICK && tick_to_sqrt_price_x96(tick + 1)? <= sqrt_price_x96 {
+        Ok(tick + 1)
+    } else {
+        Ok(tick)
+    }
+}
 
-    let tick_low = tick_approx - 1;
-    let tick_high = tick_approx + 1;
+// a * b / denominator over the full 256-bit product, rounded down or up
+pub fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> Result<u128> {
+    require!(denominator > 0, ClmmError::ArithmeticOverflow);
+    let (high, low) = full_mul(a, b);
 
-    Ok(if let Ok(price_high) = tick_to_sqrt_price_x96(tick_high) {
-        if price_high <= sqrt_price_x96 {
-            tick_high
-        } else if let Ok(price_approx) = tick_to_sqrt_price_x96(tick_approx) {
-            if price_approx <= sqrt_price_x96 {
-                tick_approx
-            } else {
-                tick_low
+    let (quotient, remainder) = if high == 0 {
+        (low / denominator, low % denominator)
+    } else {
+        // the quotient only fits when the high half is below the denominator
+        require!(high < denominator, ClmmError::ArithmeticOverflow);
+        // long division one bit at a time, the remainder stays below the denominator
+        let mut quotient = 0u128;
+        let mut remainder = high;
+        for bit in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = (remainder << 1) | ((low >> bit) & 1);
+            quotient <<= 1;
+            if carry == 1 || remainder >= denominator {
+                remainder = remainder.wrapping_sub(denominator);
+                quotient |= 1;
             }
-        } else {
-            tick_low
         }
+        (quotient, remainder)
+    };
+
+    if round_up && remainder > 0 {
+        Ok(quotient.checked_add(1).ok_or(ClmmError::ArithmeticOverflow)?)
     } else {
-        tick_approx
-    })
+        Ok(quotient)
+    }
+}
+
+// the 256-bit product of a and b as (high, low) halves
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const LOW_64: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & LOW_64);
+    let (b_high, b_low) = (b >> 64, b & LOW_64);
+
+    let (middle, middle_carry) = (a_high * b_low).overflowing_add(a_low * b_high);
+    let (low, low_carry) = (a_low * b_low).overflowing_add(middle << 64);
+    let high =
+        a_high * b_high + (middle >> 64) + ((middle_carry as u128) << 64) + low_carry as u128;
+    (high, low)
 }
 
+// token_0 held by `liquidity` between two sqrt prices: L * Q96 * (upper - lower) / (lower * upper)
+pub fn get_amount_0_delta(
+    sqrt_price_x96_lower: u128,
+    sqrt_price_x96_upper: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128> {
+    let delta = sqrt_price_x96_upper
+        .checked_sub(sqrt_price_x96_lower)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    // dividing by the lower price first leaves a second factor below one, so the first
+    // rounding is not magnified
+    let virtual_amount = mul_div(liquidity, Q96, sqrt_price_x96_lower, round_up)?;
+    mul_div(virtual_amount, delta, sqrt_price_x96_upper, round_up)
+}
+
+// token_1 held by `liquidity` between two sqrt prices: L * (upper - lower) / Q96
+pub fn get_amount_1_delta(
+    sqrt_price_x96_lower: u128,
+    sqrt_price_x96_upper: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128> {
+    let delta = sqrt_price_x96_upper
+        .checked_sub(sqrt_price_x96_lower)
+        .ok_or(ClmmError::ArithmeticOverflow)?;
+    mul_div(liquidity, delta, Q96, round_up)
+}
+
+// token amounts for `liquidity` over [lower, upper] at the current price. deposits round up
+// and withdrawals round down, so the vaults always cover every position
 pub fn get_amounts_for_liquidity(
     sqrt_price_x96_current: u128,
     sqrt_price_x96_lower: u128,
     sqrt_price_x96_upper: u128,
     liquidity: u128,
+    round_up: bool,
 ) -> Result<(u64, u64)> {
-    let amount_a: u64;
-    let amount_b: u64;
-
-    if sqrt_price_x96_current <= sqrt_price_x96_lower {
-        // token A only: L * (sqrt_upper - sqrt_lower) / sqrt_lower * Q96 / sqrt_upper
-        let delta = sqrt_price_x96_upper
-            .checked_sub(sqrt_price_x96_lower)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-        // L * delta fits u128 for reasonable values; then divide by sqrt_lower first
-        let step1 = liquidity
-            .checked_mul(delta)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            / sqrt_price_x96_lower;
-        // multiply by Q96, then divide by sqrt_upper
-        amount_a = (step1
-            .checked_mul(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            / sqrt_price_x96_upper)
-        .try_into()
-        .map_err(|_| ClmmError::ArithmeticOverflow)?;
-        amount_b = 0;
-    } else if sqrt_price_x96_current >= sqrt_price_x96_upper {
-        // token B only: L * (sqrt_upper - sqrt_lower) / Q96
-        amount_a = 0;
-        amount_b = (liquidity
-            .checked_mul(
-                sqrt_price_x96_upper
-                    .checked_sub(sqrt_price_x96_lower)
-                    .ok_or(ClmmError::ArithmeticOverflow)?,
-            )
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?)
-        .try_into()
-        .map_err(|_| ClmmError::ArithmeticOverflow)?;
+    // the current price clamped into the range splits it into a token A and a token B part
+    let sqrt_price_x96 = sqrt_price_x96_current.clamp(sqrt_price_x96_lower, sqrt_price_x96_upper);
+    let amount_a = get_amount_0_delta(sqrt_price_x96, sqrt_price_x96_upper, liquidity, round_up)?;
+    let amount_b = get_amount_1_delta(sqrt_price_x96_lower, sqrt_price_x96, liquidity, round_up)?;
+
+    Ok((
+        amount_a.try_into().map_err(|_| ClmmError::ArithmeticOverflow)?,
+        amount_b.try_into().map_err(|_| ClmmError::ArithmeticOverflow)?,
+    ))
+}
+
+// the most liquidity `amount_0` and `amount_1` pay for over [lower, upper] at the current
+// price, rounded down. the deposit for it rounds up, so it can still take one unit more of a
+// token than given
+pub fn get_liquidity_for_amounts(
+    sqrt_price_x96_current: u128,
+    sqrt_price_x96_lower: u128,
+    sqrt_price_x96_upper: u128,
+    amount_0: u64,
+    amount_1: u64,
+) -> Result<u128> {
+    require!(sqrt_price_x96_lower < sqrt_price_x96_upper, ClmmError::InvalidTicks);
+    // the same split as get_amounts_for_liquidity, a side the range does not hold sets no limit
+    let sqrt_price_x96 = sqrt_price_x96_current.clamp(sqrt_price_x96_lower, sqrt_price_x96_upper);
+
+    let liquidity_0 = if sqrt_price_x96 < sqrt_price_x96_upper {
+        // L = amount_0 * lower * upper / (Q96 * (upper - lower))
+        let delta = sqrt_price_x96_upper - sqrt_price_x96;
+        let scaled = mul_div(amount_0 as u128, sqrt_price_x96, Q96, false)?;
+        mul_div(scaled, sqrt_price_x96_upper, delta, false)?
+    } else {
+        u128::MAX
+    };
+    let liquidity_1 = if sqrt_price_x96 > sqrt_price_x96_lower {
+        // L = amount_1 * Q96 / (upper - lower)
+        mul_div(amount_1 as u128, Q96, sqrt_price_x96 - sqrt_price_x96_lower, false)?
     } else {
-        // both tokens
-        // token A: L * (sqrt_upper - sqrt_current) / sqrt_current * Q96 / sqrt_upper
-        let delta = sqrt_price_x96_upper
-            .checked_sub(sqrt_price_x96_current)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-        let step1 = liquidity
-            .checked_mul(delta)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            / sqrt_price_x96_current;
-        amount_a = (step1
-            .checked_mul(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            / sqrt_price_x96_upper)
-        .try_into()
-        .map_err(|_| ClmmError::ArithmeticOverflow)?;
-        // amount_b = L * (current - lower) / Q96
-        amount_b = (liquidity
-            .checked_mul(
-                sqrt_price_x96_current
-                    .checked_sub(sqrt_price_x96_lower)
-                    .ok_or(ClmmError::ArithmeticOverflow)?,
-            )
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?)
-        .try_into()
-        .map_err(|_| ClmmError::ArithmeticOverflow)?;
-    }
-    Ok((amount_a, amount_b))
+        u128::MAX
+    };
+    Ok(liquidity_0.min(liquidity_1))
 }
+
+// one step toward the target price at constant liquidity, returns the new price, the input
+// used and the output. rounding favors the pool: the input needed to reach the target rounds
+// up, a partial step moves the price no further than its input pays for, the output rounds
+// down
 pub fn compute_swap_step(
     sqrt_price_current_x96: u128,
     sqrt_price_target_x96: u128,
@@ -257,135 +308,55 @@ pub fn compute_swap_step(
     require!(liquidity > 0, ClmmError::InsufficientLiquidity);
 
     if a_to_b {
-
         // a_to_b (selling token_a, price goes DOWN)
-        // formula:
-        //   required_in = L * (1/√P_target - 1/√P_current)
-        //
-        // in Q96 fixed-point, 1/√P = Q96 / √P_x96, so:
-        //   required_in = L * Q96 / √P_target - L * Q96 / √P_current
-        //
-        // we compute each term separately to avoid overflow.
-
-        let l_q96_div_target = liquidity
-            .checked_mul(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(sqrt_price_target_x96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-
-        let l_q96_div_current = liquidity
-            .checked_mul(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(sqrt_price_current_x96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-
-        let required_in = l_q96_div_target
-            .checked_sub(l_q96_div_current)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+        let required_in =
+            get_amount_0_delta(sqrt_price_target_x96, sqrt_price_current_x96, liquidity, true)?;
 
         // can we reach the target tick, or do we stop partway?
         let (next_sqrt_price_x96, amount_in) = if amount_remaining >= required_in {
-            // we have enough input to fully cross to the target tick
             (sqrt_price_target_x96, required_in)
         } else {
             // not enough input — price stops between current and target
             //
             // formula:
-            //   1/√P_new = 1/√P_current + amount_in / (L * Q96)
             //   √P_new = L * Q96 / (L * Q96 / √P_current + amount_in)
             //
-            // we already computed L*Q96/√P_current above, so reuse it.
-
-            let denom = l_q96_div_current
+            // flooring the virtual token_a reserve and rounding the quotient up keeps the
+            // new price at or above the exact one
+            let virtual_amount = mul_div(liquidity, Q96, sqrt_price_current_x96, false)?;
+            let denom = virtual_amount
                 .checked_add(amount_remaining)
                 .ok_or(ClmmError::ArithmeticOverflow)?;
-
-            let next_price = liquidity
-                .checked_mul(Q96)
-                .ok_or(ClmmError::ArithmeticOverflow)?
-                .checked_div(denom)
-                .ok_or(ClmmError::ArithmeticOverflow)?;
-
-            (next_price, amount_remaining)
+            (mul_div(liquidity, Q96, denom, true)?, amount_remaining)
         };
 
-        //  how much token_b the user receives
-        //   amount_out = L * (√P_current - √P_next) / Q96
-        //
-        // this is safe because (√P_current - √P_next) < Q96 and L is small
-        let out_diff = sqrt_price_current_x96
-            .checked_sub(next_sqrt_price_x96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-
-        let amount_out = liquidity
-            .checked_mul(out_diff)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+        // how much token_b the user receives
+        let amount_out =
+            get_amount_1_delta(next_sqrt_price_x96, sqrt_price_current_x96, liquidity, false)?;
 
         Ok((next_sqrt_price_x96, amount_in, amount_out))
     } else {
-        // CASE: b_to_a (selling token_b, price goes UP)
-        //
-        // formula:
-        //   required_in = L * (√P_target - √P_current) / Q96
-        //
-        // this is safe: L * price_diff can overflow, but we divide by Q96
-        // immediately, so we split it: (L * price_diff) / Q96
-
-        let price_diff = sqrt_price_target_x96
-            .checked_sub(sqrt_price_current_x96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-
-        let required_in = liquidity
-            .checked_mul(price_diff)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+        // b_to_a (selling token_b, price goes UP)
+        let required_in =
+            get_amount_1_delta(sqrt_price_current_x96, sqrt_price_target_x96, liquidity, true)?;
 
         // full cross or partial step?
         let (next_sqrt_price_x96, amount_in) = if amount_remaining >= required_in {
-            // enough input to reach the target tick
             (sqrt_price_target_x96, required_in)
         } else {
             // partial step — price stops between current and target
             //
-            //   next_price = √P_current + (amount_in * Q96 / L)
-
-            let price_delta = amount_remaining
-                .checked_mul(Q96)
-                .ok_or(ClmmError::ArithmeticOverflow)?
-                .checked_div(liquidity)
-                .ok_or(ClmmError::ArithmeticOverflow)?;
-
+            //   next_price = √P_current + (amount_in * Q96 / L), rounded down
+            let price_delta = mul_div(amount_remaining, Q96, liquidity, false)?;
             let next_price = sqrt_price_current_x96
                 .checked_add(price_delta)
                 .ok_or(ClmmError::ArithmeticOverflow)?;
-
             (next_price, amount_remaining)
         };
 
-        // calculate output: how much token_a the user receives
-        //   amount_out = L * (1/√P_current - 1/√P_next)
-        //             = L * Q96 / √P_current - L * Q96 / √P_next
-        //
-        // same divide-first trick as the a_to_b required_in calculation.
-
-        let l_q96_div_current = liquidity
-            .checked_mul(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(sqrt_price_current_x96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-
-        let l_q96_div_next = liquidity
-            .checked_mul(Q96)
-            .ok_or(ClmmError::ArithmeticOverflow)?
-            .checked_div(next_sqrt_price_x96)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
-
-        let amount_out = l_q96_div_current
-            .checked_sub(l_q96_div_next)
-            .ok_or(ClmmError::ArithmeticOverflow)?;
+        // how much token_a the user receives
+        let amount_out =
+            get_amount_0_delta(sqrt_price_current_x96, next_sqrt_price_x96, liquidity, false)?;
 
         Ok((next_sqrt_price_x96, amount_in, amount_out))
     }
@@ -394,6 +365,7 @@ pub fn compute_swap_step(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_price_to_sqrt_price_x96_perfect_square() {
@@ -463,7 +435,7 @@ mod tests {
         let original_tick = 1000;
         let sqrt_price = tick_to_sqrt_price_x96(original_tick).unwrap();
         let recovered_tick = sqrt_price_x96_to_tick(sqrt_price).unwrap();
-        assert!((recovered_tick - original_tick).abs() <= 1);
+        assert_eq!(recovered_tick, original_tick);
     }
 
     #[test]
@@ -471,7 +443,85 @@ mod tests {
         let original_tick = -5000;
         let sqrt_price = tick_to_sqrt_price_x96(original_tick).unwrap();
         let recovered_tick = sqrt_price_x96_to_tick(sqrt_price).unwrap();
-        assert!((recovered_tick - original_tick).abs() <= 1);
+        assert_eq!(recovered_tick, original_tick);
+    }
+
+    // a u128 of random bit width, so both small and full-width values come up
+    fn any_width() -> impl Strategy<Value = u128> {
+        (any::<u128>(), 0..128u32).prop_map(|(value, shift)| value >> shift)
+    }
+
+    // checks that `tick` is the greatest tick whose sqrt price is at or below `sqrt_price`
+    fn assert_greatest_tick_at_or_below(sqrt_price: u128, tick: i32) {
+        assert!(
+            tick_to_sqrt_price_x96(tick).unwrap() <= sqrt_price,
+            "tick {tick} is above sqrt price {sqrt_price}"
+        );
+        if tick < MAX_TICK {
+            assert!(
+                tick_to_sqrt_price_x96(tick + 1).unwrap() > sqrt_price,
+                "tick {} is also at or below sqrt price {sqrt_price}",
+                tick + 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_tick_to_sqrt_price_is_strictly_increasing() {
+        let mut previous = tick_to_sqrt_price_x96(MIN_TICK).unwrap();
+        for tick in MIN_TICK + 1..=MAX_TICK {
+            let sqrt_price = tick_to_sqrt_price_x96(tick).unwrap();
+            assert!(sqrt_price > previous, "not increasing at tick {tick}");
+            previous = sqrt_price;
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_is_exact_for_every_tick() {
+        for tick in MIN_TICK..=MAX_TICK {
+            let sqrt_price = tick_to_sqrt_price_x96(tick).unwrap();
+            assert_eq!(sqrt_price_x96_to_tick(sqrt_price).unwrap(), tick);
+        }
+    }
+
+    #[test]
+    fn test_price_just_below_a_tick_maps_to_the_previous_tick() {
+        for tick in MIN_TICK + 1..=MAX_TICK {
+            let sqrt_price = tick_to_sqrt_price_x96(tick).unwrap() - 1;
+            assert_eq!(sqrt_price_x96_to_tick(sqrt_price).unwrap(), tick - 1);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10_000))]
+
+        // uniform prices land almost entirely in the upper ticks
+        #[test]
+        fn test_sqrt_price_to_tick_is_greatest_tick_at_or_below(
+            sqrt_price in MIN_SQRT_PRICE_X96..=MAX_SQRT_PRICE_X96,
+        ) {
+            let tick = sqrt_price_x96_to_tick(sqrt_price).unwrap();
+            assert_greatest_tick_at_or_below(sqrt_price, tick);
+        }
+
+        // so also pick a random tick and a random price inside it
+        #[test]
+        fn test_sqrt_price_inside_a_tick_maps_to_it(
+            (tick, sqrt_price) in (MIN_TICK..MAX_TICK).prop_flat_map(|tick| {
+                let low = tick_to_sqrt_price_x96(tick).unwrap();
+                let high = tick_to_sqrt_price_x96(tick + 1).unwrap() - 1;
+                (Just(tick), low..=high)
+            }),
+        ) {
+            prop_assert_eq!(sqrt_price_x96_to_tick(sqrt_price).unwrap(), tick);
+        }
+    }
+
+    #[test]
+    fn test_sqrt_price_to_tick_at_the_bounds() {
+        assert_eq!(sqrt_price_x96_to_tick(MIN_SQRT_PRICE_X96).unwrap(), MIN_TICK);
+        assert_eq!(sqrt_price_x96_to_tick(MAX_SQRT_PRICE_X96).unwrap(), MAX_TICK);
+        assert!(sqrt_price_x96_to_tick(MAX_SQRT_PRICE_X96 + 1).is_err());
     }
 
     #[test]
@@ -488,6 +538,141 @@ mod tests {
         assert!(result.is_err());
     }
 
+    // mul_div tests
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10_000))]
+
+        // random bit widths so both the 128-bit and the 256-bit paths are taken
+        #[test]
+        fn test_mul_div_matches_big_integers(
+            a in any_width(),
+            b in any_width(),
+            denominator in any_width().prop_map(|denominator| denominator.max(1)),
+        ) {
+            use num_bigint::BigUint;
+
+            let product = BigUint::from(a) * BigUint::from(b);
+            let floor = &product / denominator;
+            let ceil = (&product + denominator - 1u32) / denominator;
+            for (round_up, expected) in [(false, floor), (true, ceil)] {
+                match u128::try_from(&expected) {
+                    Ok(expected) => {
+                        prop_assert_eq!(mul_div(a, b, denominator, round_up).unwrap(), expected)
+                    }
+                    Err(_) => prop_assert!(mul_div(a, b, denominator, round_up).is_err()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mul_div_edges() {
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX, false).unwrap(), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX, true).unwrap(), u128::MAX);
+        assert_eq!(mul_div(7, 3, 2, false).unwrap(), 10);
+        assert_eq!(mul_div(7, 3, 2, true).unwrap(), 11);
+        assert!(mul_div(u128::MAX, 2, 1, false).is_err());
+        assert!(mul_div(u128::MAX, u128::MAX, u128::MAX - 1, true).is_err());
+        assert!(mul_div(1, 1, 0, false).is_err());
+    }
+
+    #[test]
+    fn test_amounts_for_liquidity_round_toward_the_pool() {
+        let lower = tick_to_sqrt_price_x96(-600).unwrap();
+        let upper = tick_to_sqrt_price_x96(60).unwrap();
+        let current = tick_to_sqrt_price_x96(-7).unwrap();
+        let liquidity = 1_000_000_007;
+
+        let deposit = get_amounts_for_liquidity(current, lower, upper, liquidity, true).unwrap();
+        let withdraw = get_amounts_for_liquidity(current, lower, upper, liquidity, false).unwrap();
+        assert_eq!((deposit.0 - withdraw.0, deposit.1 - withdraw.1), (1, 1));
+
+        // outside the range only one token is held
+        let below = get_amounts_for_liquidity(lower - 1, lower, upper, liquidity, false).unwrap();
+        let above = get_amounts_for_liquidity(upper, lower, upper, liquidity, false).unwrap();
+        assert!(below.0 > 0 && below.1 == 0);
+        assert!(above.0 == 0 && above.1 > 0);
+    }
+
+    #[test]
+    fn test_liquidity_for_amounts_inverts_amounts_for_liquidity() {
+        let lower = tick_to_sqrt_price_x96(-600).unwrap();
+        let upper = tick_to_sqrt_price_x96(60).unwrap();
+        let liquidity = 1_000_000_007;
+
+        for current in [lower - 1, lower, tick_to_sqrt_price_x96(-7).unwrap(), upper, upper + 1] {
+            let (amount_0, amount_1) =
+                get_amounts_for_liquidity(current, lower, upper, liquidity, false).unwrap();
+            let found =
+                get_liquidity_for_amounts(current, lower, upper, amount_0, amount_1).unwrap();
+            assert!(found <= liquidity && liquidity - found < liquidity / 100_000, "{found}");
+            let paid = get_amounts_for_liquidity(current, lower, upper, found, false).unwrap();
+            assert!(paid.0 <= amount_0 && paid.1 <= amount_1);
+        }
+
+        // the scarcer token sets the liquidity, the other one is left over
+        let current = tick_to_sqrt_price_x96(-7).unwrap();
+        let (amount_0, amount_1) =
+            get_amounts_for_liquidity(current, lower, upper, liquidity, false).unwrap();
+        let found = get_liquidity_for_amounts(current, lower, upper, amount_0, 2 * amount_1);
+        assert!(found.unwrap() <= liquidity);
+        assert!(get_liquidity_for_amounts(current, upper, lower, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_swap_step_with_liquidity_above_2_pow_32() {
+        // L * Q96 no longer fits a u128 here
+        let sqrt_current = tick_to_sqrt_price_x96(0).unwrap();
+        let sqrt_target = tick_to_sqrt_price_x96(-60).unwrap();
+        let liquidity: u128 = 1 << 60;
+
+        let (next_price, amount_in, amount_out) =
+            compute_swap_step(sqrt_current, sqrt_target, liquidity, 1_000_000, true).unwrap();
+        assert!(next_price < sqrt_current && next_price > sqrt_target);
+        assert_eq!(amount_in, 1_000_000);
+        // at a price of ~1 the output is just under the input
+        assert!(amount_out < amount_in && amount_out > amount_in - 2);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2_000))]
+
+        #[test]
+        fn test_swap_step_rounds_for_the_pool(
+            current_tick in -1_000..=1_000,
+            target_distance in 1..=600,
+            liquidity in 1..=1u128 << 80,
+            // compute_swap never steps with no input
+            amount_remaining in (any::<u64>(), 0..64u32)
+                .prop_map(|(amount, shift)| (amount >> shift).max(1) as u128),
+        ) {
+            use num_bigint::BigUint;
+
+            let big = BigUint::from;
+            let q96 = big(Q96);
+            let current = tick_to_sqrt_price_x96(current_tick).unwrap();
+
+            // falling price, exact: in = L * Q96 * (cur - next) / (cur * next)
+            let target = tick_to_sqrt_price_x96(current_tick - target_distance).unwrap();
+            let (next, amount_in, amount_out) =
+                compute_swap_step(current, target, liquidity, amount_remaining, true).unwrap();
+            prop_assert!(next >= target && next <= current);
+            let paid = big(liquidity) * &q96 * big(current - next);
+            prop_assert!(big(amount_in) * big(current) * big(next) >= paid);
+            prop_assert!(big(amount_out) * &q96 <= big(liquidity) * big(current - next));
+
+            // rising price, exact: in = L * (next - cur) / Q96
+            let target = tick_to_sqrt_price_x96(current_tick + target_distance).unwrap();
+            let (next, amount_in, amount_out) =
+                compute_swap_step(current, target, liquidity, amount_remaining, false).unwrap();
+            prop_assert!(next <= target && next >= current);
+            prop_assert!(big(amount_in) * &q96 >= big(liquidity) * big(next - current));
+            let owed = big(liquidity) * &q96 * big(next - current);
+            prop_assert!(big(amount_out) * big(current) * big(next) <= owed);
+        }
+    }
+
     // compute_swap_step tests
 
     #[test]
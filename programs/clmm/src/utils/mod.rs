@@ -1,5 +1,7 @@
 pub mod constants;
 pub mod math;
+pub mod validation;
 
 pub use constants::*;
 pub use math::*;
+pub use validation::*;
@@ -0,0 +1,166 @@
+use crate::errors::ClmmError;
+use crate::utils::{tick_to_sqrt_price_x96, MAX_TICK, MAX_TICK_SPACING, MIN_TICK};
+use anchor_lang::prelude::*;
+
+// lowest and highest ticks that are multiples of `tick_spacing`
+pub fn usable_tick_range(tick_spacing: i32) -> (i32, i32) {
+    (
+        -(-MIN_TICK / tick_spacing) * tick_spacing,
+        (MAX_TICK / tick_spacing) * tick_spacing,
+    )
+}
+
+pub fn validate_tick_spacing(tick_spacing: i32) -> Result<()> {
+    require!(tick_spacing > 0, ClmmError::InvalidTickSpacing);
+    require!(tick_spacing <= MAX_TICK_SPACING, ClmmError::TickSpacingTooLarge);
+
+    // at least one position range has to fit between the usable bounds
+    let (min_usable_tick, max_usable_tick) = usable_tick_range(tick_spacing);
+    require!(
+        max_usable_tick - min_usable_tick >= tick_spacing,
+        ClmmError::TickSpacingTooLarge
+    );
+    Ok(())
+}
+
+// a pool price has to sit strictly between the usable bounds so both sides stay tradable
+pub fn validate_sqrt_price(sqrt_price_x96: u128, tick_spacing: i32) -> Result<()> {
+    let (min_usable_tick, max_usable_tick) = usable_tick_range(tick_spacing);
+    require!(
+        sqrt_price_x96 > tick_to_sqrt_price_x96(min_usable_tick)?,
+        ClmmError::SqrtPriceBelowMinimum
+    );
+    require!(
+        sqrt_price_x96 < tick_to_sqrt_price_x96(max_usable_tick)?,
+        ClmmError::SqrtPriceAboveMaximum
+    );
+    Ok(())
+}
+
+// a position spans lower_tick..upper_tick, both on the spacing grid and inside the tick range
+pub fn validate_position_range(lower_tick: i32, upper_tick: i32, tick_spacing: i32) -> Result<()> {
+    require!(lower_tick < upper_tick, ClmmError::InvalidPositionRange);
+    require!(
+        lower_tick >= MIN_TICK && upper_tick <= MAX_TICK,
+        ClmmError::TickOutOfBounds
+    );
+    require!(
+        lower_tick % tick_spacing == 0 && upper_tick % tick_spacing == 0,
+        ClmmError::InvalidTickRange
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{sqrt_price_x96_to_tick, MAX_SQRT_PRICE_X96, MIN_SQRT_PRICE_X96};
+
+    #[test]
+    fn test_usable_tick_range_is_spacing_aligned() {
+        assert_eq!(usable_tick_range(1), (MIN_TICK, MAX_TICK));
+        assert_eq!(usable_tick_range(60), (-443580, 443580));
+        let (min, max) = usable_tick_range(MAX_TICK_SPACING);
+        assert_eq!((min % MAX_TICK_SPACING, max % MAX_TICK_SPACING), (0, 0));
+        assert!(min >= MIN_TICK && max <= MAX_TICK);
+    }
+
+    #[test]
+    fn test_validate_tick_spacing() {
+        assert!(validate_tick_spacing(1).is_ok());
+        assert!(validate_tick_spacing(MAX_TICK_SPACING).is_ok());
+        assert_eq!(
+            validate_tick_spacing(0).unwrap_err(),
+            ClmmError::InvalidTickSpacing.into()
+        );
+        assert_eq!(
+            validate_tick_spacing(MAX_TICK_SPACING + 1).unwrap_err(),
+            ClmmError::TickSpacingTooLarge.into()
+        );
+        assert_eq!(
+            validate_tick_spacing(MAX_TICK + 1).unwrap_err(),
+            ClmmError::TickSpacingTooLarge.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_sqrt_price_bounds() {
+        let (min_usable_tick, max_usable_tick) = usable_tick_range(60);
+        let min_sqrt = tick_to_sqrt_price_x96(min_usable_tick).unwrap();
+        let max_sqrt = tick_to_sqrt_price_x96(max_usable_tick).unwrap();
+
+        assert!(validate_sqrt_price(1 << 96, 60).is_ok());
+        assert!(validate_sqrt_price(min_sqrt + 1, 60).is_ok());
+        assert_eq!(
+            validate_sqrt_price(min_sqrt, 60).unwrap_err(),
+            ClmmError::SqrtPriceBelowMinimum.into()
+        );
+        assert_eq!(
+            validate_sqrt_price(max_sqrt, 60).unwrap_err(),
+            ClmmError::SqrtPriceAboveMaximum.into()
+        );
+        assert_eq!(
+            validate_sqrt_price(0, 60).unwrap_err(),
+            ClmmError::SqrtPriceBelowMinimum.into()
+        );
+    }
+
+    #[test]
+    fn test_valid_prices_convert_to_ticks() {
+        // anything validate_sqrt_price accepts must also be accepted by sqrt_price_x96_to_tick
+        let min_sqrt = tick_to_sqrt_price_x96(MIN_TICK).unwrap();
+        let max_sqrt = tick_to_sqrt_price_x96(MAX_TICK).unwrap();
+        assert!(min_sqrt >= MIN_SQRT_PRICE_X96);
+        assert!(max_sqrt <= MAX_SQRT_PRICE_X96);
+        assert!(sqrt_price_x96_to_tick(min_sqrt + 1).is_ok());
+        assert!(sqrt_price_x96_to_tick(max_sqrt - 1).is_ok());
+    }
+
+    #[test]
+    fn test_validate_position_range() {
+        assert!(validate_position_range(-600, 60, 60).is_ok());
+        assert!(validate_position_range(-443580, 443580, 60).is_ok());
+        assert!(validate_position_range(MIN_TICK, MAX_TICK, 1).is_ok());
+    }
+
+    #[test]
+    fn test_validate_position_range_rejects_inverted() {
+        assert_eq!(
+            validate_position_range(60, -600, 60).unwrap_err(),
+            ClmmError::InvalidPositionRange.into()
+        );
+        assert_eq!(
+            validate_position_range(60, 60, 60).unwrap_err(),
+            ClmmError::InvalidPositionRange.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_position_range_rejects_misaligned() {
+        assert_eq!(
+            validate_position_range(-590, 60, 60).unwrap_err(),
+            ClmmError::InvalidTickRange.into()
+        );
+        assert_eq!(
+            validate_position_range(-600, 61, 60).unwrap_err(),
+            ClmmError::InvalidTickRange.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_position_range_rejects_out_of_bounds() {
+        assert_eq!(
+            validate_position_range(MIN_TICK - 1, 0, 1).unwrap_err(),
+            ClmmError::TickOutOfBounds.into()
+        );
+        assert_eq!(
+            validate_position_range(0, MAX_TICK + 1, 1).unwrap_err(),
+            ClmmError::TickOutOfBounds.into()
+        );
+        // aligned to the spacing but past the last usable tick
+        assert_eq!(
+            validate_position_range(-443640, 0, 60).unwrap_err(),
+            ClmmError::TickOutOfBounds.into()
+        );
+    }
+}
@@ -0,0 +1,526 @@
+// differential fuzzing of the pool math against the exact reference in `model`
+//
+// each case runs a random sequence of open / increase / decrease / close / swap through the
+// program's `core` functions, the ones the instruction handlers call, on a Pool, its tick
+// arrays and positions, and checks every step against the reference:
+//  - token amounts and the swap price agree with the exact values within rounding
+//  - global_liquidity equals the liquidity of the positions whose range holds current_tick
+//  - the vaults always hold at least what every position could withdraw
+//
+// the sequences are proptest strategies: a failure is shrunk to a shortest sequence that still
+// fails and saved under proptest-regressions/, so it is replayed first on the next run.
+// CLMM_FUZZ_CASES overrides the number of cases
+
+mod model;
+
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::*;
+use clmm::{
+    core::{self, LiquidityChange, PositionTickArrays, SwapResult},
+    errors::ClmmError,
+    states::{Pool, Position, TickArrayState},
+    test_utils::{pool_at_tick, position, tick_array},
+    utils::{sqrt_price_x96_to_tick, tick_to_sqrt_price_x96},
+};
+use model::{amounts_for_liquidity, swap_step};
+use num_bigint::BigUint;
+use proptest::{prelude::*, sample::Index};
+
+const DEFAULT_CASES: u32 = 200;
+const OPS_PER_CASE: usize = 60;
+// wide enough that some amounts exceed a u64 and the instruction is rejected
+const MAX_LIQUIDITY: u128 = 1 << 80;
+const MAX_SWAP_AMOUNT: u128 = u64::MAX as u128;
+// distance in tick spacings from the current tick for new position bounds
+const RANGE_SPACINGS: i64 = 40;
+
+// fixed-point results may differ from the exact ones by this many units
+const AMOUNT_TOLERANCE: u128 = 2;
+
+// a pool driven by the program's core functions, with tick arrays created on demand the way
+// open_position creates them
+#[derive(Clone)]
+struct Engine {
+    pool: Pool,
+    tick_arrays: BTreeMap<i32, TickArrayState>,
+    positions: Vec<Position>,
+    vault_0: u128,
+    vault_1: u128,
+}
+
+// the liquidity instructions, each run through its core function
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LiquidityOp {
+    Open,
+    Increase,
+    Decrease,
+    Close,
+}
+
+impl Engine {
+    fn new(tick_spacing: i32, tick: i32) -> Self {
+        Self {
+            pool: pool_at_tick(tick, tick_spacing),
+            tick_arrays: BTreeMap::new(),
+            positions: Vec::new(),
+            vault_0: 0,
+            vault_1: 0,
+        }
+    }
+
+    fn modify_liquidity(
+        &mut self,
+        op: LiquidityOp,
+        index: usize,
+        liquidity: u128,
+    ) -> Result<LiquidityChange> {
+        let tick_spacing = self.pool.tick_spacing;
+        let position = &mut self.positions[index];
+        let lower_start = TickArrayState::get_start_tick_idx(position.lower_tick, tick_spacing);
+        let upper_start = TickArrayState::get_start_tick_idx(position.upper_tick, tick_spacing);
+        for start in [lower_start, upper_start] {
+            self.tick_arrays.entry(start).or_insert_with(|| tick_array(start));
+        }
+
+        let mut arrays = self.tick_arrays.range_mut(lower_start..=upper_start);
+        let lower = arrays.next().unwrap().1;
+        let upper = (upper_start != lower_start).then(|| arrays.next_back().unwrap().1);
+        let tick_arrays = PositionTickArrays { lower, upper };
+
+        let pool = &mut self.pool;
+        match op {
+            LiquidityOp::Open => core::open_position(pool, position, tick_arrays, liquidity),
+            LiquidityOp::Increase => {
+                core::increase_liquidity(pool, position, tick_arrays, liquidity)
+            }
+            LiquidityOp::Decrease => {
+                core::decrease_liquidity(pool, position, tick_arrays, liquidity)
+            }
+            LiquidityOp::Close => core::close_position(pool, position, tick_arrays),
+        }
+    }
+
+    fn swap(&mut self, amount_in: u64, a_to_b: bool) -> Result<SwapResult> {
+        let boundary_tick = self.pool.swap_boundary_tick(a_to_b);
+        let start = TickArrayState::get_start_tick_idx(boundary_tick, self.pool.tick_spacing);
+        core::swap(&mut self.pool, self.tick_arrays.get_mut(&start), amount_in, a_to_b)
+    }
+}
+
+// a log-uniform amount, so small and large amounts are both exercised
+#[derive(Clone, Copy, Debug)]
+struct Amount {
+    bits: Index,
+    value: u128,
+}
+
+impl Amount {
+    // in 1..=max
+    fn up_to(self, max: u128) -> u128 {
+        let bits = self.bits.index((128 - max.leading_zeros()) as usize) as u32 + 1;
+        (self.value >> (128 - bits)).clamp(1, max)
+    }
+}
+
+fn amount() -> impl Strategy<Value = Amount> {
+    (any::<Index>(), any::<u128>()).prop_map(|(bits, value)| Amount { bits, value })
+}
+
+// one instruction of a case. positions are picked among the open ones and range bounds are
+// given in tick spacings from the current tick, so every op applies to any state
+#[derive(Clone, Debug)]
+enum Op {
+    Open { lower: i64, upper: i64, liquidity: Amount },
+    Increase { position: Index, liquidity: Amount },
+    Decrease { position: Index, liquidity: Amount },
+    Close { position: Index },
+    Swap { a_to_b: bool, amount_in: Amount },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let spacings = -RANGE_SPACINGS..=RANGE_SPACINGS;
+    prop_oneof![
+        2 => (spacings.clone(), spacings, amount())
+            .prop_map(|(lower, upper, liquidity)| Op::Open { lower, upper, liquidity }),
+        1 => (any::<Index>(), amount())
+            .prop_map(|(position, liquidity)| Op::Increase { position, liquidity }),
+        1 => (any::<Index>(), amount())
+            .prop_map(|(position, liquidity)| Op::Decrease { position, liquidity }),
+        1 => any::<Index>().prop_map(|position| Op::Close { position }),
+        5 => (any::<bool>(), amount())
+            .prop_map(|(a_to_b, amount_in)| Op::Swap { a_to_b, amount_in }),
+    ]
+}
+
+struct Case {
+    engine: Engine,
+    step: usize,
+}
+
+impl Case {
+    fn new(tick_spacing: i32, tick: i32) -> Self {
+        Self {
+            engine: Engine::new(tick_spacing, tick),
+            step: 0,
+        }
+    }
+
+    fn context(&self, what: &str) -> String {
+        format!("step {}: {what}", self.step)
+    }
+
+    // liquidity of the positions whose range holds `tick`, by the reference's bookkeeping
+    fn active_liquidity(&self, tick: i32) -> u128 {
+        self.engine
+            .positions
+            .iter()
+            .filter(|position| position.lower_tick <= tick && tick < position.upper_tick)
+            .map(|position| position.liquidity)
+            .sum()
+    }
+
+    // the range `lower` and `upper` tick spacings from the current tick, in either order
+    fn range(&self, lower: i64, upper: i64) -> (i32, i32) {
+        let spacing = self.engine.pool.tick_spacing as i64;
+        let base = (self.engine.pool.current_tick as i64).div_euclid(spacing);
+        let mut lower = base + lower;
+        let mut upper = base + upper;
+        if lower == upper {
+            upper += 1;
+        }
+        if lower > upper {
+            std::mem::swap(&mut lower, &mut upper);
+        }
+        ((lower * spacing) as i32, (upper * spacing) as i32)
+    }
+
+    // deposit or withdrawal amounts must match the reference, deposits rounded up and
+    // withdrawals rounded down so the vaults never pay out more than they took in
+    fn check_liquidity_amounts(
+        &self,
+        lower_tick: i32,
+        upper_tick: i32,
+        liquidity: u128,
+        amounts: (u64, u64),
+        deposit: bool,
+    ) {
+        let (exact_0, exact_1) = amounts_for_liquidity(
+            self.engine.pool.sqrt_price_x96,
+            tick_to_sqrt_price_x96(lower_tick).unwrap(),
+            tick_to_sqrt_price_x96(upper_tick).unwrap(),
+            liquidity,
+        );
+        for (exact, amount, token) in [(exact_0, amounts.0, 0), (exact_1, amounts.1, 1)] {
+            let what = self.context(&format!("amount_{token}"));
+            exact.assert_close(amount as u128, AMOUNT_TOLERANCE, &what);
+            if deposit {
+                assert!(exact.error(amount as u128) >= 0, "{what}: deposit rounded down");
+            } else {
+                assert!(exact.error(amount as u128) <= 0, "{what}: withdrawal rounded up");
+            }
+        }
+    }
+
+    // runs a liquidity instruction like a transaction: on error the engine is left untouched,
+    // and the only error accepted is an overflow, for amounts a u64 cannot hold
+    fn try_modify_liquidity(
+        &mut self,
+        op: LiquidityOp,
+        index: usize,
+        liquidity: u128,
+    ) -> Option<LiquidityChange> {
+        let snapshot = self.engine.clone();
+        match self.engine.modify_liquidity(op, index, liquidity) {
+            Ok(change) => Some(change),
+            Err(error) => {
+                self.engine = snapshot;
+                assert_eq!(
+                    error,
+                    ClmmError::ArithmeticOverflow.into(),
+                    "{}",
+                    self.context(&format!("unexpected {op:?} error"))
+                );
+                None
+            }
+        }
+    }
+
+    fn deposit(&mut self, op: LiquidityOp, index: usize, liquidity: u128) -> bool {
+        let liquidity_before = self.engine.positions[index].liquidity;
+        // amounts are quoted at the price before the deposit, which a deposit does not move
+        let Some(change) = self.try_modify_liquidity(op, index, liquidity) else {
+            return false;
+        };
+        let position = &self.engine.positions[index];
+        assert_eq!(
+            position.liquidity,
+            liquidity_before + liquidity,
+            "{}",
+            self.context("position liquidity")
+        );
+        let (lower_tick, upper_tick) = (position.lower_tick, position.upper_tick);
+        let amounts = (change.amount_0, change.amount_1);
+        self.check_liquidity_amounts(lower_tick, upper_tick, liquidity, amounts, true);
+
+        self.engine.vault_0 += change.amount_0 as u128;
+        self.engine.vault_1 += change.amount_1 as u128;
+        true
+    }
+
+    fn withdraw(&mut self, op: LiquidityOp, index: usize, liquidity: u128) -> bool {
+        let liquidity_before = self.engine.positions[index].liquidity;
+        let Some(change) = self.try_modify_liquidity(op, index, liquidity) else {
+            return false;
+        };
+        let position = &self.engine.positions[index];
+        assert_eq!(
+            position.liquidity,
+            liquidity_before - liquidity,
+            "{}",
+            self.context("position liquidity")
+        );
+        let (lower_tick, upper_tick) = (position.lower_tick, position.upper_tick);
+        let amounts = (change.amount_0, change.amount_1);
+        self.check_liquidity_amounts(lower_tick, upper_tick, liquidity, amounts, false);
+
+        self.engine.vault_0 = self
+            .engine
+            .vault_0
+            .checked_sub(change.amount_0 as u128)
+            .unwrap_or_else(|| panic!("{}", self.context("vault_0 overdrawn by a withdrawal")));
+        self.engine.vault_1 = self
+            .engine
+            .vault_1
+            .checked_sub(change.amount_1 as u128)
+            .unwrap_or_else(|| panic!("{}", self.context("vault_1 overdrawn by a withdrawal")));
+        true
+    }
+
+    fn open_position(&mut self, lower: i64, upper: i64, liquidity: Amount) {
+        let (lower_tick, upper_tick) = self.range(lower, upper);
+        self.engine.positions.push(position(lower_tick, upper_tick));
+        let index = self.engine.positions.len() - 1;
+        let liquidity = liquidity.up_to(MAX_LIQUIDITY);
+        if !self.deposit(LiquidityOp::Open, index, liquidity) {
+            self.engine.positions.pop();
+        }
+    }
+
+    fn open_position_at(&self, position: Index) -> Option<usize> {
+        let count = self.engine.positions.len();
+        (count > 0).then(|| position.index(count))
+    }
+
+    fn increase_liquidity(&mut self, position: Index, liquidity: Amount) {
+        if let Some(index) = self.open_position_at(position) {
+            let liquidity = liquidity.up_to(MAX_LIQUIDITY);
+            self.deposit(LiquidityOp::Increase, index, liquidity);
+        }
+    }
+
+    fn decrease_liquidity(&mut self, position: Index, liquidity: Amount) {
+        if let Some(index) = self.open_position_at(position) {
+            let held = self.engine.positions[index].liquidity;
+            if held > 0 {
+                self.withdraw(LiquidityOp::Decrease, index, liquidity.up_to(held));
+            }
+        }
+    }
+
+    fn close_position(&mut self, position: Index) {
+        if let Some(index) = self.open_position_at(position) {
+            let liquidity = self.engine.positions[index].liquidity;
+            if self.withdraw(LiquidityOp::Close, index, liquidity) {
+                self.engine.positions.swap_remove(index);
+            }
+        }
+    }
+
+    fn swap(&mut self, a_to_b: bool, amount_in: Amount) {
+        let amount_in = amount_in.up_to(MAX_SWAP_AMOUNT) as u64;
+        let pool = &self.engine.pool;
+        let (sqrt_price_before, tick_spacing) = (pool.sqrt_price_x96, pool.tick_spacing);
+
+        // the reference step: toward the next spacing boundary, leaving the boundary the
+        // price sits on first when it falls, with the liquidity of that segment
+        let boundary_tick = pool.swap_boundary_tick(a_to_b);
+        let (target_tick, segment_tick) = if !a_to_b {
+            (boundary_tick, boundary_tick - tick_spacing)
+        } else if sqrt_price_before == tick_to_sqrt_price_x96(boundary_tick).unwrap() {
+            (boundary_tick - tick_spacing, boundary_tick - tick_spacing)
+        } else {
+            (boundary_tick, boundary_tick)
+        };
+        let liquidity = self.active_liquidity(segment_tick);
+        let sqrt_target = tick_to_sqrt_price_x96(target_tick).unwrap();
+
+        let (amount_in_used, amount_out) = match self.engine.swap(amount_in, a_to_b) {
+            Ok(result) => (result.amount_in, result.amount_out),
+            Err(error) if error == ClmmError::ArithmeticOverflow.into() => return,
+            Err(error) => {
+                assert_eq!(
+                    (error, liquidity),
+                    (ClmmError::InsufficientLiquidity.into(), 0),
+                    "{}",
+                    self.context("unexpected swap error")
+                );
+                return;
+            }
+        };
+        assert!(liquidity > 0, "{}", self.context("swapped without liquidity"));
+
+        let sqrt_price_after = self.engine.pool.sqrt_price_x96;
+        assert!(
+            if a_to_b {
+                (sqrt_target..=sqrt_price_before).contains(&sqrt_price_after)
+            } else {
+                (sqrt_price_before..=sqrt_target).contains(&sqrt_price_after)
+            },
+            "{}",
+            self.context("price left the step")
+        );
+
+        // the exact step for the same input: both reach the target or both stop short,
+        // unless the input is a rounding error away from what the target needs
+        let reference = swap_step(
+            sqrt_price_before,
+            sqrt_target,
+            liquidity,
+            amount_in as u128,
+            a_to_b,
+        );
+        let reached_target = sqrt_price_after == sqrt_target;
+        if reached_target != reference.reached_target {
+            reference.required_in.assert_close(
+                amount_in as u128,
+                AMOUNT_TOLERANCE,
+                &self.context("target reached by one side only"),
+            );
+        } else if !reached_target {
+            assert_eq!(amount_in_used, amount_in, "{}", self.context("partial step input"));
+        }
+
+        // the exact move to the price the engine settled on: a sqrt price unit can be worth
+        // more than a token unit, so the price is judged by what moving to it costs. the pool
+        // never moves the price further than the input pays for, nor pays out more than the
+        // move is worth
+        let moved = swap_step(sqrt_price_before, sqrt_price_after, liquidity, u128::MAX, a_to_b);
+        let what = self.context("input for the price moved");
+        moved.amount_in.assert_close(amount_in_used as u128, AMOUNT_TOLERANCE, &what);
+        assert!(moved.amount_in.error(amount_in_used as u128) >= 0, "{what}: undercharged");
+        let what = self.context("output for the price moved");
+        moved.amount_out.assert_close(amount_out as u128, AMOUNT_TOLERANCE, &what);
+        assert!(moved.amount_out.error(amount_out as u128) <= 0, "{what}: overpaid");
+
+        let (vault_in, vault_out) = if a_to_b {
+            (&mut self.engine.vault_0, &mut self.engine.vault_1)
+        } else {
+            (&mut self.engine.vault_1, &mut self.engine.vault_0)
+        };
+        *vault_in += amount_in_used as u128;
+        *vault_out = vault_out
+            .checked_sub(amount_out as u128)
+            .unwrap_or_else(|| panic!("step {}: vault overdrawn by a swap", self.step));
+    }
+
+    // invariants that hold between instructions
+    fn check_state(&self) {
+        let pool = &self.engine.pool;
+        assert_eq!(
+            pool.current_tick,
+            sqrt_price_x96_to_tick(pool.sqrt_price_x96).unwrap(),
+            "{}",
+            self.context("current_tick out of sync with the price")
+        );
+        assert_eq!(
+            pool.global_liquidity,
+            self.active_liquidity(pool.current_tick),
+            "{}",
+            self.context("global_liquidity")
+        );
+
+        // what every position is owed at the current price, withdrawals never pay out more
+        let (mut owed_0, mut owed_1) = (BigUint::ZERO, BigUint::ZERO);
+        for position in &self.engine.positions {
+            let (amount_0, amount_1) = amounts_for_liquidity(
+                pool.sqrt_price_x96,
+                tick_to_sqrt_price_x96(position.lower_tick).unwrap(),
+                tick_to_sqrt_price_x96(position.upper_tick).unwrap(),
+                position.liquidity,
+            );
+            owed_0 += amount_0.floor();
+            owed_1 += amount_1.floor();
+        }
+        assert!(
+            BigUint::from(self.engine.vault_0) >= owed_0
+                && BigUint::from(self.engine.vault_1) >= owed_1,
+            "{}",
+            self.context(&format!(
+                "vaults hold ({}, {}) but positions are owed ({owed_0}, {owed_1})",
+                self.engine.vault_0, self.engine.vault_1
+            ))
+        );
+    }
+
+    fn run(&mut self, ops: &[Op]) {
+        for (step, op) in ops.iter().enumerate() {
+            self.step = step;
+            match *op {
+                Op::Open { lower, upper, liquidity } => self.open_position(lower, upper, liquidity),
+                Op::Increase { position, liquidity } => {
+                    self.increase_liquidity(position, liquidity)
+                }
+                Op::Decrease { position, liquidity } => {
+                    self.decrease_liquidity(position, liquidity)
+                }
+                Op::Close { position } => self.close_position(position),
+                Op::Swap { a_to_b, amount_in } => self.swap(a_to_b, amount_in),
+            }
+            self.check_state();
+        }
+
+        // everyone leaves, and the vaults cover them. a withdrawal too large for the math is
+        // split in halves until it fits
+        while let Some(position) = self.engine.positions.last() {
+            self.step += 1;
+            let index = self.engine.positions.len() - 1;
+            let mut chunk = position.liquidity;
+            while chunk > 0 && !self.withdraw(LiquidityOp::Decrease, index, chunk) {
+                chunk /= 2;
+            }
+            if self.engine.positions[index].liquidity == 0 {
+                let closed = self.withdraw(LiquidityOp::Close, index, 0);
+                assert!(closed, "{}", self.context("closing an empty position"));
+                self.engine.positions.pop();
+            }
+            self.check_state();
+        }
+        assert_eq!(self.engine.pool.global_liquidity, 0, "{}", self.context("liquidity left"));
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    let value = std::env::var(name).ok()?;
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    Some(parsed.unwrap_or_else(|_| panic!("{name} must be a number")))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: env_u64("CLMM_FUZZ_CASES").map_or(DEFAULT_CASES, |cases| cases as u32),
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn pool_math_matches_reference_model(
+        tick_spacing in prop::sample::select(vec![1, 10, 60, 200]),
+        tick in -50_000..=50_000,
+        ops in prop::collection::vec(op(), OPS_PER_CASE),
+    ) {
+        Case::new(tick_spacing, tick).run(&ops);
+    }
+}
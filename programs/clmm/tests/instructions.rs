@@ -0,0 +1,1724 @@
+// instruction-level tests running the program's SBF build on litesvm, see `svm`
+//
+// every account is created the way a client would: the config through initialize_config,
+// mints and token accounts through the SPL Token program, then a pool at tick 0 through
+// initialize_pool and one position over [-600, 60] through open_position. token movements are
+// asserted on the token accounts the SPL Token program keeps
+
+#![cfg(feature = "test-sbf")]
+
+mod svm;
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, program_pack::Pack, system_instruction, sysvar},
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use clmm::{
+    errors::ClmmError,
+    states::{
+        GlobalConfig, LimitOrder, Pool, Position, PositionDelegate, TickArrayBitmap,
+        TickArrayState, POOL_CREATION_ADMIN_ONLY, POOL_CREATION_PERMISSIONLESS,
+        POOL_STATUS_EMERGENCY, POSITION_DELEGATE_DECREASE_LIQUIDITY,
+    },
+    utils::tick_to_sqrt_price_x96,
+};
+use litesvm::types::TransactionMetadata;
+use svm::{Svm, LAMPORTS_PER_SOL};
+
+const TICK_SPACING: i32 = 60;
+const LOWER_TICK: i32 = -600;
+const UPPER_TICK: i32 = 60;
+const LIQUIDITY: u128 = 1_000_000_000;
+const Q96: u128 = 1 << 96;
+const INITIAL_BALANCE: u64 = 1_000_000_000_000;
+const MAX_SLIPPAGE_BPS: u16 = 100;
+// the id of the positions the tests open
+const POSITION_ID: u64 = 0;
+const NO_FEE: (Pubkey, u64) = (Pubkey::new_from_array([0; 32]), 0);
+
+struct Fixture {
+    svm: Svm,
+    admin: Pubkey,
+    user: Pubkey,
+    global_config: Pubkey,
+    pool: Pubkey,
+    token_0: Pubkey,
+    token_1: Pubkey,
+    vault_0: Pubkey,
+    vault_1: Pubkey,
+    user_0: Pubkey,
+    user_1: Pubkey,
+    tick_array_bitmap: Pubkey,
+    lower_tick_array: Pubkey,
+    upper_tick_array: Pubkey,
+    position: Pubkey,
+    // drive the pool in native SOL mode, its WSOL side then has no user token account
+    native_sol: bool,
+}
+
+// the user side of an instruction's accounts
+struct UserAccounts {
+    user_0: Option<Pubkey>,
+    user_1: Option<Pubkey>,
+    native_mint: Option<Pubkey>,
+    native_sol_account: Option<Pubkey>,
+}
+
+fn tick_array_start(tick: i32) -> i32 {
+    TickArrayState::get_start_tick_idx(tick, TICK_SPACING)
+}
+
+fn tick_array_address(pool: &Pubkey, start_tick: i32) -> Pubkey {
+    TickArrayState::address(pool, start_tick)
+}
+
+fn pool_address(token_0: &Pubkey, token_1: &Pubkey, tick_spacing: i32) -> Pubkey {
+    let seeds = [b"pool".as_ref(), token_0.as_ref(), token_1.as_ref(), &tick_spacing.to_le_bytes()];
+    Pubkey::find_program_address(&seeds, &clmm::ID).0
+}
+
+fn bitmap_address(pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"tick_array_bitmap", pool.as_ref()], &clmm::ID).0
+}
+
+fn native_sol_address(signer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"native_sol", signer.as_ref()], &clmm::ID).0
+}
+
+fn position_address(
+    pool: &Pubkey,
+    owner: &Pubkey,
+    lower_tick: i32,
+    upper_tick: i32,
+    id: u64,
+) -> Pubkey {
+    let seeds = [
+        b"position".as_ref(),
+        pool.as_ref(),
+        owner.as_ref(),
+        &lower_tick.to_le_bytes(),
+        &upper_tick.to_le_bytes(),
+        &id.to_le_bytes(),
+    ];
+    Pubkey::find_program_address(&seeds, &clmm::ID).0
+}
+
+// the value the instruction returned, as the client decodes it
+fn return_value<T: AnchorDeserialize>(meta: &TransactionMetadata) -> T {
+    assert_eq!(meta.return_data.program_id, clmm::ID);
+    T::deserialize(&mut meta.return_data.data.as_slice()).unwrap()
+}
+
+// passes `a` and `b` in each other's place
+fn swap_accounts(ix: &mut Instruction, a: Pubkey, b: Pubkey) {
+    for meta in &mut ix.accounts {
+        if meta.pubkey == a {
+            meta.pubkey = b;
+        } else if meta.pubkey == b {
+            meta.pubkey = a;
+        }
+    }
+}
+
+fn expect_error<T>(
+    result: std::result::Result<T, ProgramError>,
+    error: impl Into<anchor_lang::error::Error>,
+) {
+    assert_eq!(result.err(), Some(ProgramError::from(error.into())));
+}
+
+fn create_account(svm: &mut Svm, payer: Pubkey, space: usize, owner: &Pubkey) -> Pubkey {
+    let key = svm.new_keypair();
+    let lamports = svm.minimum_balance(space);
+    let ix = system_instruction::create_account(&payer, &key, lamports, space as u64, owner);
+    svm.process(&ix).unwrap();
+    key
+}
+
+// a mint without extensions under either token program
+fn create_mint(svm: &mut Svm, authority: Pubkey, token_program: &Pubkey) -> Pubkey {
+    use spl_token_2022::instruction::initialize_mint2;
+    let mint = create_account(svm, authority, spl_token::state::Mint::LEN, token_program);
+    let ix = initialize_mint2(token_program, &mint, &authority, None, 6);
+    svm.process(&ix.unwrap()).unwrap();
+    mint
+}
+
+// the SPL Token wrapped SOL mint, which exists at genesis rather than being created
+fn create_native_mint(svm: &mut Svm) -> Pubkey {
+    let mint = spl_token::state::Mint {
+        decimals: 9,
+        is_initialized: true,
+        ..Default::default()
+    };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    mint.pack_into_slice(&mut data);
+    svm.set_data(spl_token::native_mint::ID, spl_token::ID, data);
+    spl_token::native_mint::ID
+}
+
+// opens a token account for `owner` and mints `amount` into it, `authority` holds the mint
+fn create_token_account(
+    svm: &mut Svm,
+    mint: Pubkey,
+    authority: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Pubkey {
+    use spl_token_2022::instruction::{initialize_account3, mint_to};
+    let token_program = svm.account(&mint).unwrap().owner;
+    let account = create_account(svm, authority, spl_token::state::Account::LEN, &token_program);
+    let ix = initialize_account3(&token_program, &account, &mint, &owner);
+    svm.process(&ix.unwrap()).unwrap();
+    if amount > 0 {
+        let ix = mint_to(
+            &token_program,
+            &mint,
+            &account,
+            &authority,
+            &[],
+            amount,
+        );
+        svm.process(&ix.unwrap()).unwrap();
+    }
+    account
+}
+
+fn initialize_config_ix(signer: Pubkey, program_data: Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        clmm::ID,
+        &clmm::instruction::InitializeConfig {}.data(),
+        clmm::accounts::InitializeConfig {
+            signer,
+            global_config: Pubkey::find_program_address(&[b"global_config"], &clmm::ID).0,
+            program: clmm::ID,
+            program_data,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+    )
+}
+
+struct PoolInit {
+    signer: Pubkey,
+    token_0: Pubkey,
+    token_1: Pubkey,
+    tick_spacing: i32,
+    sqrt_price_x96: u128,
+    treasury: Option<Pubkey>,
+    token_fee: Option<TokenFee>,
+}
+
+#[derive(Clone, Copy)]
+struct TokenFee {
+    mint: Pubkey,
+    payer_account: Pubkey,
+    treasury_account: Pubkey,
+    token_program: Pubkey,
+}
+
+// initialize_pool with fresh vault keypairs, returns the instruction and the vaults
+fn initialize_pool_ix(svm: &mut Svm, init: &PoolInit) -> (Instruction, Pubkey, Pubkey) {
+    let pool = pool_address(&init.token_0, &init.token_1, init.tick_spacing);
+    let (vault_0, vault_1) = (svm.new_keypair(), svm.new_keypair());
+    let ix = Instruction::new_with_bytes(
+        clmm::ID,
+        &clmm::instruction::InitializePool {
+            tick_spacing: init.tick_spacing,
+            initialize_sqrt_price: init.sqrt_price_x96,
+        }
+        .data(),
+        clmm::accounts::InitializePool {
+            signer: init.signer,
+            global_config: Pubkey::find_program_address(&[b"global_config"], &clmm::ID).0,
+            token_0_mint: init.token_0,
+            token_1_mint: init.token_1,
+            pool,
+            tick_array_bitmap: bitmap_address(&pool),
+            token_0_vault: vault_0,
+            token_1_vault: vault_1,
+            treasury: init.treasury,
+            creation_fee_mint: init.token_fee.map(|fee| fee.mint),
+            creation_fee_payer_account: init.token_fee.map(|fee| fee.payer_account),
+            creation_fee_treasury_account: init.token_fee.map(|fee| fee.treasury_account),
+            creation_fee_token_program: init.token_fee.map(|fee| fee.token_program),
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+    );
+    (ix, vault_0, vault_1)
+}
+
+fn initialize_pool(
+    svm: &mut Svm,
+    init: &PoolInit,
+) -> std::result::Result<TransactionMetadata, ProgramError> {
+    let (ix, _, _) = initialize_pool_ix(svm, init);
+    svm.process(&ix)
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Self::with_pool(false)
+    }
+
+    // a pool pairing a fresh mint with wrapped SOL, whose instructions run in native SOL mode
+    fn new_native() -> Self {
+        Self::with_pool(true)
+    }
+
+    fn with_pool(native_sol: bool) -> Self {
+        let mut svm = Svm::new();
+        let admin = svm.new_keypair();
+        let user = svm.new_keypair();
+        svm.airdrop(admin, 100 * LAMPORTS_PER_SOL);
+        svm.airdrop(user, 100 * LAMPORTS_PER_SOL);
+
+        let program_data = svm.deploy_clmm(admin);
+        svm.process(&initialize_config_ix(admin, program_data)).unwrap();
+        let global_config = Pubkey::find_program_address(&[b"global_config"], &clmm::ID).0;
+
+        let mint_a = create_mint(&mut svm, admin, &spl_token::ID);
+        let mint_b = if native_sol {
+            create_native_mint(&mut svm)
+        } else {
+            create_mint(&mut svm, admin, &spl_token::ID)
+        };
+        let (token_0, token_1) = if mint_a < mint_b { (mint_a, mint_b) } else { (mint_b, mint_a) };
+        // the user holds SOL instead of a WSOL token account
+        let mut user_account = |mint| {
+            if mint == spl_token::native_mint::ID {
+                Pubkey::default()
+            } else {
+                create_token_account(&mut svm, mint, admin, user, INITIAL_BALANCE)
+            }
+        };
+        let user_0 = user_account(token_0);
+        let user_1 = user_account(token_1);
+
+        let (ix, vault_0, vault_1) = initialize_pool_ix(&mut svm, &PoolInit {
+            signer: user,
+            token_0,
+            token_1,
+            tick_spacing: TICK_SPACING,
+            sqrt_price_x96: Q96,
+            treasury: None,
+            token_fee: None,
+        });
+        svm.process(&ix).unwrap();
+        let pool = pool_address(&token_0, &token_1, TICK_SPACING);
+
+        let mut f = Self {
+            svm,
+            admin,
+            user,
+            global_config,
+            pool,
+            token_0,
+            token_1,
+            vault_0,
+            vault_1,
+            user_0,
+            user_1,
+            tick_array_bitmap: bitmap_address(&pool),
+            lower_tick_array: tick_array_address(&pool, tick_array_start(LOWER_TICK)),
+            upper_tick_array: tick_array_address(&pool, tick_array_start(UPPER_TICK)),
+            position: position_address(&pool, &user, LOWER_TICK, UPPER_TICK, POSITION_ID),
+            native_sol,
+        };
+        f.svm.process(&f.open_position_ix(f.user, LIQUIDITY, Q96)).unwrap();
+        f
+    }
+
+    fn pool_state(&self) -> Pool {
+        self.svm.anchor_account(&self.pool)
+    }
+
+    fn config(&self) -> GlobalConfig {
+        self.svm.anchor_account(&self.global_config)
+    }
+
+    fn position_state(&self) -> Position {
+        self.svm.anchor_account(&self.position)
+    }
+
+    fn balances(&self) -> [u64; 4] {
+        [self.user_0, self.user_1, self.vault_0, self.vault_1]
+            .map(|account| self.svm.token_balance(&account))
+    }
+
+    // in native SOL mode the WSOL side moves through the signer's temporary account instead
+    fn user_accounts(&self, signer: Pubkey, native_sol: bool) -> UserAccounts {
+        if !native_sol {
+            return UserAccounts {
+                user_0: Some(self.user_0),
+                user_1: Some(self.user_1),
+                native_mint: None,
+                native_sol_account: None,
+            };
+        }
+        let native_0 = self.token_0 == spl_token::native_mint::ID;
+        UserAccounts {
+            user_0: (!native_0).then_some(self.user_0),
+            user_1: native_0.then_some(self.user_1),
+            native_mint: Some(if native_0 { self.token_0 } else { self.token_1 }),
+            native_sol_account: Some(native_sol_address(&signer)),
+        }
+    }
+
+    fn open_position_ix(
+        &self,
+        signer: Pubkey,
+        liquidity_amount: u128,
+        sqrt_price_x96: u128,
+    ) -> Instruction {
+        let users = self.user_accounts(signer, self.native_sol);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::OpenPosition {
+                upper_tick: UPPER_TICK,
+                lower_tick: LOWER_TICK,
+                tick_array_lower_start_index: tick_array_start(LOWER_TICK),
+                tick_array_upper_start_index: tick_array_start(UPPER_TICK),
+                position_id: POSITION_ID,
+                liquidity_amount,
+                native_sol: self.native_sol,
+                sqrt_price_x96,
+                max_slippage_bps: MAX_SLIPPAGE_BPS,
+            }
+            .data(),
+            clmm::accounts::OpenPosition {
+                signer,
+                pool: self.pool,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                lower_tick_array: self.lower_tick_array,
+                upper_tick_array: self.upper_tick_array,
+                tick_array_bitmap: self.tick_array_bitmap,
+                position: position_address(
+                    &self.pool,
+                    &signer,
+                    LOWER_TICK,
+                    UPPER_TICK,
+                    POSITION_ID,
+                ),
+                user_0: users.user_0,
+                user_1: users.user_1,
+                native_mint: users.native_mint,
+                native_sol_account: users.native_sol_account,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn swap_ix(&self, amount_in: u64, a_to_b: bool, min_amount_out: u64) -> Instruction {
+        let start = tick_array_start(self.pool_state().swap_boundary_tick(a_to_b));
+        let tick_array = tick_array_address(&self.pool, start);
+        self.swap_ix_with(tick_array, amount_in, a_to_b, min_amount_out, false)
+    }
+
+    fn swap_ix_with(
+        &self,
+        tick_array: Pubkey,
+        amount_in: u64,
+        a_to_b: bool,
+        min_amount_out: u64,
+        native_sol: bool,
+    ) -> Instruction {
+        let users = self.user_accounts(self.user, native_sol);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::Swap {
+                amount_in,
+                a_to_b,
+                min_amount_out,
+                native_sol,
+            }
+            .data(),
+            clmm::accounts::Swap {
+                signer: self.user,
+                pool: self.pool,
+                tick_array,
+                user_0: users.user_0,
+                user_1: users.user_1,
+                native_mint: users.native_mint,
+                native_sol_account: users.native_sol_account,
+                token_vault_0: self.vault_0,
+                token_vault_1: self.vault_1,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn quote_swap_ix(&self, tick_array: Pubkey, amount_in: u64, a_to_b: bool) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::QuoteSwap { amount_in, a_to_b }.data(),
+            clmm::accounts::QuoteSwap {
+                pool: self.pool,
+                tick_array,
+                token_0: self.token_0,
+                token_1: self.token_1,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn increase_liquidity_ix(&self, liquidity_amount: u128, lower_tick: i32) -> Instruction {
+        self.increase_liquidity_ix_with(self.user, liquidity_amount, lower_tick, None)
+    }
+
+    fn increase_liquidity_ix_with(
+        &self,
+        signer: Pubkey,
+        liquidity_amount: u128,
+        lower_tick: i32,
+        position_delegate: Option<Pubkey>,
+    ) -> Instruction {
+        let users = self.user_accounts(signer, false);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::IncreaseLiquidity {
+                liquidity_amount,
+                upper_tick: UPPER_TICK,
+                lower_tick,
+                tick_array_lower_start_index: tick_array_start(LOWER_TICK),
+                tick_array_upper_start_index: tick_array_start(UPPER_TICK),
+                native_sol: false,
+            }
+            .data(),
+            clmm::accounts::IncreaseLiquidity {
+                signer,
+                pool: self.pool,
+                lower_tick_array: self.lower_tick_array,
+                upper_tick_array: self.upper_tick_array,
+                position: self.position,
+                position_delegate,
+                user_0: users.user_0,
+                user_1: users.user_1,
+                native_mint: users.native_mint,
+                native_sol_account: users.native_sol_account,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn decrease_liquidity_ix(&self, signer: Pubkey, liquidity_amount: u128) -> Instruction {
+        self.decrease_liquidity_ix_with(signer, liquidity_amount, None, (self.user_0, self.user_1))
+    }
+
+    fn decrease_liquidity_ix_with(
+        &self,
+        signer: Pubkey,
+        liquidity_amount: u128,
+        position_delegate: Option<Pubkey>,
+        (user_0, user_1): (Pubkey, Pubkey),
+    ) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::DecreaseLiquidity {
+                liquidity_amount,
+                upper_tick: UPPER_TICK,
+                lower_tick: LOWER_TICK,
+                tick_array_lower_start_index: tick_array_start(LOWER_TICK),
+                tick_array_upper_start_index: tick_array_start(UPPER_TICK),
+                native_sol: false,
+            }
+            .data(),
+            clmm::accounts::DecreaseLiquidity {
+                signer,
+                pool: self.pool,
+                lower_tick_array: self.lower_tick_array,
+                upper_tick_array: self.upper_tick_array,
+                tick_array_bitmap: self.tick_array_bitmap,
+                position: self.position,
+                position_delegate,
+                user_0: Some(user_0),
+                user_1: Some(user_1),
+                native_mint: None,
+                native_sol_account: None,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn close_position_ix(&self) -> Instruction {
+        let users = self.user_accounts(self.user, self.native_sol);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::ClosePosition {
+                upper_tick: UPPER_TICK,
+                lower_tick: LOWER_TICK,
+                _tick_array_lower_start_index: tick_array_start(LOWER_TICK),
+                _tick_array_upper_start_index: tick_array_start(UPPER_TICK),
+                native_sol: self.native_sol,
+            }
+            .data(),
+            clmm::accounts::ClosePosition {
+                signer: self.user,
+                pool: self.pool,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                lower_tick_array: self.lower_tick_array,
+                upper_tick_array: self.upper_tick_array,
+                tick_array_bitmap: self.tick_array_bitmap,
+                position: self.position,
+                user_0: users.user_0,
+                user_1: users.user_1,
+                native_mint: users.native_mint,
+                native_sol_account: users.native_sol_account,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn split_position_ix(
+        &self,
+        signer: Pubkey,
+        recipient: Pubkey,
+        new_position_id: u64,
+        liquidity_amount: u128,
+    ) -> Instruction {
+        let position = |owner, id| position_address(&self.pool, owner, LOWER_TICK, UPPER_TICK, id);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::SplitPosition {
+                upper_tick: UPPER_TICK,
+                lower_tick: LOWER_TICK,
+                recipient,
+                new_position_id,
+                liquidity_amount,
+            }
+            .data(),
+            clmm::accounts::SplitPosition {
+                signer,
+                pool: self.pool,
+                position: position(&signer, POSITION_ID),
+                new_position: position(&recipient, new_position_id),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn merge_positions_ix(&self, signer: Pubkey, id: u64, destination: Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::MergePositions {
+                _upper_tick: UPPER_TICK,
+                _lower_tick: LOWER_TICK,
+            }
+            .data(),
+            clmm::accounts::MergePositions {
+                signer,
+                pool: self.pool,
+                position: position_address(&self.pool, &signer, LOWER_TICK, UPPER_TICK, id),
+                destination,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn position_delegate_address(&self, operator: &Pubkey) -> Pubkey {
+        let seeds = [
+            b"position_delegate".as_ref(),
+            self.pool.as_ref(),
+            self.user.as_ref(),
+            operator.as_ref(),
+        ];
+        Pubkey::find_program_address(&seeds, &clmm::ID).0
+    }
+
+    fn approve_position_delegate_ix(&self, operator: Pubkey, permissions: u8) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::ApprovePositionDelegate {
+                operator,
+                permissions,
+            }
+            .data(),
+            clmm::accounts::ApprovePositionDelegate {
+                owner: self.user,
+                pool: self.pool,
+                position_delegate: self.position_delegate_address(&operator),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn revoke_position_delegate_ix(&self, operator: Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::RevokePositionDelegate { operator }.data(),
+            clmm::accounts::RevokePositionDelegate {
+                owner: self.user,
+                pool: self.pool,
+                position_delegate: self.position_delegate_address(&operator),
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn limit_order_address(&self, tick: i32, zero_for_one: bool) -> Pubkey {
+        let seeds = [
+            b"limit_order".as_ref(),
+            self.pool.as_ref(),
+            self.user.as_ref(),
+            &tick.to_le_bytes(),
+            &[zero_for_one as u8],
+        ];
+        Pubkey::find_program_address(&seeds, &clmm::ID).0
+    }
+
+    fn open_limit_order_ix(
+        &self,
+        tick: i32,
+        zero_for_one: bool,
+        liquidity_amount: u128,
+    ) -> Instruction {
+        let tick_array_start_index = tick_array_start(tick);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::OpenLimitOrder {
+                tick,
+                zero_for_one,
+                tick_array_start_index,
+                liquidity_amount,
+            }
+            .data(),
+            clmm::accounts::OpenLimitOrder {
+                signer: self.user,
+                pool: self.pool,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                tick_array: tick_array_address(&self.pool, tick_array_start_index),
+                tick_array_bitmap: self.tick_array_bitmap,
+                limit_order: self.limit_order_address(tick, zero_for_one),
+                user_0: self.user_0,
+                user_1: self.user_1,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn cancel_limit_order_ix(&self, tick: i32, zero_for_one: bool) -> Instruction {
+        let tick_array_start_index = tick_array_start(tick);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::CancelLimitOrder {
+                tick,
+                zero_for_one,
+                tick_array_start_index,
+            }
+            .data(),
+            clmm::accounts::CancelLimitOrder {
+                signer: self.user,
+                pool: self.pool,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                tick_array: tick_array_address(&self.pool, tick_array_start_index),
+                tick_array_bitmap: self.tick_array_bitmap,
+                limit_order: self.limit_order_address(tick, zero_for_one),
+                user_0: self.user_0,
+                user_1: self.user_1,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    // settles the user's order selling token_0 at `tick`, paid out in token_1
+    fn settle_limit_order_ix(&self, signer: Pubkey, tick: i32) -> Instruction {
+        let tick_array_start_index = tick_array_start(tick + TICK_SPACING);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::SettleLimitOrder {
+                tick_array_start_index,
+            }
+            .data(),
+            clmm::accounts::SettleLimitOrder {
+                signer,
+                pool: self.pool,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                tick_array: tick_array_address(&self.pool, tick_array_start_index),
+                tick_array_bitmap: self.tick_array_bitmap,
+                limit_order: self.limit_order_address(tick, true),
+                owner: self.user,
+                owner_token_account: self.user_1,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn reposition_ix(
+        &self,
+        (new_lower_tick, new_upper_tick): (i32, i32),
+        (swap_amount_in, min_amount_out): (u64, u64),
+        liquidity_amount: u128,
+        sqrt_price_x96: u128,
+    ) -> Instruction {
+        let new_lower_start = tick_array_start(new_lower_tick);
+        let new_upper_start = tick_array_start(new_upper_tick);
+        let swap_start = tick_array_start(self.pool_state().swap_boundary_tick(true));
+        let users = self.user_accounts(self.user, false);
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::Reposition {
+                _upper_tick: UPPER_TICK,
+                _lower_tick: LOWER_TICK,
+                new_upper_tick,
+                new_lower_tick,
+                _tick_array_lower_start_index: tick_array_start(LOWER_TICK),
+                _tick_array_upper_start_index: tick_array_start(UPPER_TICK),
+                new_tick_array_lower_start_index: new_lower_start,
+                new_tick_array_upper_start_index: new_upper_start,
+                swap_amount_in,
+                a_to_b: true,
+                min_amount_out,
+                liquidity_amount,
+                sqrt_price_x96,
+                max_slippage_bps: MAX_SLIPPAGE_BPS,
+                native_sol: false,
+            }
+            .data(),
+            clmm::accounts::Reposition {
+                signer: self.user,
+                pool: self.pool,
+                token_0: self.token_0,
+                token_1: self.token_1,
+                lower_tick_array: self.lower_tick_array,
+                upper_tick_array: self.upper_tick_array,
+                new_lower_tick_array: tick_array_address(&self.pool, new_lower_start),
+                new_upper_tick_array: tick_array_address(&self.pool, new_upper_start),
+                swap_tick_array: tick_array_address(&self.pool, swap_start),
+                tick_array_bitmap: self.tick_array_bitmap,
+                owner: self.user,
+                position: self.position,
+                position_delegate: None,
+                new_position: position_address(
+                    &self.pool,
+                    &self.user,
+                    new_lower_tick,
+                    new_upper_tick,
+                    POSITION_ID,
+                ),
+                user_0: users.user_0,
+                user_1: users.user_1,
+                native_mint: users.native_mint,
+                native_sol_account: users.native_sol_account,
+                pool_vault_0: self.vault_0,
+                pool_vault_1: self.vault_1,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn close_tick_array_ix(
+        &self,
+        tick_array: Pubkey,
+        start_tick: i32,
+        payer: Pubkey,
+    ) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::CloseTickArray {
+                tick_array_start_index: start_tick,
+            }
+            .data(),
+            clmm::accounts::CloseTickArray {
+                signer: self.user,
+                pool: self.pool,
+                tick_array,
+                tick_array_bitmap: self.tick_array_bitmap,
+                payer,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn set_pool_creation_config_ix(
+        &self,
+        admin: Pubkey,
+        pool_creation_mode: u8,
+        treasury: Pubkey,
+        (creation_fee_mint, creation_fee_amount): (Pubkey, u64),
+    ) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::SetPoolCreationConfig {
+                pool_creation_mode,
+                treasury,
+                creation_fee_mint,
+                creation_fee_amount,
+            }
+            .data(),
+            clmm::accounts::SetPoolCreationConfig {
+                admin,
+                global_config: self.global_config,
+            }
+            .to_account_metas(None),
+        )
+    }
+
+    fn set_pool_status_ix(&self, admin: Pubkey, status: u8) -> Instruction {
+        Instruction::new_with_bytes(
+            clmm::ID,
+            &clmm::instruction::SetPoolStatus { status }.data(),
+            clmm::accounts::SetPoolStatus {
+                admin,
+                global_config: self.global_config,
+                pool: self.pool,
+            }
+            .to_account_metas(None),
+        )
+    }
+}
+
+fn propose_admin_ix(f: &Fixture, admin: Pubkey, new_admin: Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        clmm::ID,
+        &clmm::instruction::ProposeAdmin { new_admin }.data(),
+        clmm::accounts::ProposeAdmin {
+            admin,
+            global_config: f.global_config,
+        }
+        .to_account_metas(None),
+    )
+}
+
+fn accept_admin_ix(f: &Fixture, pending_admin: Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        clmm::ID,
+        &clmm::instruction::AcceptAdmin {}.data(),
+        clmm::accounts::AcceptAdmin {
+            pending_admin,
+            global_config: f.global_config,
+        }
+        .to_account_metas(None),
+    )
+}
+
+#[test]
+fn initialize_config_is_upgrade_authority_only() {
+    let mut svm = Svm::new();
+    let (authority, other) = (svm.new_keypair(), svm.new_keypair());
+    svm.airdrop(authority, LAMPORTS_PER_SOL);
+    svm.airdrop(other, LAMPORTS_PER_SOL);
+    let program_data = svm.deploy_clmm(authority);
+
+    let result = svm.process(&initialize_config_ix(other, program_data));
+    expect_error(result, ClmmError::Unauthorized);
+
+    svm.process(&initialize_config_ix(authority, program_data)).unwrap();
+    let global_config = Pubkey::find_program_address(&[b"global_config"], &clmm::ID).0;
+    let config: GlobalConfig = svm.anchor_account(&global_config);
+    assert_eq!(config.admin, authority);
+    assert_eq!(config.treasury, authority);
+    assert_eq!(config.pool_creation_mode, POOL_CREATION_PERMISSIONLESS);
+
+    // the config is a singleton
+    assert!(svm.process(&initialize_config_ix(authority, program_data)).is_err());
+}
+
+#[test]
+fn initialize_pool_creates_the_vaults() {
+    let f = Fixture::new();
+
+    let pool = f.pool_state();
+    assert_eq!((pool.token_0, pool.token_1), (f.token_0, f.token_1));
+    assert_eq!((pool.token_vault_0, pool.token_vault_1), (f.vault_0, f.vault_1));
+    assert_eq!((pool.sqrt_price_x96, pool.current_tick), (Q96, 0));
+    for (vault, mint) in [(f.vault_0, f.token_0), (f.vault_1, f.token_1)] {
+        let vault: spl_token::state::Account = f.svm.packed_account(&vault);
+        assert_eq!((vault.mint, vault.owner), (mint, f.pool));
+    }
+    let bitmap: TickArrayBitmap = f.svm.zero_copy_account(&f.tick_array_bitmap);
+    assert_eq!(bitmap.pool, f.pool);
+}
+
+#[test]
+fn initialize_pool_errors_and_creation_fee() {
+    let mut f = Fixture::new();
+    let treasury = Pubkey::new_unique();
+    let init = PoolInit {
+        signer: f.user,
+        token_0: f.token_0,
+        token_1: f.token_1,
+        tick_spacing: 2 * TICK_SPACING,
+        sqrt_price_x96: Q96,
+        treasury: None,
+        token_fee: None,
+    };
+
+    let reversed = PoolInit {
+        token_0: f.token_1,
+        token_1: f.token_0,
+        ..init
+    };
+    expect_error(initialize_pool(&mut f.svm, &reversed), ClmmError::InvalidTokenOrder);
+
+    let ix = f.set_pool_creation_config_ix(f.admin, POOL_CREATION_ADMIN_ONLY, treasury, NO_FEE);
+    f.svm.process(&ix).unwrap();
+    let result = initialize_pool(&mut f.svm, &init);
+    expect_error(result, ClmmError::PoolCreationNotAllowed);
+
+    let fee = (Pubkey::default(), LAMPORTS_PER_SOL);
+    let ix = f.set_pool_creation_config_ix(f.admin, POOL_CREATION_PERMISSIONLESS, treasury, fee);
+    f.svm.process(&ix).unwrap();
+    let result = initialize_pool(&mut f.svm, &init);
+    expect_error(result, ClmmError::InvalidCreationFeeAccount);
+    let wrong_treasury = PoolInit {
+        treasury: Some(f.admin),
+        ..init
+    };
+    let result = initialize_pool(&mut f.svm, &wrong_treasury);
+    expect_error(result, ClmmError::InvalidCreationFeeAccount);
+
+    let user_lamports = f.svm.lamports(&f.user);
+    let with_treasury = PoolInit {
+        treasury: Some(treasury),
+        ..init
+    };
+    initialize_pool(&mut f.svm, &with_treasury).unwrap();
+    assert_eq!(f.svm.lamports(&treasury), LAMPORTS_PER_SOL);
+    assert!(f.svm.lamports(&f.user) < user_lamports - LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn initialize_pool_charges_a_token_fee() {
+    let mut f = Fixture::new();
+    let treasury = Pubkey::new_unique();
+    const FEE: u64 = 5_000_000;
+
+    // the fee mint lives under Token-2022 while the pool's mints use SPL Token
+    let fee_mint = create_mint(&mut f.svm, f.admin, &spl_token_2022::ID);
+    let payer_account = create_token_account(&mut f.svm, fee_mint, f.admin, f.user, FEE);
+    let treasury_account = create_token_account(&mut f.svm, fee_mint, f.admin, treasury, 0);
+    let other_mint = create_mint(&mut f.svm, f.admin, &spl_token_2022::ID);
+    let other_mint_account = create_token_account(&mut f.svm, other_mint, f.admin, f.user, FEE);
+    let admin_account = create_token_account(&mut f.svm, fee_mint, f.admin, f.admin, FEE);
+    let ix = f.set_pool_creation_config_ix(
+        f.admin,
+        POOL_CREATION_PERMISSIONLESS,
+        treasury,
+        (fee_mint, FEE),
+    );
+    f.svm.process(&ix).unwrap();
+
+    let token_fee = TokenFee {
+        mint: fee_mint,
+        payer_account,
+        treasury_account,
+        token_program: spl_token_2022::ID,
+    };
+    let init = |token_fee: Option<TokenFee>| PoolInit {
+        signer: f.user,
+        token_0: f.token_0,
+        token_1: f.token_1,
+        tick_spacing: 2 * TICK_SPACING,
+        sqrt_price_x96: Q96,
+        treasury: None,
+        token_fee,
+    };
+
+    let result = initialize_pool(&mut f.svm, &init(None));
+    expect_error(result, ClmmError::InvalidCreationFeeAccount);
+    // the pool's token program cannot move the fee mint
+    let wrong_program = TokenFee {
+        token_program: spl_token::ID,
+        ..token_fee
+    };
+    let result = initialize_pool(&mut f.svm, &init(Some(wrong_program)));
+    expect_error(result, ErrorCode::ConstraintMintTokenProgram);
+    // the fee is paid from the signer's own account of the fee mint
+    let not_the_signers = TokenFee {
+        payer_account: admin_account,
+        ..token_fee
+    };
+    let result = initialize_pool(&mut f.svm, &init(Some(not_the_signers)));
+    expect_error(result, ErrorCode::ConstraintTokenOwner);
+    let other_mint = TokenFee {
+        payer_account: other_mint_account,
+        ..token_fee
+    };
+    let result = initialize_pool(&mut f.svm, &init(Some(other_mint)));
+    expect_error(result, ErrorCode::ConstraintTokenMint);
+    let to_the_payer = TokenFee {
+        treasury_account: admin_account,
+        ..token_fee
+    };
+    let result = initialize_pool(&mut f.svm, &init(Some(to_the_payer)));
+    expect_error(result, ClmmError::InvalidCreationFeeAccount);
+
+    initialize_pool(&mut f.svm, &init(Some(token_fee))).unwrap();
+    assert_eq!(f.svm.token_balance(&payer_account), 0);
+    assert_eq!(f.svm.token_balance(&treasury_account), FEE);
+    assert_eq!(f.svm.token_balance(&admin_account), FEE);
+}
+
+#[test]
+fn open_position_pays_the_vaults() {
+    let mut f = Fixture::new();
+
+    // the fixture's open_position took both tokens into the vaults, the range holds the price
+    let (amount_0, amount_1) = (f.svm.token_balance(&f.vault_0), f.svm.token_balance(&f.vault_1));
+    assert!(amount_0 > 0 && amount_1 > 0);
+    assert_eq!(
+        f.balances(),
+        [INITIAL_BALANCE - amount_0, INITIAL_BALANCE - amount_1, amount_0, amount_1]
+    );
+    let position = f.position_state();
+    assert_eq!((position.liquidity, position.owner), (LIQUIDITY, f.user));
+    assert_eq!((position.lower_tick, position.upper_tick), (LOWER_TICK, UPPER_TICK));
+    assert_eq!(f.pool_state().global_liquidity, LIQUIDITY);
+    let bitmap: TickArrayBitmap = f.svm.zero_copy_account(&f.tick_array_bitmap);
+    assert!(bitmap.is_initialized(tick_array_start(LOWER_TICK), TICK_SPACING).unwrap());
+    assert!(bitmap.is_initialized(tick_array_start(UPPER_TICK), TICK_SPACING).unwrap());
+
+    let other = f.svm.new_keypair();
+    f.svm.airdrop(other, LAMPORTS_PER_SOL);
+    let far_price = Q96 + Q96 / 50;
+    let result = f.svm.process(&f.open_position_ix(other, LIQUIDITY, far_price));
+    expect_error(result, ClmmError::PriceSlippageExceeded);
+    // the position is one per owner and range
+    assert!(f.svm.process(&f.open_position_ix(f.user, LIQUIDITY, Q96)).is_err());
+}
+
+#[test]
+fn admin_transfer_takes_two_steps() {
+    let mut f = Fixture::new();
+    let new_admin = f.svm.new_keypair();
+
+    let result = f.svm.process(&propose_admin_ix(&f, f.user, new_admin));
+    expect_error(result, ClmmError::Unauthorized);
+
+    f.svm.process(&propose_admin_ix(&f, f.admin, new_admin)).unwrap();
+    assert_eq!(f.config().admin, f.admin);
+    assert_eq!(f.config().pending_admin, new_admin);
+
+    let result = f.svm.process(&accept_admin_ix(&f, f.user));
+    expect_error(result, ClmmError::Unauthorized);
+
+    f.svm.process(&accept_admin_ix(&f, new_admin)).unwrap();
+    assert_eq!(f.config().admin, new_admin);
+    assert_eq!(f.config().pending_admin, Pubkey::default());
+}
+
+#[test]
+fn admin_lists_are_admin_only() {
+    let mut f = Fixture::new();
+    let key = Pubkey::new_unique();
+
+    for allowed in [true, false] {
+        for signer in [f.user, f.admin] {
+            let instructions = [
+                Instruction::new_with_bytes(
+                    clmm::ID,
+                    &clmm::instruction::SetTransferHookProgram {
+                        program_id: key,
+                        allowed,
+                    }
+                    .data(),
+                    clmm::accounts::SetTransferHookProgram {
+                        admin: signer,
+                        global_config: f.global_config,
+                    }
+                    .to_account_metas(None),
+                ),
+                Instruction::new_with_bytes(
+                    clmm::ID,
+                    &clmm::instruction::SetMintOverride { mint: key, allowed }.data(),
+                    clmm::accounts::SetMintOverride {
+                        admin: signer,
+                        global_config: f.global_config,
+                    }
+                    .to_account_metas(None),
+                ),
+                Instruction::new_with_bytes(
+                    clmm::ID,
+                    &clmm::instruction::SetPoolCreator {
+                        creator: key,
+                        allowed,
+                    }
+                    .data(),
+                    clmm::accounts::SetPoolCreator {
+                        admin: signer,
+                        global_config: f.global_config,
+                    }
+                    .to_account_metas(None),
+                ),
+            ];
+            for instruction in &instructions {
+                let result = f.svm.process(instruction);
+                if signer == f.admin {
+                    result.unwrap();
+                } else {
+                    expect_error(result, ClmmError::Unauthorized);
+                }
+            }
+        }
+
+        let config = f.config();
+        assert_eq!(config.is_transfer_hook_allowed(&key), allowed);
+        assert_eq!(config.is_mint_overridden(&key), allowed);
+        assert_eq!(config.pool_creators.contains(&key), allowed);
+    }
+}
+
+#[test]
+fn set_pool_creation_config_validates_mode() {
+    let mut f = Fixture::new();
+    let treasury = Pubkey::new_unique();
+
+    let ix = f.set_pool_creation_config_ix(f.user, POOL_CREATION_ADMIN_ONLY, treasury, NO_FEE);
+    expect_error(f.svm.process(&ix), ClmmError::Unauthorized);
+    let ix = f.set_pool_creation_config_ix(f.admin, 3, treasury, NO_FEE);
+    expect_error(f.svm.process(&ix), ClmmError::InvalidPoolCreationMode);
+
+    let fee = (Pubkey::default(), LAMPORTS_PER_SOL);
+    let ix = f.set_pool_creation_config_ix(f.admin, POOL_CREATION_ADMIN_ONLY, treasury, fee);
+    f.svm.process(&ix).unwrap();
+    let config = f.config();
+    assert_eq!(config.pool_creation_mode, POOL_CREATION_ADMIN_ONLY);
+    assert_eq!(config.treasury, treasury);
+    assert_eq!(config.creation_fee_amount, LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn set_pool_status_pauses_swaps() {
+    let mut f = Fixture::new();
+
+    let result = f.svm.process(&f.set_pool_status_ix(f.user, POOL_STATUS_EMERGENCY));
+    expect_error(result, ClmmError::Unauthorized);
+    let result = f.svm.process(&f.set_pool_status_ix(f.admin, 1 << 4));
+    expect_error(result, ClmmError::InvalidPoolStatus);
+
+    f.svm.process(&f.set_pool_status_ix(f.admin, POOL_STATUS_EMERGENCY)).unwrap();
+    assert_eq!(f.pool_state().status, POOL_STATUS_EMERGENCY);
+    let result = f.svm.process(&f.swap_ix(1_000, true, 0));
+    expect_error(result, ClmmError::PoolOperationDisabled);
+    let result = f.svm.process(&f.increase_liquidity_ix(1_000, LOWER_TICK));
+    expect_error(result, ClmmError::PoolOperationDisabled);
+
+    // withdrawals stay open in emergency mode
+    f.svm.process(&f.decrease_liquidity_ix(f.user, 1_000)).unwrap();
+
+    f.svm.process(&f.set_pool_status_ix(f.admin, 0)).unwrap();
+    f.svm.process(&f.swap_ix(1_000, true, 0)).unwrap();
+}
+
+#[test]
+fn swap_moves_the_price_and_the_tokens() {
+    let mut f = Fixture::new();
+    let [user_0, user_1, vault_0, vault_1] = f.balances();
+
+    f.svm.process(&f.swap_ix(1_000, true, 990)).unwrap();
+    let pool = f.pool_state();
+    assert!(pool.sqrt_price_x96 < Q96);
+    assert_eq!(pool.current_tick, -1);
+    let amount_out = f.svm.token_balance(&f.user_1) - user_1;
+    assert!(amount_out >= 990);
+    assert_eq!(
+        f.balances(),
+        [user_0 - 1_000, user_1 + amount_out, vault_0 + 1_000, vault_1 - amount_out]
+    );
+
+    // a swap reads the array holding the boundary it moves toward, tick 0 when rising from -1
+    let result = f.svm.process(&f.swap_ix_with(f.lower_tick_array, 1_000, false, 990, false));
+    expect_error(result, ClmmError::InvalidTickArrayAccount);
+    f.svm.process(&f.swap_ix(1_000, false, 990)).unwrap();
+    assert!(f.pool_state().sqrt_price_x96 > pool.sqrt_price_x96);
+}
+
+#[test]
+fn swap_crosses_position_bounds() {
+    let mut f = Fixture::new();
+
+    // rising to the position's upper tick leaves its range
+    f.svm.process(&f.swap_ix(u32::MAX as u64, false, 0)).unwrap();
+    let pool = f.pool_state();
+    assert_eq!(pool.sqrt_price_x96, tick_to_sqrt_price_x96(UPPER_TICK).unwrap());
+    assert_eq!(pool.current_tick, UPPER_TICK);
+    assert_eq!(pool.global_liquidity, 0);
+    // the swap bought out the position's token_0, all but the rounding dust
+    assert!(f.svm.token_balance(&f.vault_0) <= 1);
+
+    let result = f.svm.process(&f.swap_ix(1_000, false, 0));
+    expect_error(result, ClmmError::InsufficientLiquidity);
+
+    // falling from the bound crosses back in before moving
+    f.svm.process(&f.swap_ix(1_000, true, 0)).unwrap();
+    let pool = f.pool_state();
+    assert_eq!(pool.global_liquidity, LIQUIDITY);
+    assert_eq!(pool.current_tick, UPPER_TICK - 1);
+}
+
+#[test]
+fn swap_errors() {
+    let mut f = Fixture::new();
+    let pool_before = f.svm.account(&f.pool).unwrap().data;
+    let balances_before = f.balances();
+
+    let result = f.svm.process(&f.swap_ix(1_000, true, u64::MAX));
+    expect_error(result, ClmmError::SlippageExceeded);
+    let result = f.svm.process(&f.swap_ix(0, true, 0));
+    expect_error(result, ClmmError::ZeroAmount);
+    let result = f.svm.process(&f.swap_ix_with(f.lower_tick_array, 1_000, true, 0, false));
+    expect_error(result, ClmmError::InvalidTickArrayAccount);
+    let result = f.svm.process(&f.swap_ix_with(f.upper_tick_array, 1_000, true, 0, true));
+    expect_error(result, ClmmError::NativeSolUnsupported);
+
+    // failed swaps leave the pool and the tokens untouched
+    assert_eq!(f.svm.account(&f.pool).unwrap().data, pool_before);
+    assert_eq!(f.balances(), balances_before);
+
+    // a pool created without a price cannot be swapped against before its first position
+    let (ix, vault_0, vault_1) = initialize_pool_ix(&mut f.svm, &PoolInit {
+        signer: f.user,
+        token_0: f.token_0,
+        token_1: f.token_1,
+        tick_spacing: 2 * TICK_SPACING,
+        sqrt_price_x96: 0,
+        treasury: None,
+        token_fee: None,
+    });
+    f.svm.process(&ix).unwrap();
+    let mut unpriced = Fixture {
+        pool: pool_address(&f.token_0, &f.token_1, 2 * TICK_SPACING),
+        vault_0,
+        vault_1,
+        ..f
+    };
+    let tick_array = tick_array_address(&unpriced.pool, 0);
+    let result = unpriced.svm.process(&unpriced.swap_ix_with(tick_array, 1_000, true, 0, false));
+    expect_error(result, ClmmError::PoolPriceUninitialized);
+}
+
+#[test]
+fn native_sol_moves_through_a_temporary_account() {
+    // the fixture opened its position in native SOL mode, paying the WSOL side in SOL
+    let mut f = Fixture::new_native();
+    let native_0 = f.token_0 == spl_token::native_mint::ID;
+    let (native_vault, other_user, other_vault) = if native_0 {
+        (f.vault_0, f.user_1, f.vault_1)
+    } else {
+        (f.vault_1, f.user_0, f.vault_0)
+    };
+    let temporary = native_sol_address(&f.user);
+    assert!(f.svm.token_balance(&native_vault) > 0);
+    assert!(!f.svm.exists(&temporary));
+
+    let swap_ix = |f: &Fixture, a_to_b: bool, native_sol: bool| {
+        let start = tick_array_start(f.pool_state().swap_boundary_tick(a_to_b));
+        f.swap_ix_with(tick_array_address(&f.pool, start), 1_000, a_to_b, 0, native_sol)
+    };
+    let sol = |f: &Fixture| (f.svm.lamports(&f.user), f.svm.token_balance(&native_vault));
+    let other = |f: &Fixture| (f.svm.token_balance(&other_user), f.svm.token_balance(&other_vault));
+
+    // selling SOL wraps exactly the consumed input, the account's rent comes back on closing
+    let (user_sol, vault_sol) = sol(&f);
+    let (user_other, vault_other) = other(&f);
+    f.svm.process(&swap_ix(&f, native_0, true)).unwrap();
+    assert_eq!(sol(&f), (user_sol - 1_000, vault_sol + 1_000));
+    let bought = f.svm.token_balance(&other_user) - user_other;
+    assert!(bought > 0);
+    assert_eq!(other(&f), (user_other + bought, vault_other - bought));
+    assert!(!f.svm.exists(&temporary));
+
+    // buying SOL pays the output out as lamports
+    let (user_sol, vault_sol) = sol(&f);
+    f.svm.process(&swap_ix(&f, !native_0, true)).unwrap();
+    let sold = vault_sol - f.svm.token_balance(&native_vault);
+    assert!(sold > 0);
+    assert_eq!(f.svm.lamports(&f.user), user_sol + sold);
+    assert!(!f.svm.exists(&temporary));
+
+    // the temporary account and the native SOL flag go together
+    let mut ix = swap_ix(&f, native_0, true);
+    *ix.data.last_mut().unwrap() = 0;
+    expect_error(f.svm.process(&ix), ClmmError::InvalidNativeSolAccount);
+    let mut ix = swap_ix(&f, native_0, true);
+    let meta = ix.accounts.iter_mut().find(|meta| meta.pubkey == temporary).unwrap();
+    *meta = AccountMeta::new_readonly(clmm::ID, false);
+    expect_error(f.svm.process(&ix), ClmmError::InvalidNativeSolAccount);
+
+    // closing the position pays the WSOL side out as SOL as well
+    let (user_sol, vault_sol) = sol(&f);
+    f.svm.process(&f.close_position_ix()).unwrap();
+    let withdrawn = vault_sol - f.svm.token_balance(&native_vault);
+    assert!(withdrawn > 0);
+    assert!(f.svm.lamports(&f.user) > user_sol + withdrawn);
+    assert!(!f.svm.exists(&temporary));
+}
+
+#[test]
+fn quote_swap_returns_the_swap_without_writing() {
+    let mut f = Fixture::new();
+    let pool_before = f.svm.account(&f.pool).unwrap().data;
+    let balances_before = f.balances();
+
+    let meta = f.svm.process(&f.quote_swap_ix(f.upper_tick_array, 1_000, true)).unwrap();
+    assert_eq!(f.svm.account(&f.pool).unwrap().data, pool_before);
+    assert_eq!(f.balances(), balances_before);
+
+    // the quote comes back as return data and is what the swap then does
+    let (amount_in, amount_out, sqrt_price_x96, tick): (u64, u64, u128, i32) =
+        return_value(&meta);
+    assert_eq!(amount_in, 1_000);
+    f.svm.process(&f.swap_ix(1_000, true, 0)).unwrap();
+    assert_eq!(f.svm.token_balance(&f.user_1) - balances_before[1], amount_out);
+    let pool = f.pool_state();
+    assert_eq!((pool.sqrt_price_x96, pool.current_tick), (sqrt_price_x96, tick));
+
+    let result = f.svm.process(&f.quote_swap_ix(f.lower_tick_array, 1_000, false));
+    expect_error(result, ClmmError::InvalidTickArrayAccount);
+}
+
+#[test]
+fn increase_and_decrease_liquidity() {
+    let mut f = Fixture::new();
+    let [user_0, user_1, vault_0, vault_1] = f.balances();
+
+    f.svm.process(&f.increase_liquidity_ix(LIQUIDITY, LOWER_TICK)).unwrap();
+    assert_eq!(f.position_state().liquidity, 2 * LIQUIDITY);
+    assert_eq!(f.pool_state().global_liquidity, 2 * LIQUIDITY);
+    let lower: TickArrayState = f.svm.zero_copy_account(&f.lower_tick_array);
+    let index = ((LOWER_TICK - lower.starting_tick) / TICK_SPACING) as usize;
+    assert_eq!({ lower.ticks[index].gross_liquidity }, 2 * LIQUIDITY);
+    // the same liquidity again costs what open_position took, give or take rounding
+    let [deposit_0, deposit_1] =
+        [f.svm.token_balance(&f.vault_0) - vault_0, f.svm.token_balance(&f.vault_1) - vault_1];
+    assert!(deposit_0.abs_diff(vault_0) <= 1 && deposit_1.abs_diff(vault_1) <= 1);
+    assert_eq!(
+        f.balances(),
+        [user_0 - deposit_0, user_1 - deposit_1, vault_0 + deposit_0, vault_1 + deposit_1]
+    );
+
+    let [paid_0, paid_1, _, _] = f.balances();
+    let meta = f.svm.process(&f.decrease_liquidity_ix(f.user, LIQUIDITY)).unwrap();
+    assert_eq!(f.position_state().liquidity, LIQUIDITY);
+    assert_eq!(f.pool_state().global_liquidity, LIQUIDITY);
+    // withdrawals round down, the pool keeps the dust
+    let [now_user_0, now_user_1, now_vault_0, now_vault_1] = f.balances();
+    let received: (u64, u64) = return_value(&meta);
+    assert_eq!(received, (now_user_0 - paid_0, now_user_1 - paid_1));
+    assert!(now_user_0 <= user_0 && user_0 - now_user_0 <= 2);
+    assert!(now_user_1 <= user_1 && user_1 - now_user_1 <= 2);
+    assert_eq!(now_user_0 + now_vault_0, user_0 + vault_0);
+    assert_eq!(now_user_1 + now_vault_1, user_1 + vault_1);
+}
+
+#[test]
+fn decreasing_to_zero_clears_the_bitmap() {
+    let mut f = Fixture::new();
+    let (lower_start, upper_start) = (tick_array_start(LOWER_TICK), tick_array_start(UPPER_TICK));
+
+    f.svm.process(&f.decrease_liquidity_ix(f.user, LIQUIDITY - 1)).unwrap();
+    let bitmap: TickArrayBitmap = f.svm.zero_copy_account(&f.tick_array_bitmap);
+    assert!(bitmap.is_initialized(lower_start, TICK_SPACING).unwrap());
+
+    // the position keeps its account, only its ticks leave the arrays
+    f.svm.process(&f.decrease_liquidity_ix(f.user, 1)).unwrap();
+    let bitmap: TickArrayBitmap = f.svm.zero_copy_account(&f.tick_array_bitmap);
+    assert!(!bitmap.is_initialized(lower_start, TICK_SPACING).unwrap());
+    assert!(!bitmap.is_initialized(upper_start, TICK_SPACING).unwrap());
+    assert_eq!(bitmap.next_initialized_tick_array(upper_start, TICK_SPACING, true).unwrap(), None);
+}
+
+#[test]
+fn liquidity_errors() {
+    let mut f = Fixture::new();
+
+    let result = f.svm.process(&f.increase_liquidity_ix(500, LOWER_TICK - TICK_SPACING));
+    expect_error(result, ClmmError::InvalidTicks);
+    let result = f.svm.process(&f.increase_liquidity_ix(0, LOWER_TICK));
+    expect_error(result, ClmmError::ZeroAmount);
+
+    let stranger = f.svm.new_keypair();
+    let result = f.svm.process(&f.decrease_liquidity_ix(stranger, 500));
+    expect_error(result, ClmmError::InvalidPositionOwner);
+    let result = f.svm.process(&f.decrease_liquidity_ix(f.user, LIQUIDITY + 1));
+    expect_error(result, ClmmError::InvalidAmount);
+}
+
+#[test]
+fn operator_withdraws_to_the_owner_only() {
+    let mut f = Fixture::new();
+    let operator = f.svm.new_keypair();
+    let position_delegate = f.position_delegate_address(&operator);
+    // no operation is behind the unassigned bit
+    let result = f.svm.process(&f.approve_position_delegate_ix(operator, 1 << 2));
+    expect_error(result, ClmmError::InvalidDelegatePermissions);
+    f.svm
+        .process(&f.approve_position_delegate_ix(operator, POSITION_DELEGATE_DECREASE_LIQUIDITY))
+        .unwrap();
+    let delegate: PositionDelegate = f.svm.anchor_account(&position_delegate);
+    assert_eq!((delegate.owner, delegate.operator), (f.user, operator));
+    assert_eq!(delegate.permissions, POSITION_DELEGATE_DECREASE_LIQUIDITY);
+
+    let operator_0 = create_token_account(&mut f.svm, f.token_0, f.admin, operator, 0);
+    let operator_1 = create_token_account(&mut f.svm, f.token_1, f.admin, operator, 0);
+    let owner_accounts = (f.user_0, f.user_1);
+
+    let ix = f.decrease_liquidity_ix_with(operator, 500_000, None, owner_accounts);
+    expect_error(f.svm.process(&ix), ClmmError::InvalidPositionOwner);
+    let operator_accounts = (operator_0, operator_1);
+    let ix =
+        f.decrease_liquidity_ix_with(operator, 500_000, Some(position_delegate), operator_accounts);
+    expect_error(f.svm.process(&ix), ClmmError::InvalidWithdrawalAccount);
+    // the approval does not cover deposits
+    let ix = f.increase_liquidity_ix_with(operator, 500, LOWER_TICK, Some(position_delegate));
+    expect_error(f.svm.process(&ix), ClmmError::InvalidPositionOwner);
+
+    let [user_0, user_1, _, _] = f.balances();
+    let ix =
+        f.decrease_liquidity_ix_with(operator, 500_000, Some(position_delegate), owner_accounts);
+    f.svm.process(&ix).unwrap();
+    assert_eq!(f.position_state().liquidity, LIQUIDITY - 500_000);
+    assert!(f.svm.token_balance(&f.user_0) > user_0);
+    assert!(f.svm.token_balance(&f.user_1) > user_1);
+
+    // revoking closes the approval and refunds its rent to the owner
+    let owner_lamports = f.svm.lamports(&f.user);
+    let delegate_rent = f.svm.lamports(&position_delegate);
+    f.svm.process(&f.revoke_position_delegate_ix(operator)).unwrap();
+    assert!(!f.svm.exists(&position_delegate));
+    assert_eq!(f.svm.lamports(&f.user), owner_lamports + delegate_rent);
+    let ix = f.decrease_liquidity_ix_with(operator, 500, Some(position_delegate), owner_accounts);
+    expect_error(f.svm.process(&ix), ErrorCode::AccountNotInitialized);
+}
+
+#[test]
+fn split_and_merge_positions_leave_the_ticks() {
+    let mut f = Fixture::new();
+    let other = f.svm.new_keypair();
+    let other_position = position_address(&f.pool, &other, LOWER_TICK, UPPER_TICK, 7);
+    let second_position = position_address(&f.pool, &f.user, LOWER_TICK, UPPER_TICK, 1);
+    let arrays_before = [f.lower_tick_array, f.upper_tick_array]
+        .map(|array| f.svm.account(&array).unwrap().data);
+
+    // a split needs another position, the owner's own under another id will do
+    let result = f.svm.process(&f.split_position_ix(f.user, f.user, POSITION_ID, 400));
+    expect_error(result, ClmmError::InvalidPositionRange);
+    f.svm.process(&f.split_position_ix(f.user, f.user, 1, 300)).unwrap();
+    let split: Position = f.svm.anchor_account(&second_position);
+    assert_eq!((split.liquidity, split.owner, split.id), (300, f.user, 1));
+    f.svm.process(&f.split_position_ix(f.user, other, 7, 400)).unwrap();
+    assert_eq!(f.position_state().liquidity, LIQUIDITY - 700);
+    let split: Position = f.svm.anchor_account(&other_position);
+    assert_eq!((split.liquidity, split.owner, split.pool), (400, other, f.pool));
+
+    let result = f.svm.process(&f.merge_positions_ix(f.user, POSITION_ID, f.position));
+    expect_error(result, ClmmError::InvalidPositionRange);
+    f.svm.process(&f.merge_positions_ix(f.user, 1, f.position)).unwrap();
+    assert!(!f.svm.exists(&second_position));
+    f.svm.process(&f.merge_positions_ix(other, 7, f.position)).unwrap();
+    assert_eq!(f.position_state().liquidity, LIQUIDITY);
+    assert!(!f.svm.exists(&other_position));
+
+    let arrays_after = [f.lower_tick_array, f.upper_tick_array]
+        .map(|array| f.svm.account(&array).unwrap().data);
+    assert_eq!(arrays_before, arrays_after);
+    assert_eq!(f.pool_state().global_liquidity, LIQUIDITY);
+}
+
+#[test]
+fn cancel_limit_order_refunds_the_deposit() {
+    let mut f = Fixture::new();
+    let limit_order = f.limit_order_address(0, true);
+    let [user_0, user_1, vault_0, vault_1] = f.balances();
+
+    // selling token_0 below the price would fill at once
+    let result = f.svm.process(&f.open_limit_order_ix(-2 * TICK_SPACING, true, LIQUIDITY));
+    expect_error(result, ClmmError::LimitOrderCrossesPrice);
+    // the user accounts must hold the pool's mints
+    let mut ix = f.open_limit_order_ix(0, true, LIQUIDITY);
+    swap_accounts(&mut ix, f.user_0, f.user_1);
+    expect_error(f.svm.process(&ix), ErrorCode::ConstraintTokenMint);
+
+    f.svm.process(&f.open_limit_order_ix(0, true, LIQUIDITY)).unwrap();
+    let order: LimitOrder = f.svm.anchor_account(&limit_order);
+    assert_eq!((order.liquidity, order.owner, order.tick), (LIQUIDITY, f.user, 0));
+    let deposit = user_0 - f.svm.token_balance(&f.user_0);
+    assert!(deposit > 0);
+    assert_eq!(f.balances(), [user_0 - deposit, user_1, vault_0 + deposit, vault_1]);
+    // nothing reached the order's range yet
+    let result = f.svm.process(&f.settle_limit_order_ix(f.user, 0));
+    expect_error(result, ClmmError::LimitOrderNotFilled);
+
+    let user_lamports = f.svm.lamports(&f.user);
+    let order_rent = f.svm.lamports(&limit_order);
+    f.svm.process(&f.cancel_limit_order_ix(0, true)).unwrap();
+    assert!(!f.svm.exists(&limit_order));
+    assert_eq!(f.svm.lamports(&f.user), user_lamports + order_rent);
+    let refund = f.svm.token_balance(&f.user_0) - (user_0 - deposit);
+    assert!(deposit - refund <= 1);
+    assert_eq!(
+        f.balances(),
+        [user_0 - deposit + refund, user_1, vault_0 + deposit - refund, vault_1]
+    );
+}
+
+#[test]
+fn settle_limit_order_pays_the_bought_token() {
+    let mut f = Fixture::new();
+    let limit_order = f.limit_order_address(0, true);
+    f.svm.process(&f.open_limit_order_ix(0, true, LIQUIDITY)).unwrap();
+
+    // a rising price sells the order's token_0 as it moves through [0, 60]
+    f.svm.process(&f.swap_ix(u32::MAX as u64, false, 0)).unwrap();
+    assert!(f.pool_state().current_tick >= TICK_SPACING);
+
+    // anyone can settle, the proceeds go to the owner
+    let keeper = f.svm.new_keypair();
+    let [user_0, user_1, _, vault_1] = f.balances();
+    f.svm.process(&f.settle_limit_order_ix(keeper, 0)).unwrap();
+    assert!(!f.svm.exists(&limit_order));
+    let proceeds = f.svm.token_balance(&f.user_1) - user_1;
+    assert!(proceeds > 0);
+    assert_eq!(f.svm.token_balance(&f.user_0), user_0);
+    assert_eq!(f.svm.token_balance(&f.vault_1), vault_1 - proceeds);
+}
+
+#[test]
+fn reposition_moves_the_position() {
+    let mut f = Fixture::new();
+    let new_range = (-2 * TICK_SPACING, 2 * TICK_SPACING);
+    let new_position = position_address(&f.pool, &f.user, new_range.0, new_range.1, POSITION_ID);
+    let total = |f: &Fixture| {
+        let [user_0, user_1, vault_0, vault_1] = f.balances();
+        (user_0 + vault_0, user_1 + vault_1)
+    };
+    let totals = total(&f);
+
+    // the expected price bounds every step, so it cannot be left out
+    let result = f.svm.process(&f.reposition_ix(new_range, (0, 0), LIQUIDITY, 0));
+    expect_error(result, ClmmError::MissingExpectedPrice);
+    let result = f.svm.process(&f.reposition_ix(new_range, (0, 0), LIQUIDITY, Q96 + Q96 / 50));
+    expect_error(result, ClmmError::PriceSlippageExceeded);
+
+    let user_lamports = f.svm.lamports(&f.user);
+    let [user_0, user_1, _, _] = f.balances();
+    f.svm.process(&f.reposition_ix(new_range, (0, 0), LIQUIDITY, Q96)).unwrap();
+    assert!(!f.svm.exists(&f.position));
+    let position: Position = f.svm.anchor_account(&new_position);
+    assert_eq!((position.lower_tick, position.upper_tick), new_range);
+    assert_eq!(position.liquidity, LIQUIDITY);
+    assert_eq!(f.pool_state().global_liquidity, LIQUIDITY);
+    // the old position's rent paid for the new one
+    assert_eq!(f.svm.lamports(&f.user), user_lamports);
+
+    // the range moved up: the user paid in token_0 and got token_1 back
+    assert!(f.svm.token_balance(&f.user_0) < user_0);
+    assert!(f.svm.token_balance(&f.user_1) > user_1);
+    assert_eq!(total(&f), totals);
+}
+
+#[test]
+fn reposition_bounds_its_swap() {
+    let mut f = Fixture::new();
+    // half the liquidity stays behind in a second position for the swap to trade against
+    f.svm.process(&f.split_position_ix(f.user, f.user, 1, LIQUIDITY / 2)).unwrap();
+    let new_range = (-2 * TICK_SPACING, 2 * TICK_SPACING);
+
+    // the user accounts must hold the pool's mints
+    let mut ix = f.reposition_ix(new_range, (1_000, 0), LIQUIDITY / 2, Q96);
+    swap_accounts(&mut ix, f.user_0, f.user_1);
+    expect_error(f.svm.process(&ix), ErrorCode::ConstraintTokenMint);
+
+    // at a price of ~1 the swap pays out just under its input
+    let ix = f.reposition_ix(new_range, (1_000, 1_000), LIQUIDITY / 2, Q96);
+    expect_error(f.svm.process(&ix), ClmmError::SlippageExceeded);
+    let ix = f.reposition_ix(new_range, (1_000, 990), LIQUIDITY / 2, Q96);
+    f.svm.process(&ix).unwrap();
+    assert!(f.pool_state().sqrt_price_x96 < Q96);
+    assert_eq!(f.pool_state().global_liquidity, LIQUIDITY);
+}
+
+#[test]
+fn close_position_then_tick_arrays() {
+    let mut f = Fixture::new();
+    let payer = f.user;
+    let lower_start = tick_array_start(LOWER_TICK);
+
+    // the position still references both arrays
+    let result = f.svm.process(&f.close_tick_array_ix(f.lower_tick_array, lower_start, payer));
+    expect_error(result, ClmmError::TickArrayNotEmpty);
+
+    let user_lamports = f.svm.lamports(&f.user);
+    let position_rent = f.svm.lamports(&f.position);
+    let [user_0, user_1, vault_0, vault_1] = f.balances();
+    let meta = f.svm.process(&f.close_position_ix()).unwrap();
+    assert!(!f.svm.exists(&f.position));
+    assert_eq!(f.svm.lamports(&f.user), user_lamports + position_rent);
+    assert_eq!(f.pool_state().global_liquidity, 0);
+    let [amount_0, amount_1] =
+        [f.svm.token_balance(&f.user_0) - user_0, f.svm.token_balance(&f.user_1) - user_1];
+    assert_eq!(return_value::<(u64, u64)>(&meta), (amount_0, amount_1));
+    assert_eq!(
+        f.balances(),
+        [user_0 + amount_0, user_1 + amount_1, vault_0 - amount_0, vault_1 - amount_1]
+    );
+    // withdrawals round down, only that dust stays in the vaults
+    assert!(vault_0 - amount_0 <= 1 && vault_1 - amount_1 <= 1);
+
+    let bitmap: TickArrayBitmap = f.svm.zero_copy_account(&f.tick_array_bitmap);
+    assert!(!bitmap.is_initialized(lower_start, TICK_SPACING).unwrap());
+
+    let result =
+        f.svm.process(&f.close_tick_array_ix(f.lower_tick_array, lower_start, f.admin));
+    expect_error(result, ClmmError::InvalidTickArrayPayer);
+
+    let tick_array_rent = f.svm.lamports(&f.lower_tick_array);
+    let payer_lamports = f.svm.lamports(&payer);
+    f.svm.process(&f.close_tick_array_ix(f.lower_tick_array, lower_start, payer)).unwrap();
+    assert!(!f.svm.exists(&f.lower_tick_array));
+    assert_eq!(f.svm.lamports(&payer), payer_lamports + tick_array_rent);
+}
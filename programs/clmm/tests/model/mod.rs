@@ -0,0 +1,163 @@
+// reference CLMM math in exact rational arithmetic, used to check the program's fixed-point
+// results: every quantity is kept as a fraction of big integers and only rounded when
+// compared against the program
+
+#![allow(dead_code)]
+
+use num_bigint::BigUint;
+
+pub const Q96: u128 = 1 << 96;
+
+fn big(value: u128) -> BigUint {
+    BigUint::from(value)
+}
+
+// a non-negative fraction
+#[derive(Clone, Debug)]
+pub struct Exact {
+    num: BigUint,
+    den: BigUint,
+}
+
+impl Exact {
+    fn new(num: BigUint, den: BigUint) -> Self {
+        Self { num, den }
+    }
+
+    pub fn int(value: u128) -> Self {
+        Self::new(big(value), big(1))
+    }
+
+    pub fn floor(&self) -> BigUint {
+        &self.num / &self.den
+    }
+
+    pub fn ceil(&self) -> BigUint {
+        (&self.num + &self.den - 1u32) / &self.den
+    }
+
+    // checks that `actual` is within `tolerance` of the exact value
+    pub fn assert_close(&self, actual: u128, tolerance: u128, what: &str) {
+        let actual_big = big(actual);
+        assert!(
+            &actual_big + tolerance >= self.floor() && actual_big <= self.ceil() + tolerance,
+            "{what}: {actual} is not within {tolerance} of {self}"
+        );
+    }
+
+    // signed distance of `actual` from the exact value, rounded toward it
+    pub fn error(&self, actual: u128) -> i128 {
+        let actual_big = big(actual);
+        if actual_big < self.floor() {
+            -i128::try_from(self.floor() - actual_big).unwrap()
+        } else if actual_big > self.ceil() {
+            i128::try_from(actual_big - self.ceil()).unwrap()
+        } else {
+            0
+        }
+    }
+}
+
+impl std::fmt::Display for Exact {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.den == big(1) {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{} (~{})", self.num, self.den, self.floor())
+        }
+    }
+}
+
+// token amounts held by `liquidity` over [lower, upper] at `sqrt_price`
+pub fn amounts_for_liquidity(
+    sqrt_price: u128,
+    sqrt_lower: u128,
+    sqrt_upper: u128,
+    liquidity: u128,
+) -> (Exact, Exact) {
+    let q96 = big(Q96);
+    let l = big(liquidity);
+    // amount_0 = L * Q96 * (upper - p) / (p * upper), amount_1 = L * (p - lower) / Q96
+    let amount_0 = |from: u128| {
+        Exact::new(&l * &q96 * big(sqrt_upper - from), big(from) * big(sqrt_upper))
+    };
+    let amount_1 = |to: u128| Exact::new(&l * big(to - sqrt_lower), q96.clone());
+
+    if sqrt_price <= sqrt_lower {
+        (amount_0(sqrt_lower), Exact::int(0))
+    } else if sqrt_price >= sqrt_upper {
+        (Exact::int(0), amount_1(sqrt_upper))
+    } else {
+        (amount_0(sqrt_price), amount_1(sqrt_price))
+    }
+}
+
+pub struct SwapStep {
+    pub sqrt_price: Exact,
+    pub amount_in: Exact,
+    pub amount_out: Exact,
+    pub reached_target: bool,
+    // input needed to reach the target price
+    pub required_in: Exact,
+}
+
+// one swap step from `sqrt_price` toward `sqrt_target` with constant liquidity
+pub fn swap_step(
+    sqrt_price: u128,
+    sqrt_target: u128,
+    liquidity: u128,
+    amount: u128,
+    a_to_b: bool,
+) -> SwapStep {
+    let q96 = big(Q96);
+    let l = big(liquidity);
+    let p0 = big(sqrt_price);
+    let pt = big(sqrt_target);
+    let amount_big = big(amount);
+
+    if a_to_b {
+        // token_0 in: Δx = L * Q96 * (p0 - p1) / (p0 * p1), token_1 out: Δy = L * (p0 - p1) / Q96
+        let required_in = Exact::new(&l * &q96 * (&p0 - &pt), &p0 * &pt);
+        if &amount_big * &p0 * &pt >= &l * &q96 * (&p0 - &pt) {
+            SwapStep {
+                sqrt_price: Exact::int(sqrt_target),
+                amount_in: required_in.clone(),
+                amount_out: Exact::new(&l * (&p0 - &pt), q96.clone()),
+                reached_target: true,
+                required_in,
+            }
+        } else {
+            // p1 = L * Q96 * p0 / (L * Q96 + Δx * p0)
+            let denominator = &l * &q96 + &amount_big * &p0;
+            SwapStep {
+                sqrt_price: Exact::new(&l * &q96 * &p0, denominator.clone()),
+                amount_in: Exact::int(amount),
+                amount_out: Exact::new(&l * &amount_big * &p0 * &p0, &q96 * denominator),
+                reached_target: false,
+                required_in,
+            }
+        }
+    } else {
+        // token_1 in: Δy = L * (p1 - p0) / Q96, token_0 out: Δx = L * Q96 * (p1 - p0) / (p0 * p1)
+        let required_in = Exact::new(&l * (&pt - &p0), q96.clone());
+        if &amount_big * &q96 >= &l * (&pt - &p0) {
+            SwapStep {
+                sqrt_price: Exact::int(sqrt_target),
+                amount_in: required_in.clone(),
+                amount_out: Exact::new(&l * &q96 * (&pt - &p0), &p0 * &pt),
+                reached_target: true,
+                required_in,
+            }
+        } else {
+            // p1 = p0 + Δy * Q96 / L
+            let numerator = &p0 * &l + &amount_big * &q96;
+            SwapStep {
+                sqrt_price: Exact::new(numerator.clone(), l.clone()),
+                amount_in: Exact::int(amount),
+                amount_out: Exact::new(&l * &q96 * &amount_big * &q96, &p0 * numerator),
+                reached_target: false,
+                required_in,
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// the program's SBF build on litesvm, next to the SPL Token and Token-2022 builds litesvm ships
+//
+// the program is read from SBF_OUT_DIR, where `cargo test-sbf` builds it, or target/deploy, where
+// `anchor build` does. the tests only compile with the test-sbf feature, which cargo test-sbf
+// turns on:
+//
+//   cargo test-sbf -p clmm
+//   anchor build && cargo test -p clmm --features test-sbf
+//
+// signers are keypairs the runtime verifies. `new_keypair` registers one and `process` signs
+// with the keypair of every signer account of the instruction. fees come out of a payer of the
+// harness' own, so the lamports of the tests' accounts only move with what the program does
+
+#![allow(dead_code)]
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{bpf_loader_upgradeable, instruction::Instruction, program_pack::Pack},
+    ZeroCopy,
+};
+use anchor_spl::token::spl_token;
+use litesvm::{types::TransactionMetadata, LiteSVM};
+use solana_account::Account as SolanaAccount;
+use solana_keypair::Keypair;
+use solana_signer::Signer as _;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+pub struct Svm {
+    svm: LiteSVM,
+    payer: Keypair,
+    keypairs: HashMap<Pubkey, Keypair>,
+}
+
+impl Default for Svm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn program_path() -> PathBuf {
+    std::env::var_os("SBF_OUT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy"))
+        .join("clmm.so")
+}
+
+impl Svm {
+    pub fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 1_000 * LAMPORTS_PER_SOL).unwrap();
+        Self {
+            svm,
+            payer,
+            keypairs: HashMap::new(),
+        }
+    }
+
+    // a fresh keypair `process` signs with, for users as well as accounts created by a signature
+    pub fn new_keypair(&mut self) -> Pubkey {
+        let keypair = Keypair::new();
+        let key = keypair.pubkey();
+        self.keypairs.insert(key, keypair);
+        key
+    }
+
+    // deploys clmm the way `anchor deploy` does, upgradeable with `upgrade_authority`, and
+    // returns its program data account
+    pub fn deploy_clmm(&mut self, upgrade_authority: Pubkey) -> Pubkey {
+        let path = program_path();
+        let program = std::fs::read(&path)
+            .unwrap_or_else(|err| panic!("reading {}: {err}, build the program first", path.display()));
+        let (program_data, _) =
+            Pubkey::find_program_address(&[clmm::ID.as_ref()], &bpf_loader_upgradeable::ID);
+
+        // bincode UpgradeableLoaderState::ProgramData followed by the ELF
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(upgrade_authority.as_ref());
+        data.extend_from_slice(&program);
+        self.set_account(program_data, bpf_loader_upgradeable::ID, data, false);
+
+        // bincode UpgradeableLoaderState::Program, set last so the program data is there to load
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(program_data.as_ref());
+        self.set_account(clmm::ID, bpf_loader_upgradeable::ID, data, true);
+        program_data
+    }
+
+    pub fn minimum_balance(&self, data_len: usize) -> u64 {
+        self.svm.minimum_balance_for_rent_exemption(data_len)
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<SolanaAccount> {
+        self.svm.get_account(key)
+    }
+
+    // an account exists while it holds lamports, a closed one is purged
+    pub fn exists(&self, key: &Pubkey) -> bool {
+        self.lamports(key) > 0
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn airdrop(&mut self, key: Pubkey, lamports: u64) {
+        self.svm.airdrop(&key, lamports).unwrap();
+    }
+
+    fn set_account(&mut self, key: Pubkey, owner: Pubkey, data: Vec<u8>, executable: bool) {
+        let account = SolanaAccount {
+            lamports: self.minimum_balance(data.len()),
+            data,
+            owner,
+            executable,
+            rent_epoch: 0,
+        };
+        self.svm.set_account(key, account).unwrap();
+    }
+
+    // stores a rent-exempt account owned by `owner` holding `data`
+    pub fn set_data(&mut self, key: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        self.set_account(key, owner, data, false);
+    }
+
+    pub fn anchor_account<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let account = self.account(key).expect("account not found");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub fn zero_copy_account<T: ZeroCopy>(&self, key: &Pubkey) -> T {
+        let account = self.account(key).expect("account not found");
+        *bytemuck::from_bytes(&account.data[8..8 + std::mem::size_of::<T>()])
+    }
+
+    pub fn packed_account<T: Pack>(&self, key: &Pubkey) -> T {
+        let data = self.account(key).expect("account not found").data;
+        T::unpack_unchecked(&data[..T::LEN]).unwrap()
+    }
+
+    // token balance of an SPL Token or Token-2022 account, 0 once it is closed
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        match self.account(key) {
+            Some(account) if account.data.len() >= spl_token::state::Account::LEN => {
+                self.packed_account::<spl_token::state::Account>(key).amount
+            }
+            _ => 0,
+        }
+    }
+
+    // runs `instruction` in a transaction of its own. an instruction error comes back as the
+    // program error it stands for, anything else the transaction fails with is a bug in the test
+    pub fn process(
+        &mut self,
+        instruction: &Instruction,
+    ) -> std::result::Result<TransactionMetadata, ProgramError> {
+        let mut signers = vec![&self.payer];
+        for meta in instruction.accounts.iter().filter(|meta| meta.is_signer) {
+            let keypair = self
+                .keypairs
+                .get(&meta.pubkey)
+                .unwrap_or_else(|| panic!("no keypair for signer {}", meta.pubkey));
+            if !signers.iter().any(|signer| signer.pubkey() == meta.pubkey) {
+                signers.push(keypair);
+            }
+        }
+        let tx = Transaction::new_signed_with_payer(
+            std::slice::from_ref(instruction),
+            Some(&self.payer.pubkey()),
+            &signers,
+            self.svm.latest_blockhash(),
+        );
+        let result = self.svm.send_transaction(tx);
+        // a new blockhash lets the same instruction run again
+        self.svm.expire_blockhash();
+
+        result.map_err(|failed| match failed.err {
+            TransactionError::InstructionError(_, err) => ProgramError::try_from(err.clone())
+                .unwrap_or_else(|_| panic!("{err}\n{}", failed.meta.pretty_logs())),
+            err => panic!("{err}\n{}", failed.meta.pretty_logs()),
+        })
+    }
+}
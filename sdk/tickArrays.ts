@@ -0,0 +1,66 @@
+import { BN } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+
+// mirrors utils::constants and states::TickArrayBitmap in the program
+export const TICKS_PER_ARRAY = 60;
+export const MAX_TICK = 443636;
+// bit i tracks the tick array with index i - TICK_ARRAY_BITMAP_BITS / 2
+export const TICK_ARRAY_BITMAP_BITS =
+  Math.ceil(((Math.floor(MAX_TICK / TICKS_PER_ARRAY) + 1) * 2) / 64) * 64;
+
+function i32ToLeBytes(value: number): Buffer {
+  const buf = Buffer.allocUnsafe(4);
+  buf.writeInt32LE(value, 0);
+  return buf;
+}
+
+// tick arrays start at multiples of tickSpacing * TICKS_PER_ARRAY, rounding down for negative ticks
+export function getTickArrayStartIndex(tick: number, tickSpacing: number): number {
+  const arrayWidth = tickSpacing * TICKS_PER_ARRAY;
+  return Math.floor(tick / arrayWidth) * arrayWidth;
+}
+
+// mirrors Pool::swap_boundary_tick: a swap reads the array holding the spacing-aligned tick
+// at or below the current tick when the price falls, the next one above when it rises
+export function getSwapTickArrayStartIndex(
+  currentTick: number,
+  tickSpacing: number,
+  aToB: boolean
+): number {
+  const boundary = Math.floor(currentTick / tickSpacing) * tickSpacing + (aToB ? 0 : tickSpacing);
+  return getTickArrayStartIndex(boundary, tickSpacing);
+}
+
+export function getTickArrayAddress(programId: PublicKey, pool: PublicKey, startIndex: number): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("tick_array"), pool.toBuffer(), i32ToLeBytes(startIndex)],
+    programId
+  )[0];
+}
+
+export function getTickArrayBitmapAddress(programId: PublicKey, pool: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync([Buffer.from("tick_array_bitmap"), pool.toBuffer()], programId)[0];
+}
+
+// mirrors TickArrayBitmap::next_initialized_tick_array: the start index of the closest array
+// holding initialized ticks after startIndex (exclusive), towards lower ticks when aToB
+export function findNextInitializedTickArray(
+  bitmap: BN[],
+  startIndex: number,
+  tickSpacing: number,
+  aToB: boolean
+): number | null {
+  const arrayWidth = tickSpacing * TICKS_PER_ARRAY;
+  const isSet = (bit: number) => bitmap[Math.floor(bit / 64)].testn(bit % 64);
+  const step = aToB ? -1 : 1;
+  for (
+    let bit = startIndex / arrayWidth + TICK_ARRAY_BITMAP_BITS / 2 + step;
+    bit >= 0 && bit < TICK_ARRAY_BITMAP_BITS;
+    bit += step
+  ) {
+    if (isSet(bit)) {
+      return (bit - TICK_ARRAY_BITMAP_BITS / 2) * arrayWidth;
+    }
+  }
+  return null;
+}
@@ -0,0 +1,316 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Clmm } from "../target/types/clmm";
+import { assert } from "chai";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import {
+  createMint,
+  mintTo,
+  createAssociatedTokenAccount,
+  TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+import * as fs from "fs";
+import * as path from "path";
+
+// compute unit benchmark: runs every pool instruction on the local validator, reads the units
+// each transaction consumed and writes them to tests/compute-units.md. the table only depends on
+// the program build, so committing it shows the cost of a change in the diff
+//
+// the Rust suite cannot measure this, it runs the program compiled for the host rather than the
+// SBF binary the runtime meters
+describe("compute units", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.clmm as Program<Clmm>;
+  const provider = program.provider as anchor.AnchorProvider;
+  const wallet = provider.wallet as anchor.Wallet;
+
+  const OUTPUT = path.join(__dirname, "compute-units.md");
+  const INSTRUCTION_LIMIT = 200_000;
+  const TRANSACTION_LIMIT = 1_400_000;
+
+  const TICK_SPACING = 10;
+  const TICKS_PER_ARRAY = 60;
+  const INITIAL_SQRT_PRICE = new anchor.BN("79228162514264337593543950336"); // sqrt(1) * 2^96
+  const LIQUIDITY_AMOUNT = new anchor.BN("1000000000");
+  const POSITION_ID = new anchor.BN(0);
+
+  // one position across the arrays starting at -600 and 0, one inside the array at 0
+  const TWO_ARRAYS = { lower: -100, upper: 100 };
+  const ONE_ARRAY = { lower: 100, upper: 200 };
+
+  const rows: { instruction: string; scenario: string; token: string; units: number }[] = [];
+
+  let globalConfigPda: PublicKey;
+
+  function i32ToLeBytes(value: number): Buffer {
+    const buf = Buffer.allocUnsafe(4);
+    buf.writeInt32LE(value, 0);
+    return buf;
+  }
+
+  function getTickArrayStartIndex(tick: number): number {
+    const arrayWidth = TICK_SPACING * TICKS_PER_ARRAY;
+    return Math.floor(tick / arrayWidth) * arrayWidth;
+  }
+
+  // mirrors Pool::swap_boundary_tick, see tests/clmm.ts
+  function getSwapTickArrayStartIndex(currentTick: number, aToB: boolean): number {
+    const boundary = Math.floor(currentTick / TICK_SPACING) * TICK_SPACING + (aToB ? 0 : TICK_SPACING);
+    return getTickArrayStartIndex(boundary);
+  }
+
+  async function record(instruction: string, scenario: string, token: string, signature: string) {
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const units = tx.meta.computeUnitsConsumed;
+    rows.push({ instruction, scenario, token, units });
+    console.log(`${instruction} (${scenario}, ${token}): ${units} CU`);
+  }
+
+  before(async () => {
+    [globalConfigPda] = PublicKey.findProgramAddressSync([Buffer.from("global_config")], program.programId);
+
+    // tests/clmm.ts creates the config when the whole suite runs
+    if (!(await program.account.globalConfig.fetchNullable(globalConfigPda))) {
+      const [programData] = PublicKey.findProgramAddressSync(
+        [program.programId.toBuffer()],
+        new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+      );
+      await program.methods
+        .initializeConfig()
+        .accountsStrict({
+          signer: wallet.publicKey,
+          globalConfig: globalConfigPda,
+          program: program.programId,
+          programData,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+  });
+
+  for (const [token, tokenProgram] of [
+    ["Token", TOKEN_PROGRAM_ID],
+    ["Token-2022", TOKEN_2022_PROGRAM_ID],
+  ] as const) {
+    it(`measures every instruction with ${token} mints`, async () => {
+      const opts = { commitment: "confirmed" as const };
+      const mintA = await createMint(provider.connection, wallet.payer, wallet.publicKey, null, 6, undefined, opts, tokenProgram);
+      const mintB = await createMint(provider.connection, wallet.payer, wallet.publicKey, null, 6, undefined, opts, tokenProgram);
+      const [token0, token1] = mintA.toBuffer().compare(mintB.toBuffer()) < 0 ? [mintA, mintB] : [mintB, mintA];
+
+      const user0 = await createAssociatedTokenAccount(provider.connection, wallet.payer, token0, wallet.publicKey, opts, tokenProgram);
+      const user1 = await createAssociatedTokenAccount(provider.connection, wallet.payer, token1, wallet.publicKey, opts, tokenProgram);
+      await mintTo(provider.connection, wallet.payer, token0, user0, wallet.publicKey, 1_000_000_000_000, [], opts, tokenProgram);
+      await mintTo(provider.connection, wallet.payer, token1, user1, wallet.publicKey, 1_000_000_000_000, [], opts, tokenProgram);
+
+      const [pool] = PublicKey.findProgramAddressSync(
+        [Buffer.from("pool"), token0.toBuffer(), token1.toBuffer(), i32ToLeBytes(TICK_SPACING)],
+        program.programId
+      );
+      const [tickArrayBitmap] = PublicKey.findProgramAddressSync(
+        [Buffer.from("tick_array_bitmap"), pool.toBuffer()],
+        program.programId
+      );
+      const vault0 = Keypair.generate();
+      const vault1 = Keypair.generate();
+      const tickArray = (start: number) =>
+        PublicKey.findProgramAddressSync(
+          [Buffer.from("tick_array"), pool.toBuffer(), i32ToLeBytes(start)],
+          program.programId
+        )[0];
+
+      let signature = await program.methods
+        .initializePool(TICK_SPACING, INITIAL_SQRT_PRICE)
+        .accountsStrict({
+          signer: wallet.publicKey,
+          globalConfig: globalConfigPda,
+          token0Mint: token0,
+          token1Mint: token1,
+          pool,
+          tickArrayBitmap,
+          token0Vault: vault0.publicKey,
+          token1Vault: vault1.publicKey,
+          treasury: null,
+          creationFeeMint: null,
+          creationFeePayerAccount: null,
+          creationFeeTreasuryAccount: null,
+          creationFeeTokenProgram: null,
+          systemProgram: SystemProgram.programId,
+          tokenProgram,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([vault0, vault1])
+        .rpc(opts);
+      await record("initialize_pool", "-", token, signature);
+
+      const ranges = [
+        { name: "2 tick arrays", ...TWO_ARRAYS },
+        { name: "1 tick array", ...ONE_ARRAY },
+      ];
+      const positionAccounts = (range: { lower: number; upper: number }) => ({
+        signer: wallet.publicKey,
+        pool,
+        lowerTickArray: tickArray(getTickArrayStartIndex(range.lower)),
+        upperTickArray: tickArray(getTickArrayStartIndex(range.upper)),
+        position: PublicKey.findProgramAddressSync(
+          [
+            Buffer.from("position"),
+            pool.toBuffer(),
+            wallet.publicKey.toBuffer(),
+            i32ToLeBytes(range.lower),
+            i32ToLeBytes(range.upper),
+            POSITION_ID.toArrayLike(Buffer, "le", 8),
+          ],
+          program.programId
+        )[0],
+        user0,
+        user1,
+        nativeMint: null,
+        nativeSolAccount: null,
+        poolVault0: vault0.publicKey,
+        poolVault1: vault1.publicKey,
+        token0,
+        token1,
+        tokenProgram,
+      });
+
+      // the first position creates both arrays, the second one reuses the array at 0
+      for (const range of ranges) {
+        const lowerStart = getTickArrayStartIndex(range.lower);
+        const upperStart = getTickArrayStartIndex(range.upper);
+        signature = await program.methods
+          .openPosition(range.upper, range.lower, lowerStart, upperStart, POSITION_ID, LIQUIDITY_AMOUNT, false, new anchor.BN(0), 0)
+          .accountsStrict({
+            ...positionAccounts(range),
+            tickArrayBitmap,
+            systemProgram: SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .rpc(opts);
+        const created = range === ranges[0] ? "2 created" : "0 created";
+        await record("open_position", `${range.name}, ${created}`, token, signature);
+
+        signature = await program.methods
+          .increaseLiquidity(LIQUIDITY_AMOUNT, range.upper, range.lower, lowerStart, upperStart, false)
+          .accountsStrict({
+            ...positionAccounts(range),
+            positionDelegate: null,
+            systemProgram: SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .rpc(opts);
+        await record("increase_liquidity", range.name, token, signature);
+
+        signature = await program.methods
+          .decreaseLiquidity(LIQUIDITY_AMOUNT, range.upper, range.lower, lowerStart, upperStart, false)
+          .accountsStrict({
+            ...positionAccounts(range),
+            tickArrayBitmap,
+            positionDelegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc(opts);
+        await record("decrease_liquidity", range.name, token, signature);
+      }
+
+      // a swap takes a single step, so it crosses at most one tick: b_to_a when it reaches the
+      // boundary above, a_to_b when it starts on the boundary below
+      const swap = async (amount: number, aToB: boolean, scenario: string) => {
+        const { currentTick } = await program.account.pool.fetch(pool);
+        const swapTickArray = tickArray(getSwapTickArrayStartIndex(currentTick, aToB));
+
+        signature = await program.methods
+          .quoteSwap(new anchor.BN(amount), aToB)
+          .accountsStrict({ pool, tickArray: swapTickArray, token0, token1 })
+          .rpc(opts);
+        await record("quote_swap", scenario, token, signature);
+
+        signature = await program.methods
+          .swap(new anchor.BN(amount), aToB, new anchor.BN(0), false)
+          .accountsStrict({
+            signer: wallet.publicKey,
+            pool,
+            tickArray: swapTickArray,
+            user0,
+            user1,
+            nativeMint: null,
+            nativeSolAccount: null,
+            tokenVault0: vault0.publicKey,
+            tokenVault1: vault1.publicKey,
+            token0,
+            token1,
+            systemProgram: SystemProgram.programId,
+            tokenProgram,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .rpc(opts);
+        await record("swap", scenario, token, signature);
+      };
+      await swap(1_000, false, "b_to_a, 0 ticks crossed");
+      await swap(500, true, "a_to_b, 0 ticks crossed");
+      await swap(1_000_000_000, false, "b_to_a, 1 tick crossed");
+      assert.equal((await program.account.pool.fetch(pool)).currentTick, TICK_SPACING);
+      await swap(1_000_000_000, true, "a_to_b, 1 tick crossed");
+      assert.equal((await program.account.pool.fetch(pool)).currentTick, 0);
+
+      for (const range of ranges) {
+        signature = await program.methods
+          .closePosition(
+            range.upper,
+            range.lower,
+            getTickArrayStartIndex(range.lower),
+            getTickArrayStartIndex(range.upper),
+            false
+          )
+          .accountsStrict({
+            ...positionAccounts(range),
+            tickArrayBitmap,
+            systemProgram: SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .rpc(opts);
+        await record("close_position", range.name, token, signature);
+      }
+
+      for (const start of [getTickArrayStartIndex(TWO_ARRAYS.lower), getTickArrayStartIndex(TWO_ARRAYS.upper)]) {
+        const { payer } = await program.account.tickArrayState.fetch(tickArray(start));
+        signature = await program.methods
+          .closeTickArray(start)
+          .accountsStrict({ signer: wallet.publicKey, pool, tickArray: tickArray(start), tickArrayBitmap, payer })
+          .rpc(opts);
+        await record("close_tick_array", "-", token, signature);
+      }
+
+      signature = await program.methods
+        .setPoolStatus(0)
+        .accountsStrict({ admin: wallet.publicKey, globalConfig: globalConfigPda, pool })
+        .rpc(opts);
+      await record("set_pool_status", "-", token, signature);
+    });
+  }
+
+  after(() => {
+    if (rows.length === 0) {
+      return;
+    }
+    const lines = [
+      "# Compute units",
+      "",
+      `Generated by \`tests/compute_units.ts\`. Limits: ${INSTRUCTION_LIMIT} CU per instruction by default, ${TRANSACTION_LIMIT} per transaction.`,
+      "",
+      "| Instruction | Scenario | Token program | CU | % of default limit |",
+      "|---|---|---|---:|---:|",
+      ...rows.map(
+        (row) =>
+          `| ${row.instruction} | ${row.scenario} | ${row.token} | ${row.units} | ${((row.units / INSTRUCTION_LIMIT) * 100).toFixed(1)} |`
+      ),
+      "",
+    ];
+    fs.writeFileSync(OUTPUT, lines.join("\n"));
+    console.log("compute units written to", OUTPUT);
+  });
+});